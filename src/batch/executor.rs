@@ -0,0 +1,577 @@
+use std::time::Duration;
+
+use futures::StreamExt;
+use futures::stream::FuturesUnordered;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::time::Instant;
+
+use crate::Gateway;
+use crate::logging::access_log::AccessLogEntry;
+use crate::utils::model::{RequestContext, RequestType};
+use crate::utils::request_id::{self, REQUEST_ID_HEADER};
+
+/// Key of a placeholder object, `{"$batchRef": "/<item>/data/..."}`, that is
+/// replaced with a value from an earlier item's result. It is not `$ref` so
+/// payloads carrying JSON Schema references are left alone.
+pub const REF_KEY: &str = "$batchRef";
+
+#[derive(Debug, Clone)]
+pub struct BatchConfig {
+    pub max_items: usize,
+    /// calls of one batch running at the same time
+    pub max_concurrency: usize,
+    pub default_timeout: Duration,
+    /// upper bound for the timeout a client asks for
+    pub max_timeout: Duration,
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        Self {
+            max_items: 50,
+            max_concurrency: 10,
+            default_timeout: Duration::from_secs(10),
+            max_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BatchItem {
+    /// name later items use in their references, defaults to the index
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(flatten)]
+    pub request: RequestType,
+}
+
+/// Body of `POST /_batch`, either a bare array of items or an object with
+/// options.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum BatchRequest {
+    Items(Vec<BatchItem>),
+    WithOptions {
+        requests: Vec<BatchItem>,
+        #[serde(default)]
+        timeout_ms: Option<u64>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchItemResult {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub status: u16,
+    pub message: String,
+    pub data: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+impl BatchItemResult {
+    fn failed(id: Option<String>, status: u16, message: impl Into<String>) -> Self {
+        Self {
+            id,
+            status,
+            message: message.into(),
+            data: None,
+            request_id: None,
+        }
+    }
+
+    fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    // the JSON references point into
+    fn as_json(&self) -> Value {
+        serde_json::json!({
+            "status": self.status,
+            "message": self.message,
+            "data": self.data,
+        })
+    }
+}
+
+/// Runs the items of a batch. Items without references to each other run
+/// concurrently; an item referencing earlier results starts as soon as they
+/// are done and is skipped with `424` when one of them failed. Items still
+/// running when the batch times out are reported with `504`.
+pub async fn execute(
+    gateway: &Gateway,
+    request: BatchRequest,
+    ctx: RequestContext,
+) -> Result<Vec<BatchItemResult>, String> {
+    let config = &gateway.batch;
+    let (items, timeout_ms) = match request {
+        BatchRequest::Items(items) => (items, None),
+        BatchRequest::WithOptions {
+            requests,
+            timeout_ms,
+        } => (requests, timeout_ms),
+    };
+    if items.is_empty() {
+        return Err(String::from("batch is empty"));
+    }
+    if items.len() > config.max_items {
+        return Err(format!(
+            "batch holds {} requests, at most {} are allowed",
+            items.len(),
+            config.max_items
+        ));
+    }
+
+    let ids: Vec<String> = items
+        .iter()
+        .enumerate()
+        .map(|(i, item)| item.id.clone().unwrap_or_else(|| i.to_string()))
+        .collect();
+    // an explicit id may also be another item's index
+    if let Some(id) = ids
+        .iter()
+        .enumerate()
+        .find_map(|(i, id)| ids[..i].contains(id).then_some(id))
+    {
+        return Err(format!("request id `{}` is used more than once", id));
+    }
+    let mut dependencies = Vec::with_capacity(items.len());
+    for (i, item) in items.iter().enumerate() {
+        let mut refs = Vec::new();
+        collect_refs(&item.request.data, &mut refs);
+        let mut deps = Vec::new();
+        for reference in refs {
+            let target = reference_target(&reference)
+                .and_then(|name| ids[..i].iter().position(|id| *id == name))
+                .ok_or_else(|| {
+                    format!(
+                        "request {} references `{}`, which is not an earlier request",
+                        ids[i], reference
+                    )
+                })?;
+            deps.push(target);
+        }
+        dependencies.push(deps);
+    }
+
+    let timeout = timeout_ms
+        .map(Duration::from_millis)
+        .unwrap_or(config.default_timeout)
+        .min(config.max_timeout);
+    let deadline = Instant::now() + timeout;
+    // items are called with `{batch}-{item}` request ids
+    let batch_id = request_id::resolve(&ctx.headers);
+
+    let mut items: Vec<Option<BatchItem>> = items.into_iter().map(Some).collect();
+    let mut results: Vec<Option<BatchItemResult>> = vec![None; items.len()];
+    let mut running = FuturesUnordered::new();
+    let max_concurrency = config.max_concurrency.max(1);
+    loop {
+        // start every pending item whose dependencies have finished, as long
+        // as there is room; dependencies always come earlier, so one pass
+        // also settles the dependents of items skipped in it
+        for i in 0..items.len() {
+            if running.len() >= max_concurrency {
+                break;
+            }
+            if items[i].is_none() || !dependencies[i].iter().all(|&d| results[d].is_some()) {
+                continue;
+            }
+            let Some(mut item) = items[i].take() else {
+                continue;
+            };
+            if let Some(&failed) = dependencies[i]
+                .iter()
+                .find(|&&d| results[d].as_ref().is_some_and(|r| !r.is_success()))
+            {
+                results[i] = Some(BatchItemResult::failed(
+                    item.id,
+                    424,
+                    format!("dependency {} failed", ids[failed]),
+                ));
+                continue;
+            }
+
+            let resolved = {
+                let done: Vec<(String, Value)> = dependencies[i]
+                    .iter()
+                    .filter_map(|&d| results[d].as_ref().map(|r| (ids[d].clone(), r.as_json())))
+                    .collect();
+                resolve_refs(&mut item.request.data, &done)
+            };
+            if let Err(reference) = resolved {
+                results[i] = Some(BatchItemResult::failed(
+                    item.id,
+                    400,
+                    format!("reference `{}` did not resolve", reference),
+                ));
+                continue;
+            }
+
+            let mut ctx = ctx.clone();
+            ctx.headers.insert(
+                REQUEST_ID_HEADER.to_string(),
+                format!("{}-{}", batch_id, ids[i]),
+            );
+            running.push(async move { (i, call_item(gateway, item, ctx, deadline).await) });
+        }
+
+        match running.next().await {
+            Some((i, result)) => results[i] = Some(result),
+            None => break,
+        }
+    }
+
+    Ok(results.into_iter().flatten().collect())
+}
+
+// Calls one item, reporting it in the access log like any other call
+async fn call_item(
+    gateway: &Gateway,
+    item: BatchItem,
+    ctx: RequestContext,
+    deadline: Instant,
+) -> BatchItemResult {
+    let id = item.id;
+    let started_at = Instant::now();
+    let (service, method) = (
+        item.request.service.to_string(),
+        item.request.method.to_string(),
+    );
+    let request_body = gateway
+        .access_log
+        .include_request_body
+        .then(|| item.request.data.clone());
+    let client_ip = ctx.client_ip.clone();
+    let item_request_id = ctx.headers.get(REQUEST_ID_HEADER).cloned();
+    let call = gateway.invoke_with_context(item.request, ctx);
+    let result = match tokio::time::timeout_at(deadline, call).await {
+        Ok(response) => BatchItemResult {
+            id,
+            status: response.status_code.as_u16(),
+            message: response.message.to_string(),
+            data: response.data,
+            request_id: response.request_id,
+        },
+        Err(_) => BatchItemResult::failed(id, 504, "batch timed out"),
+    };
+    gateway.access_log.emit(AccessLogEntry {
+        request_id: result.request_id.clone().or(item_request_id),
+        service,
+        method,
+        status: result.status,
+        latency_ms: started_at.elapsed().as_secs_f64() * 1000.0,
+        bytes: AccessLogEntry::json_bytes(result.data.as_ref()),
+        client_ip,
+        request: request_body,
+    });
+    result
+}
+
+fn collect_refs(value: &Value, out: &mut Vec<String>) {
+    match value {
+        Value::Object(object) => match as_reference(value) {
+            Some(reference) => out.push(reference.to_string()),
+            None => object.values().for_each(|v| collect_refs(v, out)),
+        },
+        Value::Array(items) => items.iter().for_each(|v| collect_refs(v, out)),
+        _ => {}
+    }
+}
+
+// `{"$batchRef": "/item/data/id"}` and nothing else
+fn as_reference(value: &Value) -> Option<&str> {
+    let object = value.as_object()?;
+    if object.len() != 1 {
+        return None;
+    }
+    object
+        .get(REF_KEY)?
+        .as_str()
+        .filter(|reference| reference.starts_with('/'))
+}
+
+// the item a reference points into, its first pointer segment
+fn reference_target(reference: &str) -> Option<String> {
+    let segment = reference.strip_prefix('/')?.split('/').next()?;
+    Some(segment.replace("~1", "/").replace("~0", "~"))
+}
+
+fn resolve_refs(value: &mut Value, done: &[(String, Value)]) -> Result<(), String> {
+    if let Some(reference) = as_reference(value).map(str::to_string) {
+        let target = reference_target(&reference).unwrap_or_default();
+        let rest = &reference[1..]
+            .split_once('/')
+            .map(|(_, rest)| rest)
+            .unwrap_or("");
+        let resolved = done
+            .iter()
+            .find(|(id, _)| *id == target)
+            .and_then(|(_, result)| {
+                if rest.is_empty() {
+                    Some(result.clone())
+                } else {
+                    result.pointer(&format!("/{}", rest)).cloned()
+                }
+            })
+            .ok_or(reference)?;
+        *value = resolved;
+        return Ok(());
+    }
+
+    match value {
+        Value::Object(object) => object.values_mut().try_for_each(|v| resolve_refs(v, done)),
+        Value::Array(items) => items.iter_mut().try_for_each(|v| resolve_refs(v, done)),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    use async_trait::async_trait;
+    use serde_json::json;
+    use tonic::Code;
+    use tonic::metadata::MetadataMap;
+
+    use super::*;
+    use crate::middleware::chain::{Middleware, MiddlewareError};
+    use crate::mock::mock_mode::MockModeConfig;
+    use crate::utils::test_descriptors;
+
+    const USERS_PROTO: &str = r#"
+        name: "batch.proto" package: "batch" syntax: "proto3"
+        message_type {
+            name: "User"
+            field { name: "id" number: 1 type: TYPE_STRING label: LABEL_OPTIONAL json_name: "id" }
+            field { name: "display_name" number: 2 type: TYPE_STRING label: LABEL_OPTIONAL json_name: "displayName" }
+        }
+        service {
+            name: "Users"
+            method { name: "GetUser" input_type: ".batch.User" output_type: ".batch.User" }
+        }
+        service {
+            name: "SlowUsers"
+            method { name: "GetUser" input_type: ".batch.User" output_type: ".batch.User" }
+        }
+    "#;
+    const USERS: &str = "batch.Users";
+    const SLOW_USERS: &str = "batch.SlowUsers";
+
+    // requests as they reach the gateway's backend call
+    #[derive(Default)]
+    struct Seen(Mutex<Vec<Value>>);
+
+    #[async_trait]
+    impl Middleware for Seen {
+        async fn on_request(
+            &self,
+            req: &mut RequestType,
+            _metadata: &mut MetadataMap,
+        ) -> Result<(), MiddlewareError> {
+            self.0.lock().unwrap().push(req.data.clone());
+            Ok(())
+        }
+    }
+
+    fn gateway(config: MockModeConfig) -> (Gateway, Arc<Seen>) {
+        let gateway = Gateway::new();
+        gateway.mock.enable(
+            USERS,
+            MockModeConfig {
+                descriptors: Some(test_descriptors::pool(&[USERS_PROTO])),
+                ..config
+            },
+        );
+        let seen = Arc::new(Seen::default());
+        gateway.middleware.add_for_service(USERS, seen.clone());
+        (gateway, seen)
+    }
+
+    fn item(id: Option<&str>, data: Value) -> BatchItem {
+        BatchItem {
+            id: id.map(str::to_string),
+            request: RequestType {
+                service: USERS.to_string(),
+                method: "GetUser".to_string(),
+                data,
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn references_are_replaced_with_earlier_results() {
+        let (gateway, seen) = gateway(MockModeConfig::default());
+        let request = BatchRequest::Items(vec![
+            item(Some("user"), json!({ "id": "1" })),
+            item(
+                None,
+                json!({ "id": { "$batchRef": "/user/data/displayName" }, "tags": [{ "$batchRef": "/user/status" }] }),
+            ),
+        ]);
+
+        let results = execute(&gateway, request, RequestContext::default())
+            .await
+            .unwrap();
+
+        let display_name = results[0].data.as_ref().unwrap()["displayName"].clone();
+        assert_eq!(results[1].status, 200);
+        assert_eq!(
+            seen.0.lock().unwrap()[1],
+            json!({ "id": display_name, "tags": [200] })
+        );
+    }
+
+    #[tokio::test]
+    async fn schema_references_in_payloads_are_sent_as_they_are() {
+        let (gateway, seen) = gateway(MockModeConfig::default());
+        let data = json!({ "id": "1", "schema": { "$ref": "#/definitions/User" } });
+        let request = BatchRequest::Items(vec![item(None, data.clone())]);
+
+        let results = execute(&gateway, request, RequestContext::default())
+            .await
+            .unwrap();
+
+        assert_eq!(results[0].status, 200);
+        assert_eq!(seen.0.lock().unwrap()[0], data);
+    }
+
+    #[tokio::test]
+    async fn dependents_start_without_waiting_for_unrelated_calls() {
+        let (gateway, _) = gateway(MockModeConfig::default());
+        gateway.mock.enable(
+            SLOW_USERS,
+            MockModeConfig {
+                descriptors: Some(test_descriptors::pool(&[USERS_PROTO])),
+                latency: Duration::from_millis(300),
+                ..Default::default()
+            },
+        );
+        let slow = |data: Value| {
+            let mut slow = item(None, data);
+            slow.request.service = SLOW_USERS.to_string();
+            slow
+        };
+        // the slow dependent follows the fast call, so it should overlap the
+        // first slow call rather than run after it
+        let request = BatchRequest::Items(vec![
+            slow(json!({})),
+            item(Some("user"), json!({ "id": "1" })),
+            slow(json!({ "id": { "$batchRef": "/user/data/id" } })),
+        ]);
+
+        let started = Instant::now();
+        let results = execute(&gateway, request, RequestContext::default())
+            .await
+            .unwrap();
+
+        assert!(results.iter().all(|result| result.status == 200));
+        assert!(started.elapsed() < Duration::from_millis(550));
+    }
+
+    #[tokio::test]
+    async fn dependents_of_a_failed_call_are_skipped() {
+        let (gateway, seen) = gateway(MockModeConfig {
+            error_rate: 1.0,
+            error_code: Code::NotFound,
+            ..Default::default()
+        });
+        let request = BatchRequest::Items(vec![
+            item(Some("user"), json!({})),
+            item(None, json!({ "id": { "$batchRef": "/user/data/id" } })),
+        ]);
+
+        let results = execute(&gateway, request, RequestContext::default())
+            .await
+            .unwrap();
+
+        assert_eq!(results[1].status, 424);
+        assert_eq!(results[1].message, "dependency user failed");
+        assert_eq!(seen.0.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn calls_running_past_the_timeout_are_reported() {
+        let (gateway, _) = gateway(MockModeConfig {
+            latency: Duration::from_millis(500),
+            ..Default::default()
+        });
+        let request = BatchRequest::WithOptions {
+            requests: vec![item(None, json!({}))],
+            timeout_ms: Some(20),
+        };
+
+        let results = execute(&gateway, request, RequestContext::default())
+            .await
+            .unwrap();
+
+        assert_eq!(results[0].status, 504);
+    }
+
+    #[tokio::test]
+    async fn items_get_their_own_request_ids() {
+        let (gateway, _) = gateway(MockModeConfig::default());
+        let ctx = RequestContext {
+            headers: HashMap::from([(REQUEST_ID_HEADER.to_string(), "req-1".to_string())]),
+            ..Default::default()
+        };
+        let request =
+            BatchRequest::Items(vec![item(Some("user"), json!({})), item(None, json!({}))]);
+
+        let results = execute(&gateway, request, ctx).await.unwrap();
+
+        assert_eq!(results[0].request_id.as_deref(), Some("req-1-user"));
+        assert_eq!(results[1].request_id.as_deref(), Some("req-1-1"));
+    }
+
+    #[tokio::test]
+    async fn ids_must_be_unique() {
+        let (gateway, _) = gateway(MockModeConfig::default());
+        // the second item's index is also 1
+        let request = BatchRequest::Items(vec![item(Some("1"), json!({})), item(None, json!({}))]);
+
+        let error = execute(&gateway, request, RequestContext::default())
+            .await
+            .unwrap_err();
+
+        assert_eq!(error, "request id `1` is used more than once");
+    }
+
+    #[tokio::test]
+    async fn references_must_point_to_earlier_items() {
+        let (gateway, _) = gateway(MockModeConfig::default());
+        let request = BatchRequest::Items(vec![
+            item(None, json!({ "id": { "$batchRef": "/1/data/id" } })),
+            item(None, json!({})),
+        ]);
+
+        let error = execute(&gateway, request, RequestContext::default())
+            .await
+            .unwrap_err();
+
+        assert_eq!(
+            error,
+            "request 0 references `/1/data/id`, which is not an earlier request"
+        );
+    }
+
+    #[test]
+    fn unresolved_pointers_are_reported() {
+        let done = vec![("user".to_string(), json!({ "data": { "id": "1" } }))];
+        let mut value = json!({ "id": { "$batchRef": "/user/data/name" } });
+
+        assert_eq!(
+            resolve_refs(&mut value, &done),
+            Err("/user/data/name".to_string())
+        );
+    }
+
+    #[test]
+    fn escaped_ids_are_unescaped() {
+        assert_eq!(reference_target("/a~1b~0c/data").as_deref(), Some("a/b~c"));
+        assert_eq!(reference_target("user/data"), None);
+    }
+}
@@ -0,0 +1,58 @@
+//! Replays a traffic recording and prints the calls whose answer changed.
+//!
+//! ```text
+//! grpc-replay <recording.jsonl> (--gateway <base url> | --backend <grpc endpoint>)
+//! ```
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use grpc_gateway::recording::replay::{self, ReplayTarget};
+
+const USAGE: &str =
+    "usage: grpc-replay <recording.jsonl> (--gateway <base url> | --backend <grpc endpoint>)";
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (path, target) = match args.as_slice() {
+        [path, flag, url] if flag == "--gateway" => {
+            (PathBuf::from(path), ReplayTarget::Gateway(url.to_string()))
+        }
+        [path, flag, url] if flag == "--backend" => {
+            (PathBuf::from(path), ReplayTarget::Backend(url.to_string()))
+        }
+        _ => {
+            eprintln!("{}", USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    let calls = match replay::read_recording(&path) {
+        Ok(calls) => calls,
+        Err(e) => {
+            eprintln!("cannot read {}: {}", path.display(), e);
+            return ExitCode::from(2);
+        }
+    };
+    let results = replay::replay(&calls, &target).await;
+
+    let mut mismatches = 0;
+    for result in results.iter().filter(|result| !result.matches()) {
+        mismatches += 1;
+        match serde_json::to_string(result) {
+            Ok(line) => println!("{}", line),
+            Err(e) => eprintln!("cannot print result: {}", e),
+        }
+    }
+    eprintln!(
+        "{} calls replayed, {} mismatched",
+        results.len(),
+        mismatches
+    );
+    if mismatches == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
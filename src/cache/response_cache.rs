@@ -0,0 +1,316 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use lru::LruCache;
+use prost_reflect::MethodDescriptor;
+use prost_types::method_options::IdempotencyLevel;
+use serde_json::Value;
+
+use crate::gateway::json_format::JsonFormat;
+
+#[derive(Debug, Clone)]
+pub struct ResponseCacheConfig {
+    /// upper bound on cached entries
+    pub max_entries: usize,
+    /// upper bound on the summed size of cached JSON payloads
+    pub max_bytes: usize,
+    /// request headers that are part of the cache key, e.g. `accept-language`;
+    /// `authorization` is included by default so one caller's response is
+    /// never served to another
+    pub vary_headers: Vec<String>,
+}
+
+impl Default for ResponseCacheConfig {
+    fn default() -> Self {
+        Self {
+            max_entries: 10_000,
+            max_bytes: 64 * 1024 * 1024,
+            vary_headers: vec![String::from("authorization")],
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CachedResponse {
+    pub data: Value,
+    pub etag: String,
+    stored_at: Instant,
+    ttl: Duration,
+    size: usize,
+}
+
+impl CachedResponse {
+    pub fn remaining_ttl(&self) -> Duration {
+        self.ttl.saturating_sub(self.stored_at.elapsed())
+    }
+
+    fn is_fresh(&self) -> bool {
+        self.stored_at.elapsed() < self.ttl
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    service: String,
+    method: String,
+    request: Vec<u8>,
+    format: JsonFormat,
+    vary: Vec<(String, String)>,
+}
+
+#[derive(Debug, Default)]
+struct CacheRules {
+    // ttl per "service.method"
+    methods: HashMap<String, Duration>,
+    // ttl for every NO_SIDE_EFFECTS method of a service
+    idempotent_services: HashMap<String, Duration>,
+}
+
+struct CacheStore {
+    entries: LruCache<CacheKey, CachedResponse>,
+    bytes: usize,
+}
+
+/// Opt-in cache for responses of idempotent methods. Nothing is cached until
+/// a method or service is enabled.
+pub struct ResponseCache {
+    config: ResponseCacheConfig,
+    rules: RwLock<CacheRules>,
+    store: Mutex<CacheStore>,
+}
+
+impl std::fmt::Debug for ResponseCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ResponseCache")
+            .field("config", &self.config)
+            .field("rules", &self.rules)
+            .finish()
+    }
+}
+
+impl Default for ResponseCache {
+    fn default() -> Self {
+        Self::new(ResponseCacheConfig::default())
+    }
+}
+
+impl ResponseCache {
+    pub fn new(config: ResponseCacheConfig) -> Self {
+        Self {
+            config,
+            rules: RwLock::new(CacheRules::default()),
+            store: Mutex::new(CacheStore {
+                entries: LruCache::unbounded(),
+                bytes: 0,
+            }),
+        }
+    }
+
+    /// Caches responses of one method for `ttl`.
+    pub fn enable_method(&self, service: &str, method: &str, ttl: Duration) {
+        if let Ok(mut rules) = self.rules.write() {
+            rules.methods.insert(format!("{}.{}", service, method), ttl);
+        }
+    }
+
+    /// Caches responses of every method of `service` declared with
+    /// `option idempotency_level = NO_SIDE_EFFECTS`.
+    pub fn enable_idempotent_methods(&self, service: &str, ttl: Duration) {
+        if let Ok(mut rules) = self.rules.write() {
+            rules.idempotent_services.insert(service.to_string(), ttl);
+        }
+    }
+
+    pub fn disable_method(&self, service: &str, method: &str) {
+        if let Ok(mut rules) = self.rules.write() {
+            rules.methods.remove(&format!("{}.{}", service, method));
+        }
+    }
+
+    /// Returns the ttl for the method, `None` when it is not cacheable.
+    pub fn ttl_for(&self, service: &str, method: &MethodDescriptor) -> Option<Duration> {
+        let rules = self.rules.read().ok()?;
+        if let Some(ttl) = rules.methods.get(&format!("{}.{}", service, method.name())) {
+            return Some(*ttl);
+        }
+
+        let ttl = rules.idempotent_services.get(service)?;
+        let no_side_effects = method
+            .method_descriptor_proto()
+            .options
+            .as_ref()
+            .is_some_and(|o| o.idempotency_level() == IdempotencyLevel::NoSideEffects);
+        no_side_effects.then_some(*ttl)
+    }
+
+    pub fn key(
+        &self,
+        service: &str,
+        method: &str,
+        request: Vec<u8>,
+        format: JsonFormat,
+        headers: &HashMap<String, String>,
+    ) -> CacheKey {
+        let vary = self
+            .config
+            .vary_headers
+            .iter()
+            .map(|name| {
+                let name = name.to_lowercase();
+                let value = headers.get(&name).cloned().unwrap_or_default();
+                (name, value)
+            })
+            .collect();
+
+        CacheKey {
+            service: service.to_string(),
+            method: method.to_string(),
+            request,
+            format,
+            vary,
+        }
+    }
+
+    pub fn get(&self, key: &CacheKey) -> Option<CachedResponse> {
+        let mut store = self.store.lock().ok()?;
+        match store.entries.get(key) {
+            Some(entry) if entry.is_fresh() => Some(entry.clone()),
+            Some(_) => {
+                if let Some(stale) = store.entries.pop(key) {
+                    store.bytes -= stale.size;
+                }
+                None
+            }
+            None => None,
+        }
+    }
+
+    pub fn put(&self, key: CacheKey, data: Value, ttl: Duration) -> Option<CachedResponse> {
+        let body = serde_json::to_vec(&data).ok()?;
+        let size = body.len() + key.request.len();
+        if size > self.config.max_bytes {
+            return None;
+        }
+
+        let entry = CachedResponse {
+            data,
+            etag: etag(&body),
+            stored_at: Instant::now(),
+            ttl,
+            size,
+        };
+
+        let mut store = self.store.lock().ok()?;
+        if let Some(previous) = store.entries.put(key, entry.clone()) {
+            store.bytes -= previous.size;
+        }
+        store.bytes += size;
+
+        while store.entries.len() > self.config.max_entries || store.bytes > self.config.max_bytes {
+            match store.entries.pop_lru() {
+                Some((_, evicted)) => store.bytes -= evicted.size,
+                None => break,
+            }
+        }
+        Some(entry)
+    }
+
+    /// Drops cached responses; `None` acts as a wildcard.
+    pub fn purge(&self, service: Option<&str>, method: Option<&str>) -> usize {
+        let Ok(mut store) = self.store.lock() else {
+            return 0;
+        };
+
+        let keys: Vec<CacheKey> = store
+            .entries
+            .iter()
+            .filter(|(key, _)| {
+                service.is_none_or(|s| key.service == s) && method.is_none_or(|m| key.method == m)
+            })
+            .map(|(key, _)| key.clone())
+            .collect();
+
+        for key in &keys {
+            if let Some(entry) = store.entries.pop(key) {
+                store.bytes -= entry.size;
+            }
+        }
+        keys.len()
+    }
+}
+
+// FNV-1a, stable across gateway instances so ETags survive load balancing
+fn etag(body: &[u8]) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in body {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("\"{:016x}\"", hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(cache: &ResponseCache, headers: &[(&str, &str)]) -> CacheKey {
+        let headers = headers
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        cache.key(
+            "catalog.Products",
+            "GetProduct",
+            vec![1, 2, 3],
+            JsonFormat::default(),
+            &headers,
+        )
+    }
+
+    #[test]
+    fn callers_with_different_credentials_do_not_share_entries() {
+        let cache = ResponseCache::default();
+        cache.put(
+            key(&cache, &[("authorization", "Bearer alice")]),
+            serde_json::json!({ "owner": "alice" }),
+            Duration::from_secs(60),
+        );
+
+        assert!(
+            cache
+                .get(&key(&cache, &[("authorization", "Bearer alice")]))
+                .is_some()
+        );
+        assert!(
+            cache
+                .get(&key(&cache, &[("authorization", "Bearer bob")]))
+                .is_none()
+        );
+        assert!(cache.get(&key(&cache, &[])).is_none());
+    }
+
+    #[test]
+    fn evicts_the_least_recently_used_entry() {
+        let cache = ResponseCache::new(ResponseCacheConfig {
+            max_entries: 1,
+            ..Default::default()
+        });
+        let first = key(&cache, &[("authorization", "a")]);
+        let second = key(&cache, &[("authorization", "b")]);
+
+        cache.put(
+            first.clone(),
+            serde_json::json!({}),
+            Duration::from_secs(60),
+        );
+        cache.put(
+            second.clone(),
+            serde_json::json!({}),
+            Duration::from_secs(60),
+        );
+
+        assert!(cache.get(&first).is_none());
+        assert!(cache.get(&second).is_some());
+    }
+}
@@ -55,6 +55,10 @@ impl CircuitBreaker {
         }
     }
 
+    pub async fn state(&self) -> CircuitBreakerState {
+        self.state.read().await.current_state.clone()
+    }
+
     pub async fn is_allowed(&self) -> bool {
         let mut state = self.state.write().await;
 
@@ -62,6 +66,7 @@ impl CircuitBreaker {
             CircuitBreakerState::Closed => true,
             CircuitBreakerState::Open { opened_at } => {
                 if opened_at.elapsed() >= self.config.recovery_timeout {
+                    tracing::info!("circuit breaker half-open, allowing trial calls");
                     state.current_state = CircuitBreakerState::HalfOpen;
                     state.half_open_request = 0;
                     state.success_half_open_request = 0;
@@ -102,6 +107,7 @@ impl CircuitBreaker {
 
         match state.current_state {
             CircuitBreakerState::HalfOpen => {
+                tracing::warn!("trial call failed, circuit breaker re-opened");
                 state.current_state = CircuitBreakerState::Open {
                     opened_at: Instant::now(),
                 };
@@ -112,6 +118,10 @@ impl CircuitBreaker {
                 state.failure_count += 1;
 
                 if state.failure_count >= self.config.failure_threshold {
+                    tracing::warn!(
+                        failures = state.failure_count,
+                        "failure threshold reached, circuit breaker opened"
+                    );
                     state.current_state = CircuitBreakerState::Open {
                         opened_at: Instant::now(),
                     };
@@ -122,6 +132,9 @@ impl CircuitBreaker {
 
     // Internal method that operates on already-acquired lock
     async fn close_internal(&self, state: &mut CircuitBreakerInternalState) {
+        if state.current_state != CircuitBreakerState::Closed {
+            tracing::info!("circuit breaker closed");
+        }
         state.current_state = CircuitBreakerState::Closed;
         state.half_open_request = 0;
         state.success_half_open_request = 0;
@@ -134,6 +147,7 @@ impl CircuitBreaker {
         Fut: std::future::Future<Output = Result<T, anyhow::Error>>,
     {
         if !self.is_allowed().await {
+            tracing::debug!("circuit breaker rejected call");
             return Err(anyhow::anyhow!(
                 ResponseErrors::ServiceUnAvailable.to_string()
             ));
@@ -0,0 +1 @@
+pub mod single_flight;
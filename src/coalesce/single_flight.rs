@@ -0,0 +1,245 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, RwLock};
+
+use serde_json::Value;
+use tokio::sync::watch;
+
+use crate::gateway::json_format::JsonFormat;
+
+/// Shared outcome of a coalesced call, errors are shared by every waiter.
+pub type FlightResult = Result<Value, Arc<anyhow::Error>>;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FlightKey {
+    service: String,
+    method: String,
+    request: Vec<u8>,
+    format: JsonFormat,
+}
+
+impl FlightKey {
+    pub fn new(service: &str, method: &str, request: Vec<u8>, format: JsonFormat) -> Self {
+        Self {
+            service: service.to_string(),
+            method: method.to_string(),
+            request,
+            format,
+        }
+    }
+}
+
+type Flights = Mutex<HashMap<FlightKey, watch::Sender<Option<FlightResult>>>>;
+
+/// Deduplicates identical concurrent calls to opted-in methods: the first
+/// caller runs the backend call and every caller that arrives while it is in
+/// flight receives the same result.
+#[derive(Debug, Default)]
+pub struct SingleFlight {
+    methods: RwLock<HashSet<String>>,
+    in_flight: Arc<Flights>,
+}
+
+impl SingleFlight {
+    /// Only enable this for methods without side effects.
+    pub fn enable_method(&self, service: &str, method: &str) {
+        if let Ok(mut methods) = self.methods.write() {
+            methods.insert(format!("{}.{}", service, method));
+        }
+    }
+
+    pub fn disable_method(&self, service: &str, method: &str) {
+        if let Ok(mut methods) = self.methods.write() {
+            methods.remove(&format!("{}.{}", service, method));
+        }
+    }
+
+    pub fn is_enabled(&self, service: &str, method: &str) -> bool {
+        self.methods
+            .read()
+            .is_ok_and(|methods| methods.contains(&format!("{}.{}", service, method)))
+    }
+
+    pub async fn execute<F, Fut>(&self, key: FlightKey, call: F) -> FlightResult
+    where
+        F: FnOnce() -> Fut,
+        Fut: std::future::Future<Output = anyhow::Result<Value>>,
+    {
+        let follower = {
+            let mut flights = self.in_flight.lock().unwrap();
+            match flights.get(&key) {
+                Some(sender) => Err(sender.subscribe()),
+                None => {
+                    let sender = watch::channel(None).0;
+                    flights.insert(key.clone(), sender.clone());
+                    Ok(sender)
+                }
+            }
+        };
+
+        let sender = match follower {
+            Ok(sender) => sender,
+            Err(receiver) => return follow(receiver, call).await,
+        };
+        let guard = LeaderGuard {
+            flights: self.in_flight.clone(),
+            flight: Some((key, sender)),
+        };
+        let result = call().await.map_err(Arc::new);
+        guard.publish(result.clone());
+        result
+    }
+}
+
+// Waits for the leader's result, running the call itself when the leader is
+// cancelled before finishing
+async fn follow<F, Fut>(
+    mut receiver: watch::Receiver<Option<FlightResult>>,
+    call: F,
+) -> FlightResult
+where
+    F: FnOnce() -> Fut,
+    Fut: std::future::Future<Output = anyhow::Result<Value>>,
+{
+    loop {
+        if let Some(result) = receiver.borrow_and_update().clone() {
+            return result;
+        }
+        if receiver.changed().await.is_err() {
+            break;
+        }
+    }
+    call().await.map_err(Arc::new)
+}
+
+// Removes the flight when the leader finishes or is dropped mid-call
+struct LeaderGuard {
+    flights: Arc<Flights>,
+    // taken once the result is published
+    flight: Option<(FlightKey, watch::Sender<Option<FlightResult>>)>,
+}
+
+impl LeaderGuard {
+    fn publish(mut self, result: FlightResult) {
+        if let Some((key, sender)) = self.flight.take() {
+            remove_flight(&self.flights, &key, &sender);
+            sender.send_replace(Some(result));
+        }
+    }
+}
+
+impl Drop for LeaderGuard {
+    fn drop(&mut self) {
+        if let Some((key, sender)) = self.flight.take() {
+            remove_flight(&self.flights, &key, &sender);
+        }
+    }
+}
+
+// Removes the leader's own flight, never one a later leader started
+fn remove_flight(flights: &Flights, key: &FlightKey, sender: &watch::Sender<Option<FlightResult>>) {
+    if let Ok(mut flights) = flights.lock()
+        && flights
+            .get(key)
+            .is_some_and(|current| current.same_channel(sender))
+    {
+        flights.remove(key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    fn key() -> FlightKey {
+        FlightKey::new(
+            "catalog.Products",
+            "GetProduct",
+            vec![1],
+            JsonFormat::default(),
+        )
+    }
+
+    async fn slow_call(calls: &AtomicUsize) -> anyhow::Result<Value> {
+        let n = calls.fetch_add(1, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        Ok(serde_json::json!({ "call": n }))
+    }
+
+    #[tokio::test]
+    async fn concurrent_callers_share_one_call() {
+        let flight = SingleFlight::default();
+        let calls = AtomicUsize::new(0);
+
+        let results =
+            futures::future::join_all((0..10).map(|_| flight.execute(key(), || slow_call(&calls))))
+                .await;
+
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert!(
+            results
+                .iter()
+                .all(|result| result.as_ref().unwrap() == &serde_json::json!({ "call": 0 }))
+        );
+        assert!(flight.in_flight.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn a_finished_leader_leaves_the_next_flight_alone() {
+        let flight = SingleFlight::default();
+        let calls = AtomicUsize::new(0);
+
+        // a burst arriving after the first flight finished shares one new call
+        let (first, second) = tokio::join!(flight.execute(key(), || slow_call(&calls)), async {
+            tokio::time::sleep(Duration::from_millis(60)).await;
+            futures::future::join_all((0..5).map(|_| flight.execute(key(), || slow_call(&calls))))
+                .await
+        });
+
+        assert_eq!(first.unwrap(), serde_json::json!({ "call": 0 }));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert!(
+            second
+                .iter()
+                .all(|result| result.as_ref().unwrap() == &serde_json::json!({ "call": 1 }))
+        );
+    }
+
+    #[test]
+    fn a_stale_guard_does_not_remove_a_newer_flight() {
+        let flights: Arc<Flights> = Arc::default();
+        let stale = watch::channel(None).0;
+        let current = watch::channel(None).0;
+        flights.lock().unwrap().insert(key(), current.clone());
+
+        drop(LeaderGuard {
+            flights: flights.clone(),
+            flight: Some((key(), stale)),
+        });
+
+        let flights = flights.lock().unwrap();
+        assert!(flights[&key()].same_channel(&current));
+    }
+
+    #[tokio::test]
+    async fn a_follower_takes_over_from_a_cancelled_leader() {
+        let flight = SingleFlight::default();
+        let calls = AtomicUsize::new(0);
+
+        let (cancelled, follower) = tokio::join!(
+            tokio::time::timeout(
+                Duration::from_millis(10),
+                flight.execute(key(), || slow_call(&calls))
+            ),
+            async {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+                flight.execute(key(), || slow_call(&calls)).await
+            }
+        );
+
+        assert!(cancelled.is_err());
+        assert_eq!(follower.unwrap(), serde_json::json!({ "call": 1 }));
+        assert!(flight.in_flight.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,388 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+use tokio::sync::Notify;
+use tokio::time::Instant;
+
+/// How the concurrency limit of a backend evolves over time.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LimitStrategy {
+    /// `max_concurrency` is a hard cap
+    Fixed,
+    /// additive increase / multiplicative decrease: the limit grows by one
+    /// after each fast, successful call and is multiplied by `backoff_ratio`
+    /// when the backend fails or times out, or a call exceeds
+    /// `latency_threshold`. Client errors leave the limit unchanged.
+    Aimd {
+        min_limit: usize,
+        latency_threshold: Duration,
+        backoff_ratio: f64,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConcurrencyConfig {
+    /// in-flight calls allowed (upper bound for adaptive strategies)
+    pub max_concurrency: usize,
+    /// calls allowed to wait for a free slot before new ones are shed
+    pub max_queue: usize,
+    /// how long a queued call waits before being shed
+    pub queue_timeout: Duration,
+    pub strategy: LimitStrategy,
+}
+
+impl Default for ConcurrencyConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrency: 100,
+            max_queue: 100,
+            queue_timeout: Duration::from_secs(1),
+            strategy: LimitStrategy::Fixed,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ShedReason {
+    QueueFull,
+    QueueTimeout,
+}
+
+impl ShedReason {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ShedReason::QueueFull => "queue_full",
+            ShedReason::QueueTimeout => "queue_timeout",
+        }
+    }
+}
+
+impl fmt::Display for ShedReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShedReason::QueueFull => f.write_str("queue is full"),
+            ShedReason::QueueTimeout => f.write_str("timed out waiting in queue"),
+        }
+    }
+}
+
+impl std::error::Error for ShedReason {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CallOutcome {
+    // dropped without an outcome, e.g. rejected before reaching the backend
+    Unreported,
+    Succeeded,
+    Failed,
+}
+
+#[derive(Debug)]
+struct LimiterState {
+    limit: usize,
+    in_flight: usize,
+    queued: usize,
+}
+
+#[derive(Debug)]
+pub struct ConcurrencyLimiter {
+    config: ConcurrencyConfig,
+    state: Mutex<LimiterState>,
+    released: Notify,
+}
+
+impl ConcurrencyLimiter {
+    pub fn new(config: ConcurrencyConfig) -> Self {
+        Self {
+            state: Mutex::new(LimiterState {
+                limit: config.max_concurrency.max(1),
+                in_flight: 0,
+                queued: 0,
+            }),
+            config,
+            released: Notify::new(),
+        }
+    }
+
+    pub fn current_limit(&self) -> usize {
+        self.state.lock().map(|s| s.limit).unwrap_or_default()
+    }
+
+    /// Waits for a free slot, queueing up to `queue_timeout` when the backend
+    /// is at its limit.
+    pub async fn acquire(self: &Arc<Self>) -> Result<ConcurrencyPermit, ShedReason> {
+        let deadline = Instant::now() + self.config.queue_timeout;
+        let mut queued: Option<QueueSlot> = None;
+
+        loop {
+            // register interest before checking so a release cannot be missed
+            let released = self.released.notified();
+            tokio::pin!(released);
+            released.as_mut().enable();
+
+            {
+                let mut state = self.state.lock().unwrap();
+                if state.in_flight < state.limit {
+                    state.in_flight += 1;
+                    if let Some(slot) = queued.take() {
+                        slot.leave(&mut state);
+                    }
+                    return Ok(ConcurrencyPermit {
+                        limiter: self.clone(),
+                        started_at: Instant::now(),
+                        outcome: CallOutcome::Unreported,
+                    });
+                }
+                if queued.is_none() {
+                    if state.queued >= self.config.max_queue {
+                        return Err(ShedReason::QueueFull);
+                    }
+                    state.queued += 1;
+                    queued = Some(QueueSlot {
+                        limiter: self,
+                        left: false,
+                    });
+                }
+            }
+
+            if tokio::time::timeout_at(deadline, released).await.is_err() {
+                return Err(ShedReason::QueueTimeout);
+            }
+        }
+    }
+
+    fn release(&self, latency: Duration, outcome: CallOutcome) {
+        if let Ok(mut state) = self.state.lock() {
+            state.in_flight -= 1;
+
+            if let LimitStrategy::Aimd {
+                min_limit,
+                latency_threshold,
+                backoff_ratio,
+            } = &self.config.strategy
+            {
+                let overloaded = match outcome {
+                    CallOutcome::Unreported => None,
+                    CallOutcome::Succeeded => Some(latency > *latency_threshold),
+                    CallOutcome::Failed => Some(true),
+                };
+                match overloaded {
+                    Some(false) => {
+                        state.limit = (state.limit + 1).min(self.config.max_concurrency.max(1));
+                    }
+                    Some(true) => {
+                        let reduced = (state.limit as f64 * backoff_ratio).floor() as usize;
+                        state.limit = reduced.max(*min_limit).max(1);
+                    }
+                    None => {}
+                }
+            }
+        }
+        self.released.notify_waiters();
+    }
+}
+
+// Place of a call waiting in the queue, given back on drop so calls that
+// time out or are cancelled while waiting do not keep it
+struct QueueSlot<'a> {
+    limiter: &'a ConcurrencyLimiter,
+    left: bool,
+}
+
+impl QueueSlot<'_> {
+    // gives the place back while the state is already locked
+    fn leave(mut self, state: &mut LimiterState) {
+        state.queued -= 1;
+        self.left = true;
+    }
+}
+
+impl Drop for QueueSlot<'_> {
+    fn drop(&mut self) {
+        if !self.left
+            && let Ok(mut state) = self.limiter.state.lock()
+        {
+            state.queued -= 1;
+        }
+    }
+}
+
+/// Slot held for the duration of a backend call, released on drop.
+pub struct ConcurrencyPermit {
+    limiter: Arc<ConcurrencyLimiter>,
+    started_at: Instant,
+    outcome: CallOutcome,
+}
+
+impl ConcurrencyPermit {
+    /// Marks the call as successful so adaptive limits may grow.
+    pub fn succeeded(&mut self) {
+        self.outcome = CallOutcome::Succeeded;
+    }
+
+    /// Marks a backend fault or timeout so adaptive limits shrink. Permits
+    /// dropped without an outcome leave the limit unchanged.
+    pub fn failed(&mut self) {
+        self.outcome = CallOutcome::Failed;
+    }
+}
+
+impl Drop for ConcurrencyPermit {
+    fn drop(&mut self) {
+        self.limiter
+            .release(self.started_at.elapsed(), self.outcome);
+    }
+}
+
+/// Per-service concurrency limiters, services without a limit are unbounded.
+#[derive(Debug, Default)]
+pub struct ConcurrencyLimits {
+    limiters: RwLock<HashMap<String, Arc<ConcurrencyLimiter>>>,
+}
+
+impl ConcurrencyLimits {
+    pub fn set_service_limit(&self, service: &str, config: ConcurrencyConfig) {
+        if let Ok(mut limiters) = self.limiters.write() {
+            limiters.insert(
+                service.to_string(),
+                Arc::new(ConcurrencyLimiter::new(config)),
+            );
+        }
+    }
+
+    pub fn remove_service_limit(&self, service: &str) {
+        if let Ok(mut limiters) = self.limiters.write() {
+            limiters.remove(service);
+        }
+    }
+
+    pub fn get(&self, service: &str) -> Option<Arc<ConcurrencyLimiter>> {
+        self.limiters
+            .read()
+            .ok()
+            .and_then(|limiters| limiters.get(service).cloned())
+    }
+
+    /// Returns `Ok(None)` when the service has no concurrency limit.
+    pub async fn acquire(&self, service: &str) -> Result<Option<ConcurrencyPermit>, ShedReason> {
+        match self.get(service) {
+            Some(limiter) => limiter.acquire().await.map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn aimd() -> Arc<ConcurrencyLimiter> {
+        Arc::new(ConcurrencyLimiter::new(ConcurrencyConfig {
+            max_concurrency: 10,
+            strategy: LimitStrategy::Aimd {
+                min_limit: 1,
+                latency_threshold: Duration::from_millis(20),
+                backoff_ratio: 0.5,
+            },
+            ..Default::default()
+        }))
+    }
+
+    #[tokio::test]
+    async fn backend_failures_shrink_the_limit() {
+        let limiter = aimd();
+
+        limiter.acquire().await.unwrap().failed();
+
+        assert_eq!(limiter.current_limit(), 5);
+    }
+
+    #[tokio::test]
+    async fn calls_without_an_outcome_leave_the_limit() {
+        let limiter = aimd();
+
+        drop(limiter.acquire().await.unwrap());
+
+        assert_eq!(limiter.current_limit(), 10);
+    }
+
+    #[tokio::test]
+    async fn fast_successes_grow_the_limit_back() {
+        let limiter = aimd();
+        limiter.acquire().await.unwrap().failed();
+
+        limiter.acquire().await.unwrap().succeeded();
+
+        assert_eq!(limiter.current_limit(), 6);
+    }
+
+    #[tokio::test]
+    async fn slow_successes_shrink_the_limit() {
+        let limiter = aimd();
+
+        let mut permit = limiter.acquire().await.unwrap();
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        permit.succeeded();
+        drop(permit);
+
+        assert_eq!(limiter.current_limit(), 5);
+    }
+
+    #[tokio::test]
+    async fn sheds_when_the_queue_is_full() {
+        let limiter = Arc::new(ConcurrencyLimiter::new(ConcurrencyConfig {
+            max_concurrency: 1,
+            max_queue: 0,
+            ..Default::default()
+        }));
+
+        let _held = limiter.acquire().await.unwrap();
+
+        assert_eq!(limiter.acquire().await.err(), Some(ShedReason::QueueFull));
+    }
+
+    #[tokio::test]
+    async fn cancelled_waiters_give_their_queue_place_back() {
+        let limiter = Arc::new(ConcurrencyLimiter::new(ConcurrencyConfig {
+            max_concurrency: 1,
+            max_queue: 1,
+            queue_timeout: Duration::from_secs(5),
+            ..Default::default()
+        }));
+        let held = limiter.acquire().await.unwrap();
+
+        let cancelled = tokio::time::timeout(Duration::from_millis(20), limiter.acquire()).await;
+        let waiting = {
+            let limiter = limiter.clone();
+            tokio::spawn(async move { limiter.acquire().await.map(drop) })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        drop(held);
+
+        assert!(cancelled.is_err());
+        assert_eq!(waiting.await.unwrap(), Ok(()));
+        assert_eq!(limiter.state.lock().unwrap().queued, 0);
+    }
+
+    #[tokio::test]
+    async fn timed_out_waiters_give_their_queue_place_back() {
+        let limiter = Arc::new(ConcurrencyLimiter::new(ConcurrencyConfig {
+            max_concurrency: 1,
+            max_queue: 1,
+            queue_timeout: Duration::from_millis(10),
+            ..Default::default()
+        }));
+        let _held = limiter.acquire().await.unwrap();
+
+        assert_eq!(
+            limiter.acquire().await.err(),
+            Some(ShedReason::QueueTimeout)
+        );
+        assert_eq!(
+            limiter.acquire().await.err(),
+            Some(ShedReason::QueueTimeout)
+        );
+        assert_eq!(limiter.state.lock().unwrap().queued, 0);
+    }
+}
@@ -27,7 +27,7 @@ impl CachedDescriptors {
     }
 
     pub async fn load_discriptor(&mut self, channel: Channel) -> Result<()> {
-        println!("loading discriptor..");
+        tracing::debug!("loading descriptors via server reflection");
         let mut reflection_client = ServerReflectionClient::new(channel);
 
         // get list of services first
@@ -49,11 +49,11 @@ impl CachedDescriptors {
 
         // Extract service names
         while let Some(resp) = response_stream.message().await? {
-            if let Some(message_response) = resp.message_response {
-                if let tonic_reflection::pb::v1::server_reflection_response::MessageResponse::ListServicesResponse(services_resp) = message_response {
-                    for service in services_resp.service {
-                        service_names.push(service.name);
-                    }
+            if let Some(message_response) = resp.message_response
+                && let tonic_reflection::pb::v1::server_reflection_response::MessageResponse::ListServicesResponse(services_resp) = message_response
+            {
+                for service in services_resp.service {
+                    service_names.push(service.name);
                 }
             }
         }
@@ -76,12 +76,12 @@ impl CachedDescriptors {
             let mut response_stream = response.into_inner();
 
             while let Some(resp) = response_stream.message().await? {
-                if let Some(message_response) = resp.message_response {
-                    if let tonic_reflection::pb::v1::server_reflection_response::MessageResponse::FileDescriptorResponse(fd_resp) = message_response {
-                        for fd_bytes in fd_resp.file_descriptor_proto {
-                            let file_descriptor = FileDescriptorProto::decode(fd_bytes.as_slice())?;
-                            all_file_descriptors.push(file_descriptor);
-                        }
+                if let Some(message_response) = resp.message_response
+                    && let tonic_reflection::pb::v1::server_reflection_response::MessageResponse::FileDescriptorResponse(fd_resp) = message_response
+                {
+                    for fd_bytes in fd_resp.file_descriptor_proto {
+                        let file_descriptor = FileDescriptorProto::decode(fd_bytes.as_slice())?;
+                        all_file_descriptors.push(file_descriptor);
                     }
                 }
             }
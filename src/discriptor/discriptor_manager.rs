@@ -9,11 +9,13 @@ use tokio::time::Instant;
 use tonic::transport::Channel;
 
 use crate::discriptor;
+use crate::metrics::gateway_metrics;
 
 use discriptor::discriptor::CachedDescriptors;
 
 #[derive(Debug)]
 pub struct ReflectionDiscriptorManager {
+    pub endpoint: String,
     pub cache: Arc<RwLock<CachedDescriptors>>,
     pub channel: Channel,
     pub refresh_interval: Duration,
@@ -27,6 +29,7 @@ impl ReflectionDiscriptorManager {
             .await?;
 
         let manager = Self {
+            endpoint: endpoint.to_string(),
             cache: Arc::new(RwLock::new(CachedDescriptors::new())),
             channel,
             refresh_interval: Duration::from_secs(300), // 5 -min
@@ -39,21 +42,27 @@ impl ReflectionDiscriptorManager {
         Ok(manager)
     }
 
+    #[tracing::instrument(name = "descriptor.refresh", skip(self), fields(endpoint = %self.endpoint))]
     pub async fn refresh_discriptors(&self) -> Result<()> {
         // Build a fresh descriptor cache without holding locks across await
         let mut new_cache = CachedDescriptors::new();
-        new_cache.load_discriptor(self.channel.clone()).await?;
+        let loaded = new_cache.load_discriptor(self.channel.clone()).await;
+        gateway_metrics::record_descriptor_refresh(&self.endpoint, loaded.is_ok());
+        if let Err(e) = &loaded {
+            tracing::error!(error = %e, "descriptor refresh failed");
+        }
+        loaded?;
 
         {
             let mut cache_guard = self.cache.write().unwrap();
             *cache_guard = new_cache;
         }
 
-        // Debug: print discovered services to help diagnose mismatches
+        // Log discovered services to help diagnose mismatches
         {
             let cache = self.cache.read().unwrap();
             let services = cache.get_all_service();
-            println!("[descriptor] loaded services: {:?}", services);
+            tracing::info!(services = ?services, "descriptors loaded");
         }
 
         let mut refresh = self.last_refresh.write().unwrap();
@@ -1,2 +1,3 @@
+#[allow(clippy::module_inception)]
 pub mod discriptor;
 pub mod discriptor_manager;
@@ -50,10 +50,8 @@ impl Decoder for BytesDecoder {
     type Error = tonic::Status;
 
     fn decode(&mut self, src: &mut DecodeBuf<'_>) -> Result<Option<Self::Item>, Self::Error> {
+        // called once per frame, an empty frame is the empty message
         let len = src.remaining();
-        if len == 0 {
-            return Ok(None);
-        }
         let mut buf = vec![0u8; len];
         src.copy_to_slice(&mut buf);
         Ok(Some(Bytes::from(buf)))
@@ -99,14 +97,11 @@ impl DynamicGrpcClient {
 
         // Extract service names
         while let Some(resp) = response_stream.message().await? {
-            if let Some(message_response) = resp.message_response {
-                match message_response {
-                    tonic_reflection::pb::v1::server_reflection_response::MessageResponse::ListServicesResponse(services_resp) => {
-                        for service in services_resp.service {
-                            service_names.push(service.name);
-                        }
-                    }
-                    _ => {}
+            if let Some(message_response) = resp.message_response
+                && let tonic_reflection::pb::v1::server_reflection_response::MessageResponse::ListServicesResponse(services_resp) = message_response
+            {
+                for service in services_resp.service {
+                    service_names.push(service.name);
                 }
             }
         }
@@ -129,12 +124,12 @@ impl DynamicGrpcClient {
             let mut response_stream = response.into_inner();
 
             while let Some(resp) = response_stream.message().await? {
-                if let Some(message_response) = resp.message_response {
-                    if let tonic_reflection::pb::v1::server_reflection_response::MessageResponse::FileDescriptorResponse(fd_resp) = message_response {
-                        for fd_bytes in fd_resp.file_descriptor_proto {
-                            let file_descriptor = FileDescriptorProto::decode(fd_bytes.as_slice())?;
-                            all_file_descriptors.push(file_descriptor);
-                        }
+                if let Some(message_response) = resp.message_response
+                    && let tonic_reflection::pb::v1::server_reflection_response::MessageResponse::FileDescriptorResponse(fd_resp) = message_response
+                {
+                    for fd_bytes in fd_resp.file_descriptor_proto {
+                        let file_descriptor = FileDescriptorProto::decode(fd_bytes.as_slice())?;
+                        all_file_descriptors.push(file_descriptor);
                     }
                 }
             }
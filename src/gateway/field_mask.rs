@@ -0,0 +1,312 @@
+use std::collections::HashSet;
+use std::sync::RwLock;
+
+use prost_reflect::{DynamicMessage, FieldDescriptor, MessageDescriptor, ReflectMessage, Value};
+
+/// Query parameter listing the response fields to return, e.g.
+/// `?fields=name,address.city`.
+pub const FIELDS_PARAM: &str = "fields";
+
+const FIELD_MASK_TYPE: &str = "google.protobuf.FieldMask";
+
+/// Response projection. Paths are dot separated and may use either the
+/// `.proto` or the JSON field names; an empty mask keeps every field.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct FieldMask {
+    paths: Vec<String>,
+}
+
+impl FieldMask {
+    /// Parses a comma separated list of paths.
+    pub fn parse(paths: &str) -> Self {
+        Self::new(paths.split(','))
+    }
+
+    pub fn new<I, S>(paths: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        Self {
+            paths: paths
+                .into_iter()
+                .map(|p| p.as_ref().trim().to_string())
+                .filter(|p| !p.is_empty())
+                .collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+
+    pub fn paths(&self) -> &[String] {
+        &self.paths
+    }
+
+    /// Clears every field of `message` that is not covered by the mask.
+    /// Paths naming unknown fields are ignored.
+    pub fn apply(&self, message: &mut DynamicMessage) {
+        if self.paths.is_empty() {
+            return;
+        }
+        let paths: Vec<&str> = self.paths.iter().map(String::as_str).collect();
+        retain_paths(message, &paths);
+    }
+
+    /// The mask in the JSON form of `google.protobuf.FieldMask`, with
+    /// lowerCamelCase paths.
+    pub fn to_json_string(&self) -> String {
+        self.paths
+            .iter()
+            .map(|path| {
+                path.split('.')
+                    .map(lower_camel_case)
+                    .collect::<Vec<_>>()
+                    .join(".")
+            })
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    /// Reads the mask a client put in the request's field mask field.
+    pub fn from_request(input: &MessageDescriptor, data: &serde_json::Value) -> Option<Self> {
+        let field = read_mask_field(input)?;
+        let paths = data
+            .get(field.json_name())
+            .or_else(|| data.get(field.name()))?
+            .as_str()?;
+        let mask = Self::parse(paths);
+        (!mask.is_empty()).then_some(mask)
+    }
+}
+
+/// The input field a backend reads its read mask from: the first
+/// `google.protobuf.FieldMask` field that is not an `update_mask`.
+pub fn read_mask_field(input: &MessageDescriptor) -> Option<FieldDescriptor> {
+    input.fields().find(|field| {
+        !field.is_list()
+            && field.name() != "update_mask"
+            && field
+                .kind()
+                .as_message()
+                .is_some_and(|m| m.full_name() == FIELD_MASK_TYPE)
+    })
+}
+
+fn retain_paths(message: &mut DynamicMessage, paths: &[&str]) {
+    for field in message.descriptor().fields() {
+        let mut keep_all = false;
+        let mut nested = Vec::new();
+        for path in paths {
+            let (head, rest) = match path.split_once('.') {
+                Some((head, rest)) => (head, Some(rest)),
+                None => (*path, None),
+            };
+            if head != field.name() && head != field.json_name() {
+                continue;
+            }
+            match rest {
+                Some(rest) => nested.push(rest),
+                None => keep_all = true,
+            }
+        }
+
+        if keep_all || !message.has_field(&field) {
+            continue;
+        }
+        if nested.is_empty() {
+            message.clear_field(&field);
+            continue;
+        }
+        match message.get_field_mut(&field) {
+            Value::Message(inner) => retain_paths(inner, &nested),
+            Value::List(items) => {
+                for item in items.iter_mut() {
+                    if let Value::Message(inner) = item {
+                        retain_paths(inner, &nested);
+                    }
+                }
+            }
+            Value::Map(entries) => {
+                for item in entries.values_mut() {
+                    if let Value::Message(inner) = item {
+                        retain_paths(inner, &nested);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn lower_camel_case(segment: &str) -> String {
+    let mut out = String::with_capacity(segment.len());
+    let mut upper = false;
+    for c in segment.chars() {
+        if c == '_' {
+            upper = true;
+        } else if upper {
+            out.extend(c.to_uppercase());
+            upper = false;
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Services whose backends receive the `?fields` mask in their read mask
+/// request field.
+#[derive(Debug, Default)]
+pub struct ReadMaskForwarding {
+    services: RwLock<HashSet<String>>,
+}
+
+impl ReadMaskForwarding {
+    pub fn enable(&self, service: &str) {
+        if let Ok(mut services) = self.services.write() {
+            services.insert(service.to_string());
+        }
+    }
+
+    pub fn disable(&self, service: &str) {
+        if let Ok(mut services) = self.services.write() {
+            services.remove(service);
+        }
+    }
+
+    pub fn is_enabled(&self, service: &str) -> bool {
+        self.services
+            .read()
+            .map(|services| services.contains(service))
+            .unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gateway::json_format::JsonFormat;
+    use crate::utils::test_descriptors;
+    use serde_json::json;
+
+    const USERS_PROTO: &str = r#"
+        name: "masks.proto" package: "masks" syntax: "proto3"
+        dependency: "google/protobuf/field_mask.proto"
+        message_type {
+            name: "User"
+            field { name: "display_name" number: 1 type: TYPE_STRING label: LABEL_OPTIONAL json_name: "displayName" }
+            field { name: "address" number: 2 type: TYPE_MESSAGE type_name: ".masks.Address" label: LABEL_OPTIONAL json_name: "address" }
+            field { name: "previous_addresses" number: 3 type: TYPE_MESSAGE type_name: ".masks.Address" label: LABEL_REPEATED json_name: "previousAddresses" }
+            field { name: "age" number: 4 type: TYPE_INT32 label: LABEL_OPTIONAL json_name: "age" }
+        }
+        message_type {
+            name: "Address"
+            field { name: "city" number: 1 type: TYPE_STRING label: LABEL_OPTIONAL json_name: "city" }
+            field { name: "postal_code" number: 2 type: TYPE_STRING label: LABEL_OPTIONAL json_name: "postalCode" }
+        }
+        message_type {
+            name: "GetUserRequest"
+            field { name: "update_mask" number: 1 type: TYPE_MESSAGE type_name: ".google.protobuf.FieldMask" label: LABEL_OPTIONAL json_name: "updateMask" }
+            field { name: "read_mask" number: 2 type: TYPE_MESSAGE type_name: ".google.protobuf.FieldMask" label: LABEL_OPTIONAL json_name: "readMask" }
+        }
+    "#;
+
+    fn descriptor(name: &str) -> MessageDescriptor {
+        test_descriptors::pool(&[USERS_PROTO])
+            .get_message_by_name(name)
+            .unwrap()
+    }
+
+    fn masked(mask: &str) -> serde_json::Value {
+        let format = JsonFormat::default();
+        let mut user = DynamicMessage::new(descriptor("masks.User"));
+        format
+            .from_json(
+                &json!({
+                    "displayName": "Ada",
+                    "age": 36,
+                    "address": { "city": "London", "postalCode": "W1" },
+                    "previousAddresses": [
+                        { "city": "Paris", "postalCode": "75001" },
+                        { "city": "Rome", "postalCode": "00100" },
+                    ],
+                }),
+                &mut user,
+            )
+            .unwrap();
+        FieldMask::parse(mask).apply(&mut user);
+        format.to_json(&user).unwrap()
+    }
+
+    #[test]
+    fn parsing_trims_and_skips_empty_paths() {
+        let mask = FieldMask::parse(" display_name, ,address.city,");
+
+        assert_eq!(mask.paths(), ["display_name", "address.city"]);
+        assert!(FieldMask::parse("").is_empty());
+    }
+
+    #[test]
+    fn keeps_only_the_listed_fields() {
+        assert_eq!(
+            masked("displayName,age"),
+            json!({ "displayName": "Ada", "age": 36 })
+        );
+    }
+
+    #[test]
+    fn nested_paths_apply_to_messages_and_lists() {
+        assert_eq!(
+            masked("address.city,previous_addresses.postal_code"),
+            json!({
+                "address": { "city": "London" },
+                "previousAddresses": [{ "postalCode": "75001" }, { "postalCode": "00100" }],
+            })
+        );
+    }
+
+    #[test]
+    fn a_whole_field_wins_over_its_nested_paths() {
+        assert_eq!(
+            masked("address,address.city"),
+            json!({ "address": { "city": "London", "postalCode": "W1" } })
+        );
+    }
+
+    #[test]
+    fn unknown_paths_are_ignored() {
+        assert_eq!(masked("nickname,age"), json!({ "age": 36 }));
+        assert_eq!(masked("").as_object().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn json_form_uses_camel_case_paths() {
+        let mask = FieldMask::new(["display_name", "previous_addresses.postal_code"]);
+
+        assert_eq!(
+            mask.to_json_string(),
+            "displayName,previousAddresses.postalCode"
+        );
+    }
+
+    #[test]
+    fn the_read_mask_is_not_the_update_mask() {
+        let input = descriptor("masks.GetUserRequest");
+
+        assert_eq!(read_mask_field(&input).unwrap().name(), "read_mask");
+        assert_eq!(
+            FieldMask::from_request(
+                &input,
+                &json!({ "updateMask": "age", "readMask": "age,address" })
+            ),
+            Some(FieldMask::new(["age", "address"]))
+        );
+        assert_eq!(
+            FieldMask::from_request(&input, &json!({ "read_mask": "" })),
+            None
+        );
+        assert!(read_mask_field(&descriptor("masks.User")).is_none());
+    }
+}
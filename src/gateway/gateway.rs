@@ -1,14 +1,18 @@
 use anyhow::Result;
 use bytes::Bytes;
+use futures::StreamExt;
+use futures::stream::BoxStream;
 use prost::Message;
-use prost_reflect::{DynamicMessage, ReflectMessage};
+use prost_reflect::{DynamicMessage, MethodDescriptor, ServiceDescriptor};
 use serde_json::Value;
 use std::str::FromStr;
 use std::sync::Arc;
-use tonic::metadata::{MetadataKey, MetadataValue};
+use std::time::Instant;
+use tonic::metadata::{MetadataKey, MetadataMap, MetadataValue};
 
 use crate::discriptor::discriptor_manager::ReflectionDiscriptorManager;
 use crate::gateway::dynamic_grpc_client::BytesCodec;
+use crate::gateway::json_format::JsonFormat;
 use crate::registry::auth::{Auth, Refreshable};
 use crate::registry::model::ServiceConfig;
 use crate::utils::validation_errors::ValidationError;
@@ -26,12 +30,42 @@ impl GrpcGateway {
         })
     }
 
+    #[tracing::instrument(
+        name = "grpc.invoke",
+        skip(self, data, service_config, metadata, format),
+        fields(endpoint = %service_config.endpoint)
+    )]
     pub async fn invoke(
         &self,
         service: &str,
         method: &str,
         data: Value,
         service_config: ServiceConfig,
+        metadata: MetadataMap,
+        format: &JsonFormat,
+    ) -> Result<serde_json::Value> {
+        let request_bytes = self.encode_request(service, method, &data, format).await?;
+        self.invoke_encoded(
+            service,
+            method,
+            request_bytes,
+            service_config,
+            metadata,
+            format,
+        )
+        .await
+    }
+
+    /// Calls a unary method with an already encoded request message, such as
+    /// a recorded one.
+    pub async fn invoke_encoded(
+        &self,
+        service: &str,
+        method: &str,
+        request_bytes: Vec<u8>,
+        service_config: ServiceConfig,
+        metadata: MetadataMap,
+        format: &JsonFormat,
     ) -> Result<serde_json::Value> {
         // get method discriptor from cache
         let method_desc = self
@@ -40,27 +74,34 @@ impl GrpcGateway {
             .await?
             .ok_or_else(|| anyhow::anyhow!("Method {}.{} not found", service, method))?;
 
-        // create request using cached
-        let mut request_message = DynamicMessage::new(method_desc.input());
-        self.json_to_dynamic_message(&data, &mut request_message)?;
+        let response_bytes = self
+            .invoke_bytes(service, method, request_bytes, service_config, metadata)
+            .await?;
 
-        // Encode request
-        let request_bytes = request_message.encode_to_vec();
+        let output_type = method_desc.output();
+        let response_message = DynamicMessage::decode(output_type, response_bytes)?;
+
+        // Convert back to JSON
+        let response_json = format.to_json(&response_message)?;
+        Ok(response_json)
+    }
+
+    /// Calls a unary method with an encoded request message and returns the
+    /// encoded response message as the backend sent it.
+    pub async fn invoke_bytes(
+        &self,
+        service: &str,
+        method: &str,
+        request_bytes: Vec<u8>,
+        service_config: ServiceConfig,
+        metadata: MetadataMap,
+    ) -> Result<Bytes> {
         let full_method_name = format!("/{}/{}", service, method);
         let mut request = tonic::Request::new(request_bytes);
+        // forward caller metadata such as trace context
+        *request.metadata_mut() = metadata;
 
-        if let Some(mut config) = service_config.auth_config.clone() {
-            // call refresh trait first
-            let token = config.refresh_if_expired(&service_config.endpoint).await;
-
-            if let Err(e) = token {
-                return Err(anyhow::anyhow!(ValidationError(e.to_string())));
-            }
-            // insert into metadata
-            let key = MetadataKey::from_bytes(config.header_name().as_bytes()).unwrap();
-            let value = MetadataValue::from_str(config.value().as_str()).unwrap();
-            request.metadata_mut().insert(key, value);
-        }
+        self.authorize(&mut request, &service_config).await?;
 
         // create dynamic client using shared channel
         let channel = self.discriptor_manager.channel.clone();
@@ -75,31 +116,119 @@ impl GrpcGateway {
         let response: tonic::Response<Bytes> = client
             .unary(request, full_method_name.parse()?, BytesCodec)
             .await?;
+        Ok(response.into_inner())
+    }
 
-        let output_type = method_desc.output();
-        let response_message = DynamicMessage::decode(output_type, response.into_inner())?;
+    /// Calls a server streaming method, yielding each response message as
+    /// JSON.
+    pub async fn invoke_server_stream(
+        &self,
+        service: &str,
+        method: &str,
+        data: Value,
+        service_config: ServiceConfig,
+        metadata: MetadataMap,
+        format: JsonFormat,
+    ) -> Result<BoxStream<'static, Result<Value>>> {
+        let method_desc = self
+            .discriptor_manager
+            .get_method(service, method)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Method {}.{} not found", service, method))?;
 
-        // Convert back to JSON
-        let response_json = self.dynamic_message_to_json(&response_message)?;
-        Ok(response_json)
+        let mut request_message = DynamicMessage::new(method_desc.input());
+        format.from_json(&data, &mut request_message)?;
+
+        let full_method_name = format!("/{}/{}", service, method);
+        let mut request = tonic::Request::new(request_message.encode_to_vec());
+        *request.metadata_mut() = metadata;
+        self.authorize(&mut request, &service_config).await?;
+
+        let mut client = tonic::client::Grpc::new(self.discriptor_manager.channel.clone());
+        client
+            .ready()
+            .await
+            .map_err(|e| anyhow::anyhow!("gRPC service not ready: {:?}", e))?;
+
+        let response = client
+            .server_streaming(request, full_method_name.parse()?, BytesCodec)
+            .await?;
+
+        let output_type = method_desc.output();
+        let messages = response.into_inner().map(move |item| {
+            let bytes = item?;
+            let message = DynamicMessage::decode(output_type.clone(), bytes)?;
+            format.to_json(&message)
+        });
+        Ok(messages.boxed())
     }
 
-    fn json_to_dynamic_message(&self, json: &Value, message: &mut DynamicMessage) -> Result<()> {
-        // Use prost-reflect's serde-powered deserializer to fully support nested
-        // messages, arrays, enums, maps, oneofs, bytes, and canonical field names.
-        let descriptor = message.descriptor();
-        let json_string = serde_json::to_string(json)?;
-        let mut deserializer = serde_json::Deserializer::from_str(&json_string);
-        let parsed = DynamicMessage::deserialize(descriptor, &mut deserializer)?;
-        deserializer.end()?;
-        *message = parsed;
+    // Refreshes the service's credentials if needed and attaches them
+    async fn authorize<T>(
+        &self,
+        request: &mut tonic::Request<T>,
+        service_config: &ServiceConfig,
+    ) -> Result<()> {
+        if let Some(mut config) = service_config.auth_config.clone() {
+            // call refresh trait first
+            let token = config.refresh_if_expired(&service_config.endpoint).await;
+
+            if let Err(e) = token {
+                tracing::warn!(error = %e, "auth refresh failed before invoking backend");
+                return Err(anyhow::anyhow!(ValidationError(e.to_string())));
+            }
+            // insert into metadata
+            let key = MetadataKey::from_bytes(config.header_name().as_bytes()).unwrap();
+            let value = MetadataValue::from_str(config.value().as_str()).unwrap();
+            request.metadata_mut().insert(key, value);
+        }
         Ok(())
     }
 
-    fn dynamic_message_to_json(&self, message: &DynamicMessage) -> Result<Value> {
-        // Use prost-reflect's serde support to convert to canonical Protobuf JSON
-        let value = serde_json::to_value(message)?;
-        Ok(value)
+    pub async fn method_descriptor(
+        &self,
+        service: &str,
+        method: &str,
+    ) -> Result<Option<MethodDescriptor>> {
+        self.discriptor_manager.get_method(service, method).await
+    }
+
+    pub async fn service_descriptor(&self, service: &str) -> Option<ServiceDescriptor> {
+        self.discriptor_manager.get_service(service).await
+    }
+
+    /// Every service the endpoint exposes through reflection.
+    pub async fn reflected_services(&self) -> Vec<ServiceDescriptor> {
+        let cache = self.discriptor_manager.cache.read().unwrap();
+        cache.services.values().cloned().collect()
+    }
+
+    /// When the endpoint's descriptors were last loaded through reflection.
+    pub fn descriptors_refreshed_at(&self) -> Instant {
+        self.discriptor_manager
+            .last_refresh
+            .read()
+            .unwrap()
+            .into_std()
+    }
+
+    /// Encodes the JSON payload into the protobuf bytes sent to the backend.
+    pub async fn encode_request(
+        &self,
+        service: &str,
+        method: &str,
+        data: &Value,
+        format: &JsonFormat,
+    ) -> Result<Vec<u8>> {
+        let method_desc = self
+            .discriptor_manager
+            .get_method(service, method)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Method {}.{} not found", service, method))?;
+
+        let mut request_message = DynamicMessage::new(method_desc.input());
+        format.from_json(data, &mut request_message)?;
+        Ok(request_message.encode_to_vec())
     }
 
     pub async fn refresh_oauth(
@@ -114,8 +243,9 @@ impl GrpcGateway {
             .await?
             .ok_or_else(|| anyhow::anyhow!("Method {}.{} not found", service, method))?;
         // create request using cached
+        let format = JsonFormat::default();
         let mut request_message = DynamicMessage::new(method_desc.input());
-        self.json_to_dynamic_message(&data, &mut request_message)?;
+        format.from_json(&data, &mut request_message)?;
 
         // Encode request
         let request_bytes = request_message.encode_to_vec();
@@ -140,7 +270,7 @@ impl GrpcGateway {
         let response_message = DynamicMessage::decode(output_type, response.into_inner())?;
 
         // Convert back to JSON
-        let response_json = self.dynamic_message_to_json(&response_message)?;
+        let response_json = format.to_json(&response_message)?;
         Ok(response_json)
     }
 }
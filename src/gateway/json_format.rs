@@ -0,0 +1,542 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::RwLock;
+
+use anyhow::Result;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use prost_reflect::{
+    DeserializeOptions, DynamicMessage, ReflectMessage, SerializeOptions, Value as ProtoValue,
+};
+use serde_json::Value;
+
+use crate::gateway::field_mask::{FIELDS_PARAM, FieldMask};
+use crate::validation::rules::map_key;
+use crate::validation::validator::pointer;
+
+/// Query parameter selecting the JSON options of a single request, e.g.
+/// `?$alt=json;enum=int;names=proto`.
+pub const ALT_PARAM: &str = "$alt";
+
+const ANY_TYPE: &str = "google.protobuf.Any";
+
+/// How messages are mapped to and from JSON.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct JsonFormat {
+    /// emit fields holding their default value
+    pub emit_defaults: bool,
+    /// use the `.proto` field names instead of lowerCamelCase
+    pub proto_field_names: bool,
+    /// emit enums as numbers instead of names
+    pub enums_as_ints: bool,
+    /// emit 64-bit integers as JSON numbers instead of strings
+    pub int64_as_numbers: bool,
+    /// expand `Any` values whose type is known to the backend's descriptors;
+    /// otherwise they are emitted as `{"@type": ..., "value": <base64>}`
+    pub resolve_any: bool,
+    /// drop unknown request fields instead of failing
+    pub ignore_unknown_fields: bool,
+    /// response fields to keep, every field when empty
+    pub fields: FieldMask,
+}
+
+impl Default for JsonFormat {
+    fn default() -> Self {
+        Self {
+            emit_defaults: false,
+            proto_field_names: false,
+            enums_as_ints: false,
+            int64_as_numbers: false,
+            resolve_any: true,
+            ignore_unknown_fields: false,
+            fields: FieldMask::default(),
+        }
+    }
+}
+
+impl fmt::Display for JsonFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "json;defaults={};names={};enum={};int64={};any={};unknown={}",
+            self.emit_defaults,
+            if self.proto_field_names {
+                "proto"
+            } else {
+                "json"
+            },
+            if self.enums_as_ints { "int" } else { "string" },
+            if self.int64_as_numbers {
+                "number"
+            } else {
+                "string"
+            },
+            if self.resolve_any { "resolve" } else { "raw" },
+            if self.ignore_unknown_fields {
+                "ignore"
+            } else {
+                "reject"
+            },
+        )
+    }
+}
+
+impl JsonFormat {
+    /// Applies the options of an `$alt` value on top of `self`. Only the
+    /// `json` alternative is understood.
+    pub fn with_alt(mut self, alt: &str) -> Result<Self, String> {
+        let mut parts = alt.split(';').map(str::trim);
+        match parts.next() {
+            Some("json") | Some("") => {}
+            Some(other) => return Err(format!("unsupported alt `{}`", other)),
+            None => {}
+        }
+
+        for part in parts.filter(|p| !p.is_empty()) {
+            let (name, value) = part
+                .split_once('=')
+                .ok_or_else(|| format!("expected `name=value`, got `{}`", part))?;
+            let invalid = || format!("invalid value `{}` for `{}`", value, name);
+            match name {
+                "defaults" => self.emit_defaults = value.parse().map_err(|_| invalid())?,
+                "names" => {
+                    self.proto_field_names = match value {
+                        "proto" => true,
+                        "json" => false,
+                        _ => return Err(invalid()),
+                    }
+                }
+                "enum" => {
+                    self.enums_as_ints = match value {
+                        "int" => true,
+                        "string" => false,
+                        _ => return Err(invalid()),
+                    }
+                }
+                "int64" => {
+                    self.int64_as_numbers = match value {
+                        "number" => true,
+                        "string" => false,
+                        _ => return Err(invalid()),
+                    }
+                }
+                "any" => {
+                    self.resolve_any = match value {
+                        "resolve" => true,
+                        "raw" => false,
+                        _ => return Err(invalid()),
+                    }
+                }
+                "unknown" => {
+                    self.ignore_unknown_fields = match value {
+                        "ignore" => true,
+                        "reject" => false,
+                        _ => return Err(invalid()),
+                    }
+                }
+                _ => return Err(format!("unknown option `{}`", name)),
+            }
+        }
+        Ok(self)
+    }
+
+    pub fn serialize_options(&self) -> SerializeOptions {
+        SerializeOptions::new()
+            .skip_default_fields(!self.emit_defaults)
+            .use_proto_field_name(self.proto_field_names)
+            .use_enum_numbers(self.enums_as_ints)
+            .stringify_64_bit_integers(!self.int64_as_numbers)
+    }
+
+    pub fn deserialize_options(&self) -> DeserializeOptions {
+        DeserializeOptions::new().deny_unknown_fields(!self.ignore_unknown_fields)
+    }
+
+    pub fn from_json(&self, json: &Value, message: &mut DynamicMessage) -> Result<()> {
+        let descriptor = message.descriptor();
+        let json_string = serde_json::to_string(json)?;
+        let mut deserializer = serde_json::Deserializer::from_str(&json_string);
+        let parsed = DynamicMessage::deserialize_with_options(
+            descriptor,
+            &mut deserializer,
+            &self.deserialize_options(),
+        )?;
+        deserializer.end()?;
+        *message = parsed;
+        Ok(())
+    }
+
+    pub fn to_json(&self, message: &DynamicMessage) -> Result<Value> {
+        // `Any` values that are not expanded are taken out of the message and
+        // written back into the JSON afterwards
+        let mut message = message.clone();
+        self.fields.apply(&mut message);
+        let mut raw_any = Vec::new();
+        self.take_any(&mut message, "", &mut raw_any)?;
+
+        let options = self.serialize_options();
+        let mut json = message.serialize_with_options(serde_json::value::Serializer, &options)?;
+        for (path, value) in raw_any {
+            let Some((parent, name)) = path.rsplit_once('/') else {
+                continue;
+            };
+            let name = name.replace("~1", "/").replace("~0", "~");
+            if let Some(Value::Object(object)) = json.pointer_mut(parent) {
+                object.insert(name, value);
+            }
+        }
+        Ok(json)
+    }
+
+    fn take_any(
+        &self,
+        message: &mut DynamicMessage,
+        path: &str,
+        out: &mut Vec<(String, Value)>,
+    ) -> Result<()> {
+        let descriptor = message.descriptor();
+        for field in descriptor.fields() {
+            if !message.has_field(&field) {
+                continue;
+            }
+            let Some(kind) = field.kind().as_message().cloned() else {
+                continue;
+            };
+            let name = if self.proto_field_names {
+                field.name()
+            } else {
+                field.json_name()
+            };
+            let field_path = pointer(path, name);
+
+            if kind.full_name() == ANY_TYPE && !field.is_map() {
+                let value = message.get_field(&field);
+                let items: Vec<&DynamicMessage> = match value.as_ref() {
+                    ProtoValue::Message(any) => vec![any],
+                    ProtoValue::List(list) => list.iter().filter_map(|v| v.as_message()).collect(),
+                    _ => continue,
+                };
+                if items.iter().all(|any| self.expands(any)) {
+                    continue;
+                }
+                let mut converted = Vec::with_capacity(items.len());
+                for any in items {
+                    converted.push(self.any_to_json(any)?);
+                }
+                let json = if field.is_list() {
+                    Value::Array(converted)
+                } else {
+                    converted.remove(0)
+                };
+                message.clear_field(&field);
+                out.push((field_path, json));
+                continue;
+            }
+
+            match message.get_field_mut(&field) {
+                ProtoValue::Message(nested) => self.take_any(nested, &field_path, out)?,
+                ProtoValue::List(list) => {
+                    for (i, item) in list.iter_mut().enumerate() {
+                        if let ProtoValue::Message(nested) = item {
+                            self.take_any(nested, &pointer(&field_path, &i.to_string()), out)?;
+                        }
+                    }
+                }
+                ProtoValue::Map(map) => {
+                    for (key, item) in map.iter_mut() {
+                        if let ProtoValue::Message(nested) = item {
+                            self.take_any(nested, &pointer(&field_path, &map_key(key)), out)?;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn expands(&self, any: &DynamicMessage) -> bool {
+        let type_url = any_type_url(any);
+        let type_name = type_url.rsplit('/').next().unwrap_or_default();
+        self.resolve_any
+            && any
+                .descriptor()
+                .parent_pool()
+                .get_message_by_name(type_name)
+                .is_some()
+    }
+
+    fn any_to_json(&self, any: &DynamicMessage) -> Result<Value> {
+        if self.expands(any) {
+            let options = self.serialize_options();
+            return Ok(any.serialize_with_options(serde_json::value::Serializer, &options)?);
+        }
+        let bytes = any
+            .get_field_by_name("value")
+            .and_then(|v| v.as_bytes().cloned())
+            .unwrap_or_default();
+        Ok(serde_json::json!({
+            "@type": any_type_url(any),
+            "value": STANDARD.encode(bytes),
+        }))
+    }
+}
+
+fn any_type_url(any: &DynamicMessage) -> String {
+    any.get_field_by_name("type_url")
+        .and_then(|v| v.as_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+/// JSON options, overridable per service and per request.
+#[derive(Debug, Default)]
+pub struct JsonFormats {
+    pub defaults: JsonFormat,
+    services: RwLock<HashMap<String, JsonFormat>>,
+}
+
+impl JsonFormats {
+    pub fn set_service_format(&self, service: &str, format: JsonFormat) {
+        if let Ok(mut services) = self.services.write() {
+            services.insert(service.to_string(), format);
+        }
+    }
+
+    pub fn remove_service_format(&self, service: &str) {
+        if let Ok(mut services) = self.services.write() {
+            services.remove(service);
+        }
+    }
+
+    pub fn format_for(&self, service: &str) -> JsonFormat {
+        self.services
+            .read()
+            .ok()
+            .and_then(|services| services.get(service).cloned())
+            .unwrap_or_else(|| self.defaults.clone())
+    }
+
+    /// Whether the request's `$alt` asks to drop (`Some(true)`) or reject
+    /// (`Some(false)`) unknown request fields.
+    pub fn requested_unknown_fields(query: &HashMap<String, String>) -> Option<bool> {
+        query
+            .get(ALT_PARAM)?
+            .split(';')
+            .filter_map(|part| part.trim().split_once('='))
+            .rfind(|(name, _)| *name == "unknown")
+            .and_then(|(_, value)| match value {
+                "ignore" => Some(true),
+                "reject" => Some(false),
+                _ => None,
+            })
+    }
+
+    /// The service's format with the request's `$alt` options and `fields`
+    /// mask applied.
+    pub fn resolve(
+        &self,
+        service: &str,
+        query: &HashMap<String, String>,
+    ) -> Result<JsonFormat, String> {
+        let mut format = self.format_for(service);
+        if let Some(alt) = query.get(ALT_PARAM) {
+            format = format.with_alt(alt)?;
+        }
+        if let Some(fields) = query.get(FIELDS_PARAM) {
+            format.fields = FieldMask::parse(fields);
+        }
+        Ok(format)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_descriptors;
+    use prost::Message;
+    use prost_reflect::{DescriptorPool, MessageDescriptor};
+    use serde_json::json;
+
+    const EVENTS_PROTO: &str = r#"
+        name: "events.proto" package: "events" syntax: "proto3"
+        dependency: "google/protobuf/any.proto"
+        message_type {
+            name: "Event"
+            field { name: "event_id" number: 1 type: TYPE_INT64 label: LABEL_OPTIONAL json_name: "eventId" }
+            field { name: "payload" number: 2 type: TYPE_MESSAGE type_name: ".google.protobuf.Any" label: LABEL_OPTIONAL json_name: "payload" }
+            field { name: "extras" number: 3 type: TYPE_MESSAGE type_name: ".google.protobuf.Any" label: LABEL_REPEATED json_name: "extras" }
+            field { name: "parent" number: 4 type: TYPE_MESSAGE type_name: ".events.Event" label: LABEL_OPTIONAL json_name: "parent" }
+            field { name: "kind" number: 5 type: TYPE_ENUM type_name: ".events.Kind" label: LABEL_OPTIONAL json_name: "kind" }
+        }
+        message_type {
+            name: "Note"
+            field { name: "text" number: 1 type: TYPE_STRING label: LABEL_OPTIONAL json_name: "text" }
+        }
+        enum_type { name: "Kind" value { name: "KIND_UNSPECIFIED" number: 0 } value { name: "KIND_CREATED" number: 1 } }
+    "#;
+
+    fn pool() -> DescriptorPool {
+        test_descriptors::pool(&[EVENTS_PROTO])
+    }
+
+    fn message(pool: &DescriptorPool, name: &str) -> MessageDescriptor {
+        pool.get_message_by_name(name).unwrap()
+    }
+
+    fn any(pool: &DescriptorPool, type_name: &str, value: Vec<u8>) -> ProtoValue {
+        let mut any = DynamicMessage::new(message(pool, ANY_TYPE));
+        any.set_field_by_name(
+            "type_url",
+            ProtoValue::String(format!("type.googleapis.com/{}", type_name)),
+        );
+        any.set_field_by_name("value", ProtoValue::Bytes(value.into()));
+        ProtoValue::Message(any)
+    }
+
+    fn note(pool: &DescriptorPool, text: &str) -> Vec<u8> {
+        let mut note = DynamicMessage::new(message(pool, "events.Note"));
+        note.set_field_by_name("text", ProtoValue::String(text.to_string()));
+        note.encode_to_vec()
+    }
+
+    #[test]
+    fn alt_options_override_the_format() {
+        let format = JsonFormat::default()
+            .with_alt("json;enum=int;names=proto;int64=number;defaults=true")
+            .unwrap();
+
+        assert!(format.enums_as_ints);
+        assert!(format.proto_field_names);
+        assert!(format.int64_as_numbers);
+        assert!(format.emit_defaults);
+        assert!(format.resolve_any);
+    }
+
+    #[test]
+    fn invalid_alt_values_are_reported() {
+        let format = JsonFormat::default();
+
+        assert_eq!(
+            format.clone().with_alt("proto").unwrap_err(),
+            "unsupported alt `proto`"
+        );
+        assert_eq!(
+            format.clone().with_alt("json;enum=upper").unwrap_err(),
+            "invalid value `upper` for `enum`"
+        );
+        assert_eq!(
+            format.clone().with_alt("json;colour=red").unwrap_err(),
+            "unknown option `colour`"
+        );
+        assert_eq!(
+            format.with_alt("json;enum").unwrap_err(),
+            "expected `name=value`, got `enum`"
+        );
+    }
+
+    #[test]
+    fn display_is_a_valid_alt() {
+        let format = JsonFormat {
+            enums_as_ints: true,
+            resolve_any: false,
+            ignore_unknown_fields: true,
+            ..Default::default()
+        };
+
+        let parsed = JsonFormat::default().with_alt(&format.to_string()).unwrap();
+
+        assert_eq!(parsed, format);
+    }
+
+    #[test]
+    fn unknown_any_types_are_written_back_raw() {
+        let pool = pool();
+        let mut event = DynamicMessage::new(message(&pool, "events.Event"));
+        event.set_field_by_name("payload", any(&pool, "other.Unknown", vec![8, 1]));
+        let mut parent = DynamicMessage::new(message(&pool, "events.Event"));
+        parent.set_field_by_name(
+            "extras",
+            ProtoValue::List(vec![any(&pool, "other.Unknown", vec![8, 2])]),
+        );
+        event.set_field_by_name("parent", ProtoValue::Message(parent));
+
+        let json = JsonFormat::default().to_json(&event).unwrap();
+
+        assert_eq!(
+            json,
+            json!({
+                "payload": { "@type": "type.googleapis.com/other.Unknown", "value": "CAE=" },
+                "parent": {
+                    "extras": [{ "@type": "type.googleapis.com/other.Unknown", "value": "CAI=" }]
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn known_any_types_are_expanded_unless_raw_is_asked_for() {
+        let pool = pool();
+        let mut event = DynamicMessage::new(message(&pool, "events.Event"));
+        event.set_field_by_name("payload", any(&pool, "events.Note", note(&pool, "hi")));
+
+        let resolved = JsonFormat::default().to_json(&event).unwrap();
+        let raw = JsonFormat::default()
+            .with_alt("json;any=raw;names=proto")
+            .unwrap()
+            .to_json(&event)
+            .unwrap();
+
+        assert_eq!(
+            resolved,
+            json!({ "payload": { "@type": "type.googleapis.com/events.Note", "text": "hi" } })
+        );
+        assert_eq!(
+            raw,
+            json!({ "payload": { "@type": "type.googleapis.com/events.Note", "value": "CgJoaQ==" } })
+        );
+    }
+
+    #[test]
+    fn serialize_options_follow_the_format() {
+        let pool = pool();
+        let mut event = DynamicMessage::new(message(&pool, "events.Event"));
+        JsonFormat::default()
+            .from_json(
+                &json!({ "eventId": "12", "kind": "KIND_CREATED" }),
+                &mut event,
+            )
+            .unwrap();
+
+        let json = JsonFormat::default()
+            .with_alt("json;enum=int;int64=number;names=proto")
+            .unwrap()
+            .to_json(&event)
+            .unwrap();
+
+        assert_eq!(json, json!({ "event_id": 12, "kind": 1 }));
+    }
+
+    #[test]
+    fn requests_resolve_the_service_format_then_alt() {
+        let formats = JsonFormats::default();
+        formats.set_service_format(
+            "events.Events",
+            JsonFormat {
+                enums_as_ints: true,
+                ..Default::default()
+            },
+        );
+        let query = HashMap::from([(ALT_PARAM.to_string(), "json;names=proto".to_string())]);
+
+        let format = formats.resolve("events.Events", &query).unwrap();
+
+        assert!(format.enums_as_ints);
+        assert!(format.proto_field_names);
+        assert!(
+            !formats
+                .resolve("other.Service", &query)
+                .unwrap()
+                .enums_as_ints
+        );
+    }
+}
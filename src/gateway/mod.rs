@@ -1,3 +1,5 @@
 pub mod dynamic_grpc_client;
+pub mod field_mask;
+#[allow(clippy::module_inception)]
 pub mod gateway;
-
+pub mod json_format;
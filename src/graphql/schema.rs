@@ -0,0 +1,618 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+use std::time::Instant;
+
+use async_graphql::dynamic::{
+    Enum, Field, FieldFuture, FieldValue, InputObject, InputValue, Object, ResolverContext, Scalar,
+    Schema, SchemaError, Subscription, SubscriptionField, SubscriptionFieldFuture, TypeRef,
+};
+use async_graphql::{Error, ErrorExtensions, Value as GqlValue};
+use futures::StreamExt;
+use prost_reflect::{
+    FieldDescriptor, Kind, MessageDescriptor, MethodDescriptor, ServiceDescriptor,
+};
+use prost_types::method_options::IdempotencyLevel;
+use serde_json::Value;
+
+use crate::Gateway;
+use crate::gateway::json_format::ALT_PARAM;
+use crate::logging::access_log::AccessLogEntry;
+use crate::schema::openapi::DescriptorFingerprint;
+use crate::utils::model::{RequestContext, RequestType};
+
+/// Scalar holding any JSON value, used for maps and dynamic well-known types.
+const JSON_SCALAR: &str = "JSON";
+
+// resolvers read fields by their JSON name, so every option that changes the
+// shape of the response is pinned
+const GRAPHQL_ALT: &str = "json;defaults=true;names=json;enum=string;int64=string";
+
+/// Keeps the last generated schema until the registered services or their
+/// descriptors change.
+#[derive(Default)]
+pub struct GraphQLSchemaCache {
+    schema: RwLock<Option<(DescriptorFingerprint, Schema)>>,
+}
+
+impl std::fmt::Debug for GraphQLSchemaCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GraphQLSchemaCache").finish_non_exhaustive()
+    }
+}
+
+impl GraphQLSchemaCache {
+    pub fn get_or_build<F>(
+        &self,
+        fingerprint: DescriptorFingerprint,
+        build: F,
+    ) -> Result<Schema, SchemaError>
+    where
+        F: FnOnce() -> Result<Schema, SchemaError>,
+    {
+        if let Ok(cached) = self.schema.read()
+            && let Some((cached_fingerprint, schema)) = cached.as_ref()
+            && *cached_fingerprint == fingerprint
+        {
+            return Ok(schema.clone());
+        }
+
+        let schema = build()?;
+        if let Ok(mut cached) = self.schema.write() {
+            *cached = Some((fingerprint, schema.clone()));
+        }
+        Ok(schema)
+    }
+}
+
+/// Generates a GraphQL schema for the given services. Unary methods marked
+/// `NO_SIDE_EFFECTS` become queries, other unary methods mutations and
+/// server streaming methods subscriptions. Fields are named
+/// `{package}_{Service}_{Method}`.
+///
+/// Resolvers expect the calling `Arc<Gateway>` and the HTTP
+/// `RequestContext` in the request data.
+pub fn build_schema(services: &[ServiceDescriptor]) -> Result<Schema, SchemaError> {
+    let mut types = TypeBuilder::new();
+    let service_names: Vec<String> = services.iter().map(|s| s.full_name().to_string()).collect();
+    let mut query = Object::new("Query").field(Field::new(
+        "_services",
+        TypeRef::named_nn_list_nn(TypeRef::STRING),
+        move |_| {
+            let names = service_names.clone();
+            FieldFuture::new(async move {
+                Ok(Some(FieldValue::list(
+                    names.into_iter().map(FieldValue::value),
+                )))
+            })
+        },
+    ));
+    let mut mutation = Object::new("Mutation");
+    let mut subscription = Subscription::new("Subscription");
+    let (mut has_mutations, mut has_subscriptions) = (false, false);
+
+    for service in services {
+        for method in service.methods() {
+            if method.is_client_streaming() {
+                continue;
+            }
+            let service_name = types.name_for(
+                "service",
+                service.full_name(),
+                type_name(service.full_name()),
+            );
+            let field_name = format!("{}_{}", service_name, method.name());
+            let input = types.input_argument(&method.input());
+            let output = types.output_type(&Kind::Message(method.output()));
+
+            if method.is_server_streaming() {
+                let mut field = subscription_field(&field_name, &method, &output);
+                if let Some(input) = input {
+                    field = field.argument(InputValue::new("input", TypeRef::named(input)));
+                }
+                subscription = subscription.field(field);
+                has_subscriptions = true;
+                continue;
+            }
+
+            let mut field = unary_field(&field_name, &method, &output);
+            if let Some(input) = input {
+                field = field.argument(InputValue::new("input", TypeRef::named(input)));
+            }
+            if is_read_only(&method) {
+                query = query.field(field);
+            } else {
+                mutation = mutation.field(field);
+                has_mutations = true;
+            }
+        }
+    }
+
+    let mut builder = Schema::build(
+        query.type_name(),
+        has_mutations.then_some(mutation.type_name()),
+        has_subscriptions.then_some(subscription.type_name()),
+    )
+    .register(Scalar::new(JSON_SCALAR));
+    for ty in types.objects.into_values() {
+        builder = builder.register(ty);
+    }
+    for ty in types.inputs.into_values() {
+        builder = builder.register(ty);
+    }
+    for ty in types.enums.into_values() {
+        builder = builder.register(ty);
+    }
+    builder = builder.register(query);
+    if has_mutations {
+        builder = builder.register(mutation);
+    }
+    if has_subscriptions {
+        builder = builder.register(subscription);
+    }
+    builder.finish()
+}
+
+/// The context resolvers pass to the gateway, derived from the GraphQL HTTP
+/// request.
+pub fn call_context(ctx: &RequestContext) -> RequestContext {
+    let mut ctx = ctx.clone();
+    // GraphQL selects fields itself and does not revalidate cached responses
+    ctx.query = HashMap::from([(ALT_PARAM.to_string(), GRAPHQL_ALT.to_string())]);
+    ctx.headers.remove("if-none-match");
+    ctx
+}
+
+/// Whether the method is declared `NO_SIDE_EFFECTS`.
+pub(crate) fn is_read_only(method: &MethodDescriptor) -> bool {
+    method
+        .method_descriptor_proto()
+        .options
+        .as_ref()
+        .is_some_and(|options| options.idempotency_level() == IdempotencyLevel::NoSideEffects)
+}
+
+fn request_for(
+    ctx: &ResolverContext<'_>,
+    method: &MethodDescriptor,
+) -> async_graphql::Result<RequestType> {
+    let data = match ctx.args.get("input") {
+        Some(input) => input.as_value().clone().into_json()?,
+        None => Value::Object(Default::default()),
+    };
+    Ok(RequestType {
+        service: method.parent_service().full_name().to_string(),
+        method: method.name().to_string(),
+        data,
+    })
+}
+
+fn unary_field(name: &str, method: &MethodDescriptor, output: &OutputType) -> Field {
+    let method = method.clone();
+    let is_object = output.is_object;
+    Field::new(name, TypeRef::named(&output.name), move |ctx| {
+        let method = method.clone();
+        FieldFuture::new(async move {
+            let gateway = ctx.data::<Arc<Gateway>>()?;
+            let http_ctx = ctx.data::<RequestContext>()?;
+            let req = request_for(&ctx, &method)?;
+            let started_at = Instant::now();
+            let (service, method_name) = (req.service.to_string(), req.method.to_string());
+            let request_body = gateway
+                .access_log
+                .include_request_body
+                .then(|| req.data.clone());
+
+            let response = gateway
+                .invoke_with_context(req, call_context(http_ctx))
+                .await;
+            gateway.access_log.emit(AccessLogEntry {
+                request_id: response.request_id.clone(),
+                service,
+                method: method_name,
+                status: response.status_code.as_u16(),
+                latency_ms: started_at.elapsed().as_secs_f64() * 1000.0,
+                bytes: AccessLogEntry::json_bytes(response.data.as_ref()),
+                client_ip: http_ctx.client_ip.clone(),
+                request: request_body,
+            });
+            if !response.status_code.is_success() {
+                let status = response.status_code.as_u16();
+                return Err(Error::new(response.message.to_string())
+                    .extend_with(|_, e| e.set("status", status)));
+            }
+            Ok(field_value(response.data.as_ref(), is_object))
+        })
+    })
+}
+
+fn subscription_field(
+    name: &str,
+    method: &MethodDescriptor,
+    output: &OutputType,
+) -> SubscriptionField {
+    let method = method.clone();
+    let is_object = output.is_object;
+    SubscriptionField::new(name, TypeRef::named(&output.name), move |ctx| {
+        let method = method.clone();
+        SubscriptionFieldFuture::new(async move {
+            let gateway = ctx.data::<Arc<Gateway>>()?;
+            let http_ctx = ctx.data::<RequestContext>()?;
+            let req = request_for(&ctx, &method)?;
+
+            let stream = match gateway.invoke_stream(req, call_context(http_ctx)).await {
+                Ok(stream) => stream,
+                Err(response) => {
+                    let status = response.status_code.as_u16();
+                    return Err(Error::new(response.message.to_string())
+                        .extend_with(|_, e| e.set("status", status)));
+                }
+            };
+            Ok(stream.map(move |item| match item {
+                Ok(value) => Ok(field_value(Some(&value), is_object).unwrap_or(FieldValue::NULL)),
+                Err(e) => Err(Error::new(e.to_string())),
+            }))
+        })
+    })
+}
+
+// Resolved objects carry their JSON so nested fields can read from it
+fn field_value(value: Option<&Value>, is_object: bool) -> Option<FieldValue<'static>> {
+    match value? {
+        Value::Null => None,
+        Value::Array(items) if is_object => Some(FieldValue::list(
+            items.iter().map(|item| FieldValue::owned_any(item.clone())),
+        )),
+        value if is_object => Some(FieldValue::owned_any(value.clone())),
+        value => GqlValue::from_json(value.clone())
+            .ok()
+            .map(FieldValue::value),
+    }
+}
+
+struct OutputType {
+    name: String,
+    is_object: bool,
+}
+
+struct TypeBuilder {
+    objects: BTreeMap<String, Object>,
+    inputs: BTreeMap<String, InputObject>,
+    enums: BTreeMap<String, Enum>,
+    // GraphQL name given to each protobuf name, by what it is used for
+    names: HashMap<(&'static str, String), String>,
+    taken: HashSet<String>,
+}
+
+impl TypeBuilder {
+    fn new() -> Self {
+        let taken = [
+            "Query",
+            "Mutation",
+            "Subscription",
+            JSON_SCALAR,
+            TypeRef::STRING,
+            TypeRef::INT,
+            TypeRef::FLOAT,
+            TypeRef::BOOLEAN,
+            TypeRef::ID,
+        ];
+        Self {
+            objects: BTreeMap::new(),
+            inputs: BTreeMap::new(),
+            enums: BTreeMap::new(),
+            names: HashMap::new(),
+            taken: taken.into_iter().map(str::to_string).collect(),
+        }
+    }
+
+    // Dots become underscores, so `a.b_c` and `a_b.c` want the same name;
+    // the one seen later gets a numbered suffix
+    fn name_for(&mut self, usage: &'static str, full_name: &str, candidate: String) -> String {
+        let key = (usage, full_name.to_string());
+        if let Some(name) = self.names.get(&key) {
+            return name.clone();
+        }
+        let mut name = candidate.clone();
+        let mut suffix = 2;
+        while self.taken.contains(&name) {
+            name = format!("{}_{}", candidate, suffix);
+            suffix += 1;
+        }
+        if name != candidate {
+            tracing::warn!(
+                proto = %full_name,
+                graphql = %name,
+                "GraphQL name already taken, renamed"
+            );
+        }
+        self.taken.insert(name.clone());
+        self.names.insert(key, name.clone());
+        name
+    }
+
+    fn output_type(&mut self, kind: &Kind) -> OutputType {
+        if let Some(name) = self.leaf_type(kind) {
+            return OutputType {
+                name,
+                is_object: false,
+            };
+        }
+        let Kind::Message(message) = kind else {
+            unreachable!("every non-message kind is a leaf type");
+        };
+
+        let name = self.name_for("type", message.full_name(), type_name(message.full_name()));
+        if !self.objects.contains_key(&name) {
+            // placeholder first, messages may be recursive
+            self.objects.insert(name.clone(), Object::new(&name));
+            let mut object = Object::new(&name);
+            if let Some(description) = comments(message) {
+                object = object.description(description);
+            }
+            for field in message.fields() {
+                object = object.field(self.output_field(&field));
+            }
+            self.objects.insert(name.clone(), object);
+        }
+        OutputType {
+            name,
+            is_object: true,
+        }
+    }
+
+    fn output_field(&mut self, field: &FieldDescriptor) -> Field {
+        let (ty, is_object) = if field.is_map() {
+            (TypeRef::named(JSON_SCALAR), false)
+        } else {
+            let output = self.output_type(&field.kind());
+            let ty = if field.is_list() {
+                TypeRef::named_nn_list(&output.name)
+            } else {
+                TypeRef::named(&output.name)
+            };
+            (ty, output.is_object)
+        };
+
+        let json_name = field.json_name().to_string();
+        Field::new(field.json_name(), ty, move |ctx| {
+            let json_name = json_name.clone();
+            FieldFuture::new(async move {
+                let parent = ctx.parent_value.try_downcast_ref::<Value>()?;
+                Ok(field_value(parent.get(&json_name), is_object))
+            })
+        })
+    }
+
+    // Name of the input type for a method's request, `None` for empty
+    // messages which take no argument
+    fn input_argument(&mut self, message: &MessageDescriptor) -> Option<String> {
+        if message.fields().len() == 0 {
+            return None;
+        }
+        Some(self.input_type(&Kind::Message(message.clone())))
+    }
+
+    fn input_type(&mut self, kind: &Kind) -> String {
+        if let Some(name) = self.leaf_type(kind) {
+            return name;
+        }
+        let Kind::Message(message) = kind else {
+            unreachable!("every non-message kind is a leaf type");
+        };
+
+        let name = self.name_for(
+            "input",
+            message.full_name(),
+            format!("{}Input", type_name(message.full_name())),
+        );
+        if !self.inputs.contains_key(&name) {
+            self.inputs.insert(name.clone(), InputObject::new(&name));
+            let mut input = InputObject::new(&name);
+            for field in message.fields() {
+                let ty = if field.is_map() {
+                    TypeRef::named(JSON_SCALAR)
+                } else if field.is_list() {
+                    TypeRef::named_nn_list(self.input_type(&field.kind()))
+                } else {
+                    TypeRef::named(self.input_type(&field.kind()))
+                };
+                input = input.field(InputValue::new(field.json_name(), ty));
+            }
+            self.inputs.insert(name.clone(), input);
+        }
+        name
+    }
+
+    // Scalars, enums and messages with a scalar JSON form
+    fn leaf_type(&mut self, kind: &Kind) -> Option<String> {
+        let name = match kind {
+            Kind::Double | Kind::Float | Kind::Uint32 | Kind::Fixed32 => TypeRef::FLOAT,
+            Kind::Int32 | Kind::Sint32 | Kind::Sfixed32 => TypeRef::INT,
+            // proto3 JSON writes 64-bit integers as strings
+            Kind::Int64 | Kind::Sint64 | Kind::Sfixed64 | Kind::Uint64 | Kind::Fixed64 => {
+                TypeRef::STRING
+            }
+            Kind::Bool => TypeRef::BOOLEAN,
+            Kind::String | Kind::Bytes => TypeRef::STRING,
+            Kind::Enum(enum_desc) => {
+                let name = self.name_for(
+                    "type",
+                    enum_desc.full_name(),
+                    type_name(enum_desc.full_name()),
+                );
+                self.enums.entry(name.clone()).or_insert_with(|| {
+                    Enum::new(&name).items(enum_desc.values().map(|v| v.name().to_string()))
+                });
+                return Some(name);
+            }
+            Kind::Message(message) => match message.full_name() {
+                "google.protobuf.Timestamp"
+                | "google.protobuf.Duration"
+                | "google.protobuf.FieldMask" => TypeRef::STRING,
+                "google.protobuf.Struct"
+                | "google.protobuf.Value"
+                | "google.protobuf.ListValue"
+                | "google.protobuf.Any" => JSON_SCALAR,
+                name if name.starts_with("google.protobuf.") && name.ends_with("Value") => {
+                    let inner = message.get_field_by_name("value")?;
+                    return self.leaf_type(&inner.kind());
+                }
+                // GraphQL objects need at least one field
+                _ if message.fields().len() == 0 => JSON_SCALAR,
+                _ => return None,
+            },
+        };
+        Some(name.to_string())
+    }
+}
+
+fn type_name(full_name: &str) -> String {
+    full_name.replace('.', "_")
+}
+
+fn comments(message: &MessageDescriptor) -> Option<String> {
+    let comments = message
+        .parent_file_descriptor_proto()
+        .source_code_info
+        .as_ref()?;
+    let path = message.path();
+    comments
+        .location
+        .iter()
+        .find(|location| location.path == path)
+        .and_then(|location| location.leading_comments.as_ref())
+        .map(|c| c.trim().to_string())
+        .filter(|c| !c.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::mock_mode::MockModeConfig;
+    use crate::utils::test_descriptors;
+    use prost_reflect::DescriptorPool;
+    use serde_json::json;
+    use tonic::Code;
+
+    const USERS_PROTO: &str = r#"
+        name: "gql.proto" package: "gql" syntax: "proto3"
+        message_type {
+            name: "GetUserRequest"
+            field { name: "user_id" number: 1 type: TYPE_STRING label: LABEL_OPTIONAL json_name: "userId" }
+        }
+        message_type {
+            name: "User"
+            field { name: "display_name" number: 1 type: TYPE_STRING label: LABEL_OPTIONAL json_name: "displayName" }
+            field { name: "role" number: 2 type: TYPE_ENUM type_name: ".gql.Role" label: LABEL_OPTIONAL json_name: "role" }
+            field { name: "manager" number: 3 type: TYPE_MESSAGE type_name: ".gql.User" label: LABEL_OPTIONAL json_name: "manager" }
+        }
+        enum_type { name: "Role" value { name: "ROLE_UNSPECIFIED" number: 0 } value { name: "ROLE_ADMIN" number: 1 } }
+        service {
+            name: "Users"
+            method {
+                name: "GetUser" input_type: ".gql.GetUserRequest" output_type: ".gql.User"
+                options { idempotency_level: NO_SIDE_EFFECTS }
+            }
+            method { name: "UpdateUser" input_type: ".gql.User" output_type: ".gql.User" }
+            method { name: "WatchUsers" input_type: ".gql.GetUserRequest" output_type: ".gql.User" server_streaming: true }
+        }
+    "#;
+
+    const DOTTED_PROTO: &str = r#"
+        name: "a.proto" package: "a" syntax: "proto3"
+        message_type { name: "b_c" field { name: "x" number: 1 type: TYPE_STRING label: LABEL_OPTIONAL json_name: "x" } }
+        service { name: "S" method { name: "Get" input_type: ".a.b_c" output_type: ".a.b_c" } }
+    "#;
+
+    const UNDERSCORED_PROTO: &str = r#"
+        name: "a_b.proto" package: "a_b" syntax: "proto3"
+        message_type { name: "c" field { name: "y" number: 1 type: TYPE_STRING label: LABEL_OPTIONAL json_name: "y" } }
+        service { name: "S" method { name: "Get" input_type: ".a_b.c" output_type: ".a_b.c" } }
+    "#;
+
+    fn services(pool: &DescriptorPool) -> Vec<ServiceDescriptor> {
+        pool.services().collect()
+    }
+
+    fn gateway(config: MockModeConfig) -> (Arc<Gateway>, Schema) {
+        let pool = test_descriptors::pool(&[USERS_PROTO]);
+        let schema = build_schema(&services(&pool)).unwrap();
+        let gateway = Gateway::new();
+        gateway.mock.enable(
+            "gql.Users",
+            MockModeConfig {
+                descriptors: Some(pool),
+                ..config
+            },
+        );
+        (Arc::new(gateway), schema)
+    }
+
+    #[test]
+    fn methods_become_queries_mutations_and_subscriptions() {
+        let pool = test_descriptors::pool(&[USERS_PROTO]);
+
+        let sdl = build_schema(&services(&pool)).unwrap().sdl();
+
+        assert!(sdl.contains("gql_Users_GetUser(input: gql_GetUserRequestInput): gql_User"));
+        assert!(sdl.contains("gql_Users_UpdateUser(input: gql_UserInput): gql_User"));
+        assert!(sdl.contains("gql_Users_WatchUsers(input: gql_GetUserRequestInput): gql_User"));
+        assert!(sdl.contains("type Mutation"));
+        assert!(sdl.contains("type Subscription"));
+        assert!(sdl.contains("manager: gql_User"));
+        assert!(sdl.contains("enum gql_Role"));
+    }
+
+    #[test]
+    fn colliding_names_get_a_suffix() {
+        let pool = test_descriptors::pool(&[DOTTED_PROTO, UNDERSCORED_PROTO]);
+
+        let sdl = build_schema(&services(&pool)).unwrap().sdl();
+
+        assert!(sdl.contains("type a_b_c {"));
+        assert!(sdl.contains("type a_b_c_2 {"));
+        assert!(sdl.contains("a_S_Get(input: a_b_cInput): a_b_c"));
+        assert!(sdl.contains("a_b_S_Get(input: a_b_cInput_2): a_b_c_2"));
+    }
+
+    #[tokio::test]
+    async fn resolvers_call_the_gateway() {
+        let (gateway, schema) = gateway(MockModeConfig::default());
+        let request = async_graphql::Request::new(
+            r#"{ gql_Users_GetUser(input: { userId: "1" }) { displayName role manager { role } } }"#,
+        )
+        .data(gateway)
+        .data(RequestContext::default());
+
+        let response = schema.execute(request).await;
+
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        let data = response.data.into_json().unwrap();
+        let user = &data["gql_Users_GetUser"];
+        assert!(user["displayName"].is_string());
+        assert_eq!(user["role"], json!("ROLE_ADMIN"));
+        assert_eq!(user["manager"]["role"], json!("ROLE_ADMIN"));
+    }
+
+    #[tokio::test]
+    async fn failed_calls_report_their_http_status() {
+        let (gateway, schema) = gateway(MockModeConfig {
+            error_rate: 1.0,
+            error_code: Code::Unavailable,
+            ..Default::default()
+        });
+        let request = async_graphql::Request::new(
+            r#"mutation { gql_Users_UpdateUser(input: { displayName: "Ada" }) { displayName } }"#,
+        )
+        .data(gateway)
+        .data(RequestContext::default());
+
+        let response = schema.execute(request).await;
+
+        let error = &response.errors[0];
+        assert_eq!(
+            error.extensions.as_ref().unwrap().get("status"),
+            Some(&GqlValue::from(503))
+        );
+    }
+}
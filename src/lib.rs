@@ -1,25 +1,81 @@
 #![doc = include_str!("../README.md")]
 
-use self::circuitbreaker::breaker::{CircuitBreaker, CircuitBreakerConfig};
+use self::batch::executor::BatchConfig;
+use self::cache::response_cache::{CacheKey, CachedResponse, ResponseCache};
+use self::circuitbreaker::breaker::{CircuitBreaker, CircuitBreakerConfig, CircuitBreakerState};
+use self::coalesce::single_flight::{FlightKey, FlightResult, SingleFlight};
+use self::concurrency::limiter::{ConcurrencyLimits, ShedReason};
+use self::gateway::field_mask::{self, FieldMask, ReadMaskForwarding};
 use self::gateway::gateway::GrpcGateway;
+use self::gateway::json_format::{JsonFormat, JsonFormats};
+use self::graphql::schema::{self as graphql_schema, GraphQLSchemaCache};
+use self::logging::access_log::{AccessLogConfig, AccessLogEntry};
+use self::metrics::gateway_metrics;
+use self::middleware::chain::{self as middleware_chain, CallInfo, MiddlewareChain};
+use self::mock::mock_mode::{self, MockMode};
+use self::ratelimit::limiter::{RateLimitDecision, RateLimiter};
+use self::recording::recorder::{
+    self as traffic_recorder, PendingCall, RecordedCall, TrafficRecorder,
+};
+use self::registry::model::ServiceConfig;
 use self::registry::service_registry::{RegistryTrait, ServiceRegistry};
+use self::routing::table::RoutingTable;
+use self::routing::traffic_split::{BACKEND_VERSION_HEADER, TrafficSplit};
+use self::schema::openapi::{self, OpenApiCache};
+use self::server::proxies::TrustedProxies;
+use self::shadow::mirror::{PrimaryOutcome, ShadowCall, ShadowTraffic};
+use self::telemetry::propagation;
 use self::utils::errors::ResponseErrors;
 use self::utils::model;
+use self::utils::request_id;
 use self::utils::response::Response;
 use self::utils::validation_errors::ValidationError;
+use self::validation::RequestValidator;
+use self::validation::validator::validate_with;
+use futures::StreamExt;
+use futures::stream::BoxStream;
 use lazy_static::lazy_static;
+use opentelemetry::trace::{SpanKind, Status, TraceContextExt, Tracer};
+use opentelemetry::{KeyValue, global};
+use prost_reflect::{DynamicMessage, MessageDescriptor, MethodDescriptor, ServiceDescriptor};
 use reqwest::StatusCode;
+use tonic::metadata::{MetadataMap, MetadataValue};
 
 use std::collections::HashMap;
 use std::error::Error;
-use std::sync::Mutex;
-use std::time::Duration;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
+pub mod batch;
+pub mod cache;
 pub mod circuitbreaker;
+pub mod coalesce;
+pub mod concurrency;
 pub mod discriptor;
 pub mod gateway;
+pub mod graphql;
+pub mod logging;
+pub mod metrics;
+pub mod middleware;
+pub mod mock;
+pub mod ratelimit;
+pub mod recording;
 pub mod registry;
+pub mod routing;
+pub mod schema;
+pub mod server;
+pub mod shadow;
+pub mod telemetry;
+#[cfg(feature = "testing")]
+pub mod testing;
 pub mod utils;
+pub mod validation;
+
+// metric label used when the request never reached the backend
+const NO_GRPC_CODE: &str = "None";
+// metric label for services and methods that no backend knows
+const UNKNOWN_LABEL: &str = "unknown";
 
 lazy_static! {
     static ref grpc_client_map: Mutex<HashMap<String, GrpcGateway>> = Mutex::new(HashMap::new());
@@ -28,6 +84,24 @@ lazy_static! {
 pub struct Gateway {
     pub service_registry: ServiceRegistry,
     pub breaker: CircuitBreaker,
+    pub access_log: AccessLogConfig,
+    pub rate_limiter: RateLimiter,
+    pub concurrency: ConcurrencyLimits,
+    pub response_cache: ResponseCache,
+    pub coalescing: SingleFlight,
+    pub openapi: OpenApiCache,
+    pub validator: RequestValidator,
+    pub json_format: JsonFormats,
+    pub middleware: MiddlewareChain,
+    pub read_mask: ReadMaskForwarding,
+    pub batch: BatchConfig,
+    pub graphql: GraphQLSchemaCache,
+    pub mock: MockMode,
+    pub recorder: TrafficRecorder,
+    pub shadow: ShadowTraffic,
+    pub traffic_split: TrafficSplit,
+    pub routing: RoutingTable,
+    pub trusted_proxies: TrustedProxies,
 }
 
 impl Default for Gateway {
@@ -45,86 +119,974 @@ impl Gateway {
                 recovery_timeout: Duration::from_secs(3),
                 half_open_max_calls: 2,
             }),
+            access_log: AccessLogConfig::default(),
+            rate_limiter: RateLimiter::default(),
+            concurrency: ConcurrencyLimits::default(),
+            response_cache: ResponseCache::default(),
+            coalescing: SingleFlight::default(),
+            openapi: OpenApiCache::default(),
+            validator: RequestValidator::default(),
+            json_format: JsonFormats::default(),
+            middleware: MiddlewareChain::default(),
+            read_mask: ReadMaskForwarding::default(),
+            batch: BatchConfig::default(),
+            graphql: GraphQLSchemaCache::default(),
+            mock: MockMode::default(),
+            recorder: TrafficRecorder::default(),
+            shadow: ShadowTraffic::default(),
+            traffic_split: TrafficSplit::default(),
+            routing: RoutingTable::default(),
+            trusted_proxies: TrustedProxies::default(),
         }
     }
     pub async fn invoker(&self, req: model::RequestType) -> Response {
-        let service = self.service_registry.discover(req.service.to_string());
+        self.invoke_with_context(req, model::RequestContext::default())
+            .await
+    }
 
-        if service.is_none() {
-            return Response {
-                message: ResponseErrors::ServiceNotRegister(req.service.to_string()).message(),
-                status: ResponseErrors::Error.message(),
-                data: None,
-                status_code: StatusCode::BAD_REQUEST,
+    /// Same as `invoker`, continuing the caller's trace from the HTTP headers
+    /// in `ctx` and forwarding it to the backend.
+    #[tracing::instrument(
+        name = "gateway.invoke",
+        skip(self, req, ctx),
+        fields(service = %req.service, method = %req.method, request_id, status)
+    )]
+    pub async fn invoke_with_context(
+        &self,
+        mut req: model::RequestType,
+        ctx: model::RequestContext,
+    ) -> Response {
+        req.service = self.routing.resolve_service(&req.service);
+        let service = req.service.to_string();
+        let method = req.method.to_string();
+        let started_at = Instant::now();
+        let descriptor = self.method_descriptor(&service, &method).await;
+        let (service_label, method_label) =
+            self.metric_labels(&service, descriptor.as_ref().map(|_| method.as_str()));
+        let _in_flight = gateway_metrics::InFlightGuard::new(&service_label, &method_label);
+        let request_id = request_id::resolve(&ctx.headers);
+        tracing::Span::current().record("request_id", request_id.as_str());
+
+        let otel_cx = start_server_span(&service, &method, &request_id, &ctx);
+        let mut metadata = backend_metadata(&otel_cx, &request_id);
+
+        let chain = self.middleware.for_service(&service);
+        let call = CallInfo {
+            service: service.to_string(),
+            method: method.to_string(),
+            request_id: request_id.to_string(),
+        };
+        let mut recording = None;
+        // a binary body is only forwarded while the request stays as decoded
+        let received = ctx.protobuf_body.is_some().then(|| req.data.clone());
+        let (mut response, grpc_code) = match self.check_rate_limit(&req, &ctx).await {
+            Some(limited) => (limited, NO_GRPC_CODE.to_string()),
+            None => match middleware_chain::run_request(&chain, &mut req, &mut metadata).await {
+                Ok(()) => {
+                    if self.recorder.should_record(&service) {
+                        recording = Some((req.data.clone(), metadata.clone()));
+                    }
+                    self.dispatch(req, &ctx, metadata, received).await
+                }
+                Err(e) => (
+                    Response {
+                        message: std::borrow::Cow::Owned(e.message),
+                        status: ResponseErrors::Error.message(),
+                        data: None,
+                        status_code: e.status_code,
+                        ..Default::default()
+                    },
+                    NO_GRPC_CODE.to_string(),
+                ),
+            },
+        };
+        let reply = response
+            .protobuf_body
+            .is_some()
+            .then(|| response.data.clone());
+        middleware_chain::run_response(&chain, &call, &mut response).await;
+        // the backend's bytes no longer match data rewritten by middleware
+        if reply.is_some_and(|data| data != response.data) {
+            response.protobuf_body = None;
+        }
+
+        end_server_span(&otel_cx, &response, &grpc_code);
+
+        let elapsed = started_at.elapsed();
+        gateway_metrics::observe_request(
+            &service_label,
+            &method_label,
+            &grpc_code,
+            response.status_code.as_u16(),
+            elapsed,
+        );
+        tracing::Span::current().record("status", response.status_code.as_u16());
+        response.request_id = Some(request_id);
+        if let Some((data, metadata)) = recording {
+            self.record_call(
+                &service, &method, descriptor, data, &metadata, &ctx, &response, &grpc_code,
+                elapsed,
+            );
+        }
+        if response.status_code.is_success() {
+            tracing::debug!(grpc_code = %grpc_code, latency_ms = elapsed.as_millis() as u64, "request completed");
+        } else {
+            tracing::warn!(
+                grpc_code = %grpc_code,
+                latency_ms = elapsed.as_millis() as u64,
+                error = %response.message,
+                "request failed"
+            );
+        }
+        response
+    }
+
+    /// Calls a server streaming method. Rate limits, request middleware,
+    /// validation and the circuit breaker apply to opening the stream; the
+    /// error response is returned when it cannot be opened. Metrics, the
+    /// access log and the traffic recording report how opening went, and the
+    /// call counts as in flight until the stream is dropped.
+    pub async fn invoke_stream(
+        &self,
+        mut req: model::RequestType,
+        ctx: model::RequestContext,
+    ) -> Result<BoxStream<'static, anyhow::Result<serde_json::Value>>, Response> {
+        req.service = self.routing.resolve_service(&req.service);
+        let service = req.service.to_string();
+        let method = req.method.to_string();
+        let started_at = Instant::now();
+        let descriptor = self.method_descriptor(&service, &method).await;
+        let (service_label, method_label) =
+            self.metric_labels(&service, descriptor.as_ref().map(|_| method.as_str()));
+        let in_flight = gateway_metrics::InFlightGuard::new(&service_label, &method_label);
+        let request_id = request_id::resolve(&ctx.headers);
+        let request_body = self
+            .access_log
+            .include_request_body
+            .then(|| req.data.clone());
+
+        let otel_cx = start_server_span(&service, &method, &request_id, &ctx);
+        let mut metadata = backend_metadata(&otel_cx, &request_id);
+        let opened = match self.open_stream(&mut req, &ctx, &mut metadata).await {
+            Ok(messages) => Ok(messages),
+            Err((mut response, grpc_code)) => {
+                response.request_id = Some(request_id.to_string());
+                Err((response, grpc_code))
+            }
+        };
+        let success = Response {
+            message: ResponseErrors::Success.message(),
+            status: ResponseErrors::Success.message(),
+            status_code: StatusCode::OK,
+            request_id: Some(request_id.to_string()),
+            ..Default::default()
+        };
+        let ok_code = format!("{:?}", tonic::Code::Ok);
+        let (response, grpc_code) = match &opened {
+            Ok(_) => (&success, &ok_code),
+            Err((response, grpc_code)) => (response, grpc_code),
+        };
+        end_server_span(&otel_cx, response, grpc_code);
+
+        let elapsed = started_at.elapsed();
+        gateway_metrics::observe_request(
+            &service_label,
+            &method_label,
+            grpc_code,
+            response.status_code.as_u16(),
+            elapsed,
+        );
+        self.access_log.emit(AccessLogEntry {
+            request_id: Some(request_id.to_string()),
+            service: service.to_string(),
+            method: method.to_string(),
+            status: response.status_code.as_u16(),
+            latency_ms: elapsed.as_secs_f64() * 1000.0,
+            bytes: 0,
+            client_ip: ctx.client_ip.clone(),
+            request: request_body,
+        });
+        if self.recorder.should_record(&service) {
+            self.record_call(
+                &service, &method, descriptor, req.data, &metadata, &ctx, response, grpc_code,
+                elapsed,
+            );
+        }
+
+        match opened {
+            Ok(messages) => Ok(messages
+                .map(move |message| {
+                    let _in_flight = &in_flight;
+                    message
+                })
+                .boxed()),
+            Err((response, _)) => Err(response),
+        }
+    }
+
+    // Opens a server stream, or returns the error response and the gRPC
+    // code reported in metrics
+    async fn open_stream(
+        &self,
+        req: &mut model::RequestType,
+        ctx: &model::RequestContext,
+        metadata: &mut MetadataMap,
+    ) -> Result<BoxStream<'static, anyhow::Result<serde_json::Value>>, (Response, String)> {
+        let error = |message: std::borrow::Cow<'static, str>, status_code| {
+            (
+                Response {
+                    message,
+                    status: ResponseErrors::Error.message(),
+                    data: None,
+                    status_code,
+                    ..Default::default()
+                },
+                NO_GRPC_CODE.to_string(),
+            )
+        };
+        if let Some(limited) = self.check_rate_limit(req, ctx).await {
+            return Err((limited, NO_GRPC_CODE.to_string()));
+        }
+
+        let chain = self.middleware.for_service(&req.service);
+        if let Err(e) = middleware_chain::run_request(&chain, req, metadata).await {
+            return Err(error(std::borrow::Cow::Owned(e.message), e.status_code));
+        }
+
+        let (_, service_config) = self.resolve_backend(&req.service, &req.method, ctx);
+        let Some(service_config) = service_config else {
+            return Err(error(
+                ResponseErrors::ServiceNotRegister(req.service.to_string()).message(),
+                StatusCode::BAD_REQUEST,
+            ));
+        };
+        let client = match self.get_client(&service_config.endpoint).await {
+            Ok(client) => client,
+            Err(e) => {
+                return Err(error(
+                    std::borrow::Cow::Owned(e.to_string()),
+                    StatusCode::BAD_GATEWAY,
+                ));
+            }
+        };
+        let format = match self.json_format.resolve(&req.service, &ctx.query) {
+            Ok(format) => format,
+            Err(e) => {
+                return Err(error(
+                    ResponseErrors::InvalidJsonFormat(e).message(),
+                    StatusCode::BAD_REQUEST,
+                ));
+            }
+        };
+        if let Ok(Some(method)) = client.method_descriptor(&req.service, &req.method).await
+            && let Err(e) = self
+                .validator
+                .validate(&req.service, &method.input(), &mut req.data)
+        {
+            let (mut response, grpc_code) = error(
+                ResponseErrors::RequestValidationFailed.message(),
+                StatusCode::BAD_REQUEST,
+            );
+            response.data = Some(serde_json::json!({ "violations": e.violations }));
+            return Err((response, grpc_code));
+        }
+
+        let breaker = service_config.breaker.clone().unwrap();
+        let opened = breaker
+            .call(|| {
+                client.invoke_server_stream(
+                    &req.service,
+                    &req.method,
+                    req.data.clone(),
+                    service_config.clone(),
+                    metadata.clone(),
+                    format,
+                )
+            })
+            .await;
+        opened.map_err(|e| {
+            let status_code = if e.to_string().to_lowercase().contains("status: unavailable") {
+                StatusCode::SERVICE_UNAVAILABLE
+            } else {
+                StatusCode::BAD_REQUEST
             };
+            let (mut response, _) = error(std::borrow::Cow::Owned(e.to_string()), status_code);
+            response.grpc_code = e.downcast_ref::<tonic::Status>().map(|s| s.code());
+            (response, grpc_code_label(&e))
+        })
+    }
+
+    // Service and method labels of the request metrics. Names no backend
+    // knows come from the client, so they are collapsed to one label rather
+    // than creating a series each
+    fn metric_labels(&self, service: &str, method: Option<&str>) -> (String, String) {
+        if let Some(method) = method {
+            return (service.to_string(), method.to_string());
+        }
+        let known_service = self
+            .service_registry
+            .discover(service.to_string())
+            .is_some()
+            || !self.service_registry.versions(service).is_empty();
+        let service = if known_service {
+            service
+        } else {
+            UNKNOWN_LABEL
+        };
+        (service.to_string(), UNKNOWN_LABEL.to_string())
+    }
+
+    // Returns the rejection response when the caller is over its limit
+    async fn check_rate_limit(
+        &self,
+        req: &model::RequestType,
+        ctx: &model::RequestContext,
+    ) -> Option<Response> {
+        match self
+            .rate_limiter
+            .check(&req.service, &req.method, ctx)
+            .await
+        {
+            Ok(RateLimitDecision::Allowed) => None,
+            Ok(RateLimitDecision::Limited { retry_after }) => {
+                // Retry-After is expressed in whole seconds, round up
+                let retry_after_secs =
+                    retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+                Some(Response {
+                    message: ResponseErrors::RateLimitExceeded.message(),
+                    status: ResponseErrors::Error.message(),
+                    data: None,
+                    status_code: StatusCode::TOO_MANY_REQUESTS,
+                    headers: HashMap::from([(
+                        String::from("retry-after"),
+                        retry_after_secs.to_string(),
+                    )]),
+                    ..Default::default()
+                })
+            }
+            Err(e) => {
+                // fail open so a broken limiter store does not take the gateway down
+                tracing::error!(error = %e, "rate limiter unavailable, allowing request");
+                None
+            }
+        }
+    }
+
+    // Calls the backend and reports the gRPC code alongside the response for metrics
+    async fn dispatch(
+        &self,
+        mut req: model::RequestType,
+        ctx: &model::RequestContext,
+        metadata: MetadataMap,
+        received: Option<serde_json::Value>,
+    ) -> (Response, String) {
+        let mut format = match self.json_format.resolve(&req.service, &ctx.query) {
+            Ok(format) => format,
+            Err(e) => {
+                return (
+                    Response {
+                        message: ResponseErrors::InvalidJsonFormat(e).message(),
+                        status: ResponseErrors::Error.message(),
+                        data: None,
+                        status_code: StatusCode::BAD_REQUEST,
+                        ..Default::default()
+                    },
+                    NO_GRPC_CODE.to_string(),
+                );
+            }
+        };
+
+        format.ignore_unknown_fields = self.ignores_unknown_fields(&req.service, &ctx.query);
+
+        if let Some(config) = self.mock.config_for(&req.service) {
+            return mock_mode::respond(self, &config, &req, &format).await;
+        }
+
+        let (version, service) = self.resolve_backend(&req.service, &req.method, ctx);
+
+        if service.is_none() {
+            return (
+                Response {
+                    message: ResponseErrors::ServiceNotRegister(req.service.to_string()).message(),
+                    status: ResponseErrors::Error.message(),
+                    data: None,
+                    status_code: StatusCode::BAD_REQUEST,
+                    ..Default::default()
+                },
+                NO_GRPC_CODE.to_string(),
+            );
         }
 
         let service_config = service.unwrap();
+
         // should check the circute breaker is allowing or not to call the api
         let grpc_client = self.get_client(&service_config.endpoint).await;
         if grpc_client.is_err() {
             let e = grpc_client.err().unwrap();
 
             if e.to_string().to_lowercase().contains("transport error") {
-                return Response {
-                    message: ResponseErrors::TransportFailure.message(),
+                return (
+                    Response {
+                        message: ResponseErrors::TransportFailure.message(),
+                        status: ResponseErrors::Error.message(),
+                        data: None,
+                        status_code: StatusCode::BAD_GATEWAY,
+                        ..Default::default()
+                    },
+                    NO_GRPC_CODE.to_string(),
+                );
+            }
+            return (
+                Response {
+                    message: std::borrow::Cow::Owned(e.to_string()),
                     status: ResponseErrors::Error.message(),
                     data: None,
-                    status_code: StatusCode::BAD_GATEWAY,
-                };
-            }
-            return Response {
-                message: std::borrow::Cow::Owned(e.to_string()),
-                status: ResponseErrors::Error.message(),
-                data: None,
-                status_code: StatusCode::BAD_REQUEST,
-            };
+                    status_code: StatusCode::BAD_REQUEST,
+                    ..Default::default()
+                },
+                NO_GRPC_CODE.to_string(),
+            );
         }
         let client = grpc_client.unwrap();
+
+        let mut validation = self.validator.options_for(&req.service);
+        validation.reject_unknown_fields = !format.ignore_unknown_fields;
+        let method_desc = client
+            .method_descriptor(&req.service, &req.method)
+            .await
+            .ok()
+            .flatten();
+        if let Some(method) = &method_desc
+            && let Err(e) = validate_with(&validation, &method.input(), &mut req.data)
+        {
+            tracing::debug!(violations = e.violations.len(), "request failed validation");
+            return (
+                Response {
+                    message: ResponseErrors::RequestValidationFailed.message(),
+                    status: ResponseErrors::Error.message(),
+                    data: Some(serde_json::json!({ "violations": e.violations })),
+                    status_code: StatusCode::BAD_REQUEST,
+                    ..Default::default()
+                },
+                NO_GRPC_CODE.to_string(),
+            );
+        }
+
+        if let Some(method) = &method_desc {
+            self.resolve_field_mask(&mut req, &method.input(), &mut format);
+        }
+
+        let cache_lookup = self.lookup_cache(&client, &req, ctx, &format).await;
+        if let CacheLookup::Hit(response) = cache_lookup {
+            return (response, format!("{:?}", tonic::Code::Ok));
+        }
+
+        // binary callers get the backend's bytes back when their body is
+        // forwarded unchanged
+        let passthrough = match (&ctx.protobuf_body, &method_desc) {
+            (Some(body), Some(method))
+                if format.fields.is_empty() && received.as_ref() == Some(&req.data) =>
+            {
+                Some((body.clone(), method.output()))
+            }
+            _ => None,
+        };
+
+        // identical in-flight calls to opted-in methods share one backend
+        // call, except binary pass-through calls which need their own bytes
+        let flight_key =
+            if passthrough.is_none() && self.coalescing.is_enabled(&req.service, &req.method) {
+                client
+                    .encode_request(&req.service, &req.method, &req.data, &format)
+                    .await
+                    .ok()
+                    .map(|bytes| FlightKey::new(&req.service, &req.method, bytes, format.clone()))
+            } else {
+                None
+            };
+
+        // sampled calls are repeated against the shadow backend once answered
+        let shadow = self.shadow.sample(&req.service).map(|config| {
+            let call = ShadowCall {
+                service: req.service.to_string(),
+                method: req.method.to_string(),
+                data: req.data.clone(),
+                metadata: metadata.clone(),
+                format: format.clone(),
+                auth_config: service_config.auth_config.clone(),
+            };
+            (config, call)
+        });
+        let backend_started_at = Instant::now();
+
         let breaker = service_config.breaker.clone().unwrap();
-        let result = breaker
-            .call(|| async move {
-                let res = client
-                    .invoke(
-                        &req.service,
-                        &req.method,
-                        req.data.clone(),
-                        service_config.clone(),
-                    )
-                    .await?;
-                Ok(res)
+        // metrics are labelled with the called service, also when a method
+        // route sends it to another backend
+        let service_name = req.service.to_string();
+        let limiter = self.concurrency.get(&req.service);
+        let call_breaker = breaker.clone();
+        let backend_call = || async move {
+            // hold a concurrency slot for the whole backend call
+            let mut permit = match &limiter {
+                Some(limiter) => Some(limiter.acquire().await?),
+                None => None,
+            };
+            let result = call_breaker
+                .call(|| async move {
+                    let Some((body, output)) = passthrough else {
+                        let res = client
+                            .invoke(
+                                &req.service,
+                                &req.method,
+                                req.data.clone(),
+                                service_config.clone(),
+                                metadata,
+                                &format,
+                            )
+                            .await?;
+                        return Ok((res, None));
+                    };
+                    let bytes = client
+                        .invoke_bytes(
+                            &req.service,
+                            &req.method,
+                            body.to_vec(),
+                            service_config.clone(),
+                            metadata,
+                        )
+                        .await?;
+                    let message = DynamicMessage::decode(output, bytes.clone())?;
+                    Ok((format.to_json(&message)?, Some(bytes)))
+                })
+                .await;
+            // only backend faults and timeouts shrink an adaptive limit
+            if let Some(permit) = permit.as_mut() {
+                match &result {
+                    Ok(_) => permit.succeeded(),
+                    Err(e)
+                        if e.downcast_ref::<tonic::Status>()
+                            .is_some_and(|status| is_server_fault(status.code())) =>
+                    {
+                        permit.failed()
+                    }
+                    Err(_) => {}
+                }
+            }
+            result
+        };
+        let (result, protobuf_body) = match flight_key {
+            Some(key) => {
+                let call = || async { backend_call().await.map(|(res, _)| res) };
+                (self.coalescing.execute(key, call).await, None)
+            }
+            None => match backend_call().await {
+                Ok((res, bytes)) => (Ok(res), bytes),
+                Err(e) => (Err(Arc::new(e)), None),
+            },
+        };
+        let breaker_state = breaker.state().await;
+        gateway_metrics::record_breaker_state(
+            &service_name,
+            version.as_deref().unwrap_or_default(),
+            &breaker_state,
+        );
+
+        // compared only when the primary backend answered, not when the call
+        // was shed or stopped by the breaker
+        let primary_code = match &result {
+            Ok(_) => Some(tonic::Code::Ok),
+            Err(e) => e.downcast_ref::<tonic::Status>().map(|s| s.code()),
+        };
+        if let (Some((config, call)), Some(code)) = (shadow, primary_code) {
+            let primary = PrimaryOutcome {
+                grpc_code: format!("{:?}", code),
+                response: result.as_ref().ok().cloned(),
+                elapsed: backend_started_at.elapsed(),
+            };
+            self.shadow.mirror(config, call, primary);
+        }
+
+        if let Some(version) = &version {
+            let failed = match &result {
+                Ok(_) => false,
+                Err(e) if e.downcast_ref::<ShedReason>().is_some() => false,
+                Err(e) => e
+                    .downcast_ref::<tonic::Status>()
+                    .is_none_or(|status| is_server_fault(status.code())),
+            };
+            let breaker_open = matches!(breaker_state, CircuitBreakerState::Open { .. });
+            self.traffic_split
+                .observe(&service_name, version, failed, breaker_open);
+        }
+
+        let (mut response, grpc_code) =
+            self.backend_response(result, cache_lookup, ctx, &service_name);
+        if response.status_code == StatusCode::OK {
+            response.protobuf_body = protobuf_body;
+        }
+        if let Some(version) = version {
+            response
+                .headers
+                .insert(BACKEND_VERSION_HEADER.to_string(), version);
+        }
+        (response, grpc_code)
+    }
+
+    // Unknown request fields are dropped when the service's validation or
+    // JSON mapping allows it. A request's `unknown` option can make that
+    // stricter but never looser than the service's policy
+    fn ignores_unknown_fields(&self, service: &str, query: &HashMap<String, String>) -> bool {
+        let service_ignores = !self.validator.options_for(service).reject_unknown_fields
+            || self.json_format.format_for(service).ignore_unknown_fields;
+        service_ignores && JsonFormats::requested_unknown_fields(query) != Some(false)
+    }
+
+    // The backend answering a call: the backend of a method-level route,
+    // one of the service's versions when its traffic is split, its own
+    // registration, or the backend of its package, in that order. The
+    // version is only returned when one of its registrations answers
+    fn resolve_backend(
+        &self,
+        service: &str,
+        method: &str,
+        ctx: &model::RequestContext,
+    ) -> (Option<String>, Option<ServiceConfig>) {
+        if let Some(backend) = self.routing.method_backend(service, method) {
+            return (None, self.service_registry.discover(backend));
+        }
+        if let Some(version) = self.traffic_split.choose(service, ctx)
+            && let Some(config) = self.service_registry.discover_version(service, &version)
+        {
+            return (Some(version), Some(config));
+        }
+        (None, self.registered_backend(service))
+    }
+
+    // The service's own registration, or the backend of its package
+    fn registered_backend(&self, service: &str) -> Option<ServiceConfig> {
+        self.service_registry
+            .discover(service.to_string())
+            .or_else(|| {
+                self.routing
+                    .package_backend(service)
+                    .and_then(|backend| self.service_registry.discover(backend))
             })
-            .await;
+    }
+
+    // Builds the response for the outcome of a backend call
+    fn backend_response(
+        &self,
+        result: FlightResult,
+        cache_lookup: CacheLookup,
+        ctx: &model::RequestContext,
+        service_name: &str,
+    ) -> (Response, String) {
         match result {
             Ok(response) => {
-                let converted_data = serde_json::from_value(response).ok();
-                Response {
-                    message: ResponseErrors::Success.message(),
-                    status: ResponseErrors::Success.message(),
-                    data: converted_data,
-                    status_code: StatusCode::OK,
+                if let CacheLookup::Miss { key, ttl } = cache_lookup
+                    && let Some(entry) = self.response_cache.put(key, response.clone(), ttl)
+                {
+                    return (
+                        cached_response(entry, ctx, "MISS"),
+                        format!("{:?}", tonic::Code::Ok),
+                    );
                 }
+
+                let converted_data = serde_json::from_value(response).ok();
+                (
+                    Response {
+                        message: ResponseErrors::Success.message(),
+                        status: ResponseErrors::Success.message(),
+                        data: converted_data,
+                        status_code: StatusCode::OK,
+                        ..Default::default()
+                    },
+                    format!("{:?}", tonic::Code::Ok),
+                )
             }
             Err(e) => {
+                if let Some(reason) = e.downcast_ref::<ShedReason>() {
+                    gateway_metrics::record_shed(service_name, reason.label());
+                    tracing::warn!(reason = %reason, "request shed by concurrency limiter");
+                    return (
+                        Response {
+                            message: ResponseErrors::LoadShed(reason.to_string()).message(),
+                            status: ResponseErrors::Error.message(),
+                            data: None,
+                            status_code: StatusCode::SERVICE_UNAVAILABLE,
+                            ..Default::default()
+                        },
+                        NO_GRPC_CODE.to_string(),
+                    );
+                }
+
+                let grpc_code = grpc_code_label(&e);
+                let backend_code = e.downcast_ref::<tonic::Status>().map(|s| s.code());
                 if e.to_string().to_lowercase().contains("status: unavailable") {
-                    return Response {
-                        message: ResponseErrors::ServiceUnAvailable.message(),
+                    return (
+                        Response {
+                            message: ResponseErrors::ServiceUnAvailable.message(),
+                            status: ResponseErrors::Error.message(),
+                            data: None,
+                            status_code: StatusCode::SERVICE_UNAVAILABLE,
+                            grpc_code: backend_code,
+                            ..Default::default()
+                        },
+                        grpc_code,
+                    );
+                }
+                (
+                    Response {
+                        message: std::borrow::Cow::Owned(e.to_string()),
                         status: ResponseErrors::Error.message(),
                         data: None,
-                        status_code: StatusCode::SERVICE_UNAVAILABLE,
-                    };
-                }
-                Response {
-                    message: std::borrow::Cow::Owned(e.to_string()),
-                    status: ResponseErrors::Error.message(),
-                    data: None,
-                    status_code: StatusCode::BAD_REQUEST,
+                        status_code: StatusCode::BAD_REQUEST,
+                        grpc_code: backend_code,
+                        ..Default::default()
+                    },
+                    grpc_code,
+                )
+            }
+        }
+    }
+
+    /// Descriptors of registered services whose backend is reachable, along
+    /// with when each was last refreshed.
+    pub async fn registered_descriptors(&self) -> Vec<(ServiceDescriptor, Instant)> {
+        let mut configs = self.service_registry.services();
+        configs.sort_by(|a, b| a.service_name.cmp(&b.service_name));
+
+        let mut descriptors = Vec::new();
+        for config in configs {
+            let Ok(client) = self.get_client(&config.endpoint).await else {
+                continue;
+            };
+            if let Some(service) = client.service_descriptor(&config.service_name).await {
+                descriptors.push((service, client.descriptors_refreshed_at()));
+            }
+        }
+        descriptors
+    }
+
+    /// Every service reflected from the registered backends, including ones
+    /// that were not registered by name.
+    pub async fn discovered_services(&self) -> Vec<ServiceDescriptor> {
+        let mut endpoints: Vec<String> = self
+            .service_registry
+            .services()
+            .into_iter()
+            .map(|config| config.endpoint)
+            .collect();
+        endpoints.sort();
+        endpoints.dedup();
+
+        let mut services: Vec<ServiceDescriptor> = Vec::new();
+        for endpoint in endpoints {
+            let Ok(client) = self.get_client(&endpoint).await else {
+                continue;
+            };
+            for service in client.reflected_services().await {
+                if !services
+                    .iter()
+                    .any(|s| s.full_name() == service.full_name())
+                {
+                    services.push(service);
                 }
             }
         }
+        services.sort_by(|a, b| a.full_name().cmp(b.full_name()));
+        services
+    }
+
+    pub async fn find_message(&self, full_name: &str) -> Option<MessageDescriptor> {
+        self.discovered_services()
+            .await
+            .iter()
+            .find_map(|service| service.parent_pool().get_message_by_name(full_name))
+    }
+
+    // Queues a sampled call for the traffic recording
+    #[allow(clippy::too_many_arguments)]
+    fn record_call(
+        &self,
+        service: &str,
+        method: &str,
+        descriptor: Option<MethodDescriptor>,
+        data: serde_json::Value,
+        metadata: &MetadataMap,
+        ctx: &model::RequestContext,
+        response: &Response,
+        grpc_code: &str,
+        elapsed: Duration,
+    ) {
+        let format = self
+            .json_format
+            .resolve(service, &ctx.query)
+            .unwrap_or_default();
+        let call = RecordedCall {
+            recorded_at: chrono::Utc::now(),
+            request_id: response.request_id.clone(),
+            service: service.to_string(),
+            method: method.to_string(),
+            request: data,
+            metadata: traffic_recorder::metadata_map(metadata),
+            request_protobuf: None,
+            format: None,
+            fields: None,
+            status: response.status_code.as_u16(),
+            grpc_code: grpc_code.to_string(),
+            message: response.message.to_string(),
+            response: response.data.clone(),
+            latency_ms: elapsed.as_secs_f64() * 1000.0,
+        };
+        self.recorder.record(PendingCall {
+            call,
+            method: descriptor,
+            format,
+        });
+    }
+
+    /// Descriptor of a method of a registered service, or of a mocked
+    /// service given its descriptors.
+    pub async fn method_descriptor(&self, service: &str, method: &str) -> Option<MethodDescriptor> {
+        let service = &self.routing.resolve_service(service);
+        if let Some(method) = self.mock.method_descriptor(service, method) {
+            return Some(method);
+        }
+        // the stable version stands for a split service, so the answer does
+        // not depend on which version a call would be sent to
+        let config = match self.routing.method_backend(service, method) {
+            Some(backend) => self.service_registry.discover(backend),
+            None => self
+                .traffic_split
+                .config_for(service)
+                .and_then(|split| {
+                    self.service_registry
+                        .discover_version(service, &split.stable)
+                })
+                .or_else(|| self.registered_backend(service)),
+        }?;
+        let client = self.get_client(&config.endpoint).await.ok()?;
+        client
+            .method_descriptor(service, method)
+            .await
+            .ok()
+            .flatten()
+    }
+
+    /// OpenAPI document for the registered services, regenerated when the
+    /// registry or the reflected descriptors change.
+    pub async fn openapi_document(&self) -> Arc<serde_json::Value> {
+        let descriptors = self.registered_descriptors().await;
+        let fingerprint = descriptors
+            .iter()
+            .map(|(service, refreshed_at)| (service.full_name().to_string(), *refreshed_at))
+            .collect();
+        let services: Vec<ServiceDescriptor> = descriptors
+            .into_iter()
+            .map(|(service, _)| service)
+            .collect();
+
+        self.openapi
+            .get_or_build(fingerprint, || openapi::build_document(&services))
+    }
+
+    // Projects the response with the request's own read mask when no `fields`
+    // parameter was given, otherwise forwards `fields` to opted-in backends
+    fn resolve_field_mask(
+        &self,
+        req: &mut model::RequestType,
+        input: &MessageDescriptor,
+        format: &mut JsonFormat,
+    ) {
+        if format.fields.is_empty() {
+            if let Some(mask) = FieldMask::from_request(input, &req.data) {
+                format.fields = mask;
+            }
+            return;
+        }
+        if self.read_mask.is_enabled(&req.service)
+            && let Some(field) = field_mask::read_mask_field(input)
+            && let Some(object) = req.data.as_object_mut()
+            && !object.contains_key(field.json_name())
+            && !object.contains_key(field.name())
+        {
+            object.insert(
+                field.json_name().to_string(),
+                serde_json::Value::String(format.fields.to_json_string()),
+            );
+        }
+    }
+
+    /// GraphQL schema for the registered services, regenerated when the
+    /// registry or the reflected descriptors change.
+    pub async fn graphql_schema(&self) -> Result<async_graphql::dynamic::Schema, String> {
+        let descriptors = self.registered_descriptors().await;
+        let fingerprint = descriptors
+            .iter()
+            .map(|(service, refreshed_at)| (service.full_name().to_string(), *refreshed_at))
+            .collect();
+        let services: Vec<ServiceDescriptor> = descriptors
+            .into_iter()
+            .map(|(service, _)| service)
+            .collect();
+
+        self.graphql
+            .get_or_build(fingerprint, || graphql_schema::build_schema(&services))
+            .map_err(|e| e.to_string())
     }
 
-    async fn get_client(&self, service_endpoint: &str) -> Result<GrpcGateway, Box<dyn Error>> {
+    // Resolves whether the call is cacheable and serves fresh entries
+    async fn lookup_cache(
+        &self,
+        client: &GrpcGateway,
+        req: &model::RequestType,
+        ctx: &model::RequestContext,
+        format: &JsonFormat,
+    ) -> CacheLookup {
+        let cache_control = ctx
+            .headers
+            .get("cache-control")
+            .map(|v| v.to_lowercase())
+            .unwrap_or_default();
+        if cache_control.contains("no-store") {
+            return CacheLookup::Disabled;
+        }
+
+        let method_desc = match client.method_descriptor(&req.service, &req.method).await {
+            Ok(Some(method_desc)) => method_desc,
+            _ => return CacheLookup::Disabled,
+        };
+        let Some(ttl) = self.response_cache.ttl_for(&req.service, &method_desc) else {
+            return CacheLookup::Disabled;
+        };
+        // invalid payloads are left for the backend call to report
+        let Ok(request_bytes) = client
+            .encode_request(&req.service, &req.method, &req.data, format)
+            .await
+        else {
+            return CacheLookup::Disabled;
+        };
+
+        let key = self.response_cache.key(
+            &req.service,
+            &req.method,
+            request_bytes,
+            format.clone(),
+            &ctx.headers,
+        );
+        if !cache_control.contains("no-cache")
+            && let Some(entry) = self.response_cache.get(&key)
+        {
+            return CacheLookup::Hit(cached_response(entry, ctx, "HIT"));
+        }
+        CacheLookup::Miss { key, ttl }
+    }
+
+    async fn get_client(
+        &self,
+        service_endpoint: &str,
+    ) -> Result<GrpcGateway, Box<dyn Error + Send + Sync>> {
         let mut grpc_client = match grpc_client_map.lock() {
             Ok(mp) => mp.get(service_endpoint).cloned(),
             Err(_) => None,
@@ -145,3 +1107,114 @@ impl Gateway {
         Ok(grpc_client.unwrap())
     }
 }
+
+// Server span of a call, continuing the caller's trace from its headers
+fn start_server_span(
+    service: &str,
+    method: &str,
+    request_id: &str,
+    ctx: &model::RequestContext,
+) -> opentelemetry::Context {
+    let parent = propagation::extract_context(&ctx.headers);
+    let tracer = global::tracer("grpc_gateway");
+    let span = tracer
+        .span_builder(format!("{}/{}", service, method))
+        .with_kind(SpanKind::Server)
+        .with_attributes([
+            KeyValue::new("rpc.system", "grpc"),
+            KeyValue::new("rpc.service", service.to_string()),
+            KeyValue::new("rpc.method", method.to_string()),
+            KeyValue::new("request.id", request_id.to_string()),
+        ])
+        .start_with_context(&tracer, &parent);
+    parent.with_span(span)
+}
+
+fn end_server_span(otel_cx: &opentelemetry::Context, response: &Response, grpc_code: &str) {
+    let otel_span = otel_cx.span();
+    otel_span.set_attribute(KeyValue::new(
+        "http.response.status_code",
+        response.status_code.as_u16() as i64,
+    ));
+    otel_span.set_attribute(KeyValue::new("rpc.grpc.status_code", grpc_code.to_string()));
+    if !response.status_code.is_success() {
+        otel_span.set_status(Status::error(response.message.to_string()));
+    }
+    otel_span.end();
+}
+
+// Metadata sent to the backend: the trace context and the request id
+fn backend_metadata(otel_cx: &opentelemetry::Context, request_id: &str) -> MetadataMap {
+    let mut metadata = MetadataMap::new();
+    propagation::inject_context(otel_cx, &mut metadata);
+    if let Ok(value) = MetadataValue::from_str(request_id) {
+        metadata.insert(request_id::REQUEST_ID_HEADER, value);
+    }
+    metadata
+}
+
+// failures caused by the backend rather than by the request
+fn is_server_fault(code: tonic::Code) -> bool {
+    matches!(
+        code,
+        tonic::Code::Unknown
+            | tonic::Code::Internal
+            | tonic::Code::Unavailable
+            | tonic::Code::DeadlineExceeded
+            | tonic::Code::DataLoss
+    )
+}
+
+fn grpc_code_label(e: &anyhow::Error) -> String {
+    match e.downcast_ref::<tonic::Status>() {
+        Some(status) => format!("{:?}", status.code()),
+        None => format!("{:?}", tonic::Code::Unknown),
+    }
+}
+
+enum CacheLookup {
+    Disabled,
+    Hit(Response),
+    Miss { key: CacheKey, ttl: Duration },
+}
+
+// Builds the HTTP response for a cache entry, honouring If-None-Match
+fn cached_response(
+    entry: CachedResponse,
+    ctx: &model::RequestContext,
+    cache_status: &str,
+) -> Response {
+    let headers = HashMap::from([
+        (String::from("etag"), entry.etag.to_string()),
+        (
+            String::from("cache-control"),
+            format!("max-age={}", entry.remaining_ttl().as_secs()),
+        ),
+        (String::from("x-cache"), cache_status.to_string()),
+    ]);
+
+    let not_modified = ctx.headers.get("if-none-match").is_some_and(|tags| {
+        tags.split(',')
+            .map(|tag| tag.trim().trim_start_matches("W/"))
+            .any(|tag| tag == "*" || tag == entry.etag)
+    });
+    if not_modified {
+        return Response {
+            message: ResponseErrors::Success.message(),
+            status: ResponseErrors::Success.message(),
+            data: None,
+            status_code: StatusCode::NOT_MODIFIED,
+            headers,
+            ..Default::default()
+        };
+    }
+
+    Response {
+        message: ResponseErrors::Success.message(),
+        status: ResponseErrors::Success.message(),
+        data: Some(entry.data),
+        status_code: StatusCode::OK,
+        headers,
+        ..Default::default()
+    }
+}
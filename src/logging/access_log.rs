@@ -0,0 +1,106 @@
+use serde::Serialize;
+use serde_json::Value;
+
+pub const REDACTED: &str = "[REDACTED]";
+
+#[derive(Debug, Clone)]
+pub struct AccessLogConfig {
+    pub enabled: bool,
+    /// log the JSON request payload alongside the entry
+    pub include_request_body: bool,
+    /// payload keys whose values are replaced with `[REDACTED]`, matched at any depth
+    pub redact_fields: Vec<String>,
+    /// hide the client ip address from the entry
+    pub redact_client_ip: bool,
+}
+
+impl Default for AccessLogConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            include_request_body: false,
+            redact_fields: vec![
+                String::from("password"),
+                String::from("token"),
+                String::from("access_token"),
+                String::from("refresh_token"),
+            ],
+            redact_client_ip: false,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct AccessLogEntry {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    pub service: String,
+    pub method: String,
+    pub status: u16,
+    pub latency_ms: f64,
+    pub bytes: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_ip: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request: Option<Value>,
+}
+
+impl AccessLogEntry {
+    /// Size of a call's JSON data, for calls answered as part of a larger
+    /// response such as a batch item or a GraphQL field.
+    pub fn json_bytes(data: Option<&Value>) -> usize {
+        data.and_then(|data| serde_json::to_vec(data).ok())
+            .map_or(0, |bytes| bytes.len())
+    }
+}
+
+impl AccessLogConfig {
+    /// Applies the redaction rules and writes the entry as a single JSON event
+    /// on the `grpc_gateway::access` target.
+    pub fn emit(&self, mut entry: AccessLogEntry) {
+        if !self.enabled {
+            return;
+        }
+
+        if self.redact_client_ip && entry.client_ip.is_some() {
+            entry.client_ip = Some(REDACTED.to_string());
+        }
+
+        entry.request = match entry.request.take() {
+            Some(mut body) if self.include_request_body => {
+                self.redact(&mut body);
+                Some(body)
+            }
+            _ => None,
+        };
+
+        match serde_json::to_string(&entry) {
+            Ok(line) => tracing::info!(target: "grpc_gateway::access", "{}", line),
+            Err(e) => tracing::warn!(error = %e, "failed to serialize access log entry"),
+        }
+    }
+
+    pub fn redact(&self, value: &mut Value) {
+        match value {
+            Value::Object(map) => {
+                for (key, field) in map.iter_mut() {
+                    if self
+                        .redact_fields
+                        .iter()
+                        .any(|f| f.eq_ignore_ascii_case(key))
+                    {
+                        *field = Value::String(REDACTED.to_string());
+                    } else {
+                        self.redact(field);
+                    }
+                }
+            }
+            Value::Array(items) => {
+                for item in items.iter_mut() {
+                    self.redact(item);
+                }
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,266 @@
+use std::time::Duration;
+
+use lazy_static::lazy_static;
+use prometheus::core::Collector;
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts, Registry, TextEncoder,
+};
+
+use crate::circuitbreaker::breaker::CircuitBreakerState;
+
+lazy_static! {
+    pub static ref REGISTRY: Registry = Registry::new();
+    static ref REQUESTS_TOTAL: IntCounterVec = register(
+        IntCounterVec::new(
+            Opts::new(
+                "grpc_gateway_requests_total",
+                "Total requests handled by the gateway"
+            ),
+            &["service", "method", "grpc_code", "http_status"],
+        )
+        .unwrap()
+    );
+    static ref REQUEST_DURATION: HistogramVec = register(
+        HistogramVec::new(
+            HistogramOpts::new(
+                "grpc_gateway_request_duration_seconds",
+                "Latency of requests handled by the gateway"
+            ),
+            &["service", "method"],
+        )
+        .unwrap()
+    );
+    static ref REQUESTS_IN_FLIGHT: IntGaugeVec = register(
+        IntGaugeVec::new(
+            Opts::new(
+                "grpc_gateway_requests_in_flight",
+                "Requests currently being processed by the gateway"
+            ),
+            &["service", "method"],
+        )
+        .unwrap()
+    );
+    static ref BREAKER_STATE: IntGaugeVec = register(
+        IntGaugeVec::new(
+            Opts::new(
+                "grpc_gateway_breaker_state",
+                "Circuit breaker state per service and version (0 = closed, 1 = half-open, 2 = open)"
+            ),
+            &["service", "version"],
+        )
+        .unwrap()
+    );
+    static ref REQUESTS_SHED_TOTAL: IntCounterVec = register(
+        IntCounterVec::new(
+            Opts::new(
+                "grpc_gateway_requests_shed_total",
+                "Requests rejected by the concurrency limiter"
+            ),
+            &["service", "reason"],
+        )
+        .unwrap()
+    );
+    static ref DESCRIPTOR_REFRESH_TOTAL: IntCounterVec = register(
+        IntCounterVec::new(
+            Opts::new(
+                "grpc_gateway_descriptor_refresh_total",
+                "Descriptor reflection refreshes per endpoint"
+            ),
+            &["endpoint", "result"],
+        )
+        .unwrap()
+    );
+    static ref AUTH_REFRESH_TOTAL: IntCounterVec = register(
+        IntCounterVec::new(
+            Opts::new(
+                "grpc_gateway_auth_refresh_total",
+                "JWT token refresh attempts per endpoint"
+            ),
+            &["endpoint", "result"],
+        )
+        .unwrap()
+    );
+    static ref SHADOW_REQUESTS_TOTAL: IntCounterVec = register(
+        IntCounterVec::new(
+            Opts::new(
+                "grpc_gateway_shadow_requests_total",
+                "Calls mirrored to a shadow backend by comparison result"
+            ),
+            &["service", "method", "result"],
+        )
+        .unwrap()
+    );
+    static ref SHADOW_DURATION: HistogramVec = register(
+        HistogramVec::new(
+            HistogramOpts::new(
+                "grpc_gateway_shadow_duration_seconds",
+                "Latency of calls mirrored to a shadow backend"
+            ),
+            &["service", "method"],
+        )
+        .unwrap()
+    );
+    static ref SHADOW_LATENCY_RATIO: HistogramVec = register(
+        HistogramVec::new(
+            HistogramOpts::new(
+                "grpc_gateway_shadow_latency_ratio",
+                "Shadow backend latency divided by the primary backend's"
+            )
+            .buckets(vec![0.25, 0.5, 0.8, 1.0, 1.25, 2.0, 4.0, 8.0]),
+            &["service", "method"],
+        )
+        .unwrap()
+    );
+    static ref RECORDINGS_DROPPED_TOTAL: IntCounterVec = register(
+        IntCounterVec::new(
+            Opts::new(
+                "grpc_gateway_recordings_dropped_total",
+                "Sampled calls left out of the traffic recording because its queue was full"
+            ),
+            &["service"],
+        )
+        .unwrap()
+    );
+    static ref BACKEND_WEIGHT: IntGaugeVec = register(
+        IntGaugeVec::new(
+            Opts::new(
+                "grpc_gateway_backend_weight",
+                "Current traffic weight of each version of a service"
+            ),
+            &["service", "version"],
+        )
+        .unwrap()
+    );
+    static ref ROLLBACKS_TOTAL: IntCounterVec = register(
+        IntCounterVec::new(
+            Opts::new(
+                "grpc_gateway_rollbacks_total",
+                "Automatic rollbacks of a service version's weight"
+            ),
+            &["service", "version", "reason"],
+        )
+        .unwrap()
+    );
+}
+
+fn register<T: Collector + Clone + 'static>(collector: T) -> T {
+    REGISTRY
+        .register(Box::new(collector.clone()))
+        .expect("metric can only be registered once");
+    collector
+}
+
+fn result_label(success: bool) -> &'static str {
+    if success { "success" } else { "failure" }
+}
+
+/// Tracks a request as in-flight until the guard is dropped.
+pub struct InFlightGuard {
+    service: String,
+    method: String,
+}
+
+impl InFlightGuard {
+    pub fn new(service: &str, method: &str) -> Self {
+        REQUESTS_IN_FLIGHT
+            .with_label_values(&[service, method])
+            .inc();
+        Self {
+            service: service.to_string(),
+            method: method.to_string(),
+        }
+    }
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        REQUESTS_IN_FLIGHT
+            .with_label_values(&[self.service.as_str(), self.method.as_str()])
+            .dec();
+    }
+}
+
+pub fn observe_request(
+    service: &str,
+    method: &str,
+    grpc_code: &str,
+    http_status: u16,
+    elapsed: Duration,
+) {
+    REQUESTS_TOTAL
+        .with_label_values(&[service, method, grpc_code, &http_status.to_string()])
+        .inc();
+    REQUEST_DURATION
+        .with_label_values(&[service, method])
+        .observe(elapsed.as_secs_f64());
+}
+
+/// `version` is empty for services whose traffic is not split.
+pub fn record_breaker_state(service: &str, version: &str, state: &CircuitBreakerState) {
+    let value = match state {
+        CircuitBreakerState::Closed => 0,
+        CircuitBreakerState::HalfOpen => 1,
+        CircuitBreakerState::Open { .. } => 2,
+    };
+    BREAKER_STATE
+        .with_label_values(&[service, version])
+        .set(value);
+}
+
+pub fn record_shed(service: &str, reason: &str) {
+    REQUESTS_SHED_TOTAL
+        .with_label_values(&[service, reason])
+        .inc();
+}
+
+pub fn record_descriptor_refresh(endpoint: &str, success: bool) {
+    DESCRIPTOR_REFRESH_TOTAL
+        .with_label_values(&[endpoint, result_label(success)])
+        .inc();
+}
+
+pub fn record_auth_refresh(endpoint: &str, success: bool) {
+    AUTH_REFRESH_TOTAL
+        .with_label_values(&[endpoint, result_label(success)])
+        .inc();
+}
+
+pub fn record_shadow(service: &str, method: &str, result: &str) {
+    SHADOW_REQUESTS_TOTAL
+        .with_label_values(&[service, method, result])
+        .inc();
+}
+
+pub fn observe_shadow_latency(service: &str, method: &str, shadow: Duration, primary: Duration) {
+    SHADOW_DURATION
+        .with_label_values(&[service, method])
+        .observe(shadow.as_secs_f64());
+    if !primary.is_zero() {
+        SHADOW_LATENCY_RATIO
+            .with_label_values(&[service, method])
+            .observe(shadow.as_secs_f64() / primary.as_secs_f64());
+    }
+}
+
+pub fn record_recording_dropped(service: &str) {
+    RECORDINGS_DROPPED_TOTAL.with_label_values(&[service]).inc();
+}
+
+pub fn record_backend_weight(service: &str, version: &str, weight: u32) {
+    BACKEND_WEIGHT
+        .with_label_values(&[service, version])
+        .set(weight as i64);
+}
+
+pub fn record_rollback(service: &str, version: &str, reason: &str) {
+    ROLLBACKS_TOTAL
+        .with_label_values(&[service, version, reason])
+        .inc();
+}
+
+/// Renders every gateway metric in the Prometheus text exposition format.
+pub fn gather() -> anyhow::Result<String> {
+    let mut buffer = Vec::new();
+    TextEncoder::new().encode(&REGISTRY.gather(), &mut buffer)?;
+    Ok(String::from_utf8(buffer)?)
+}
@@ -0,0 +1,239 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, RwLock};
+
+use async_trait::async_trait;
+use reqwest::StatusCode;
+use serde_json::Value;
+use tonic::metadata::MetadataMap;
+
+use crate::utils::model::RequestType;
+use crate::utils::response::Response;
+
+/// The call a response hook runs for.
+#[derive(Debug, Clone)]
+pub struct CallInfo {
+    pub service: String,
+    pub method: String,
+    pub request_id: String,
+}
+
+/// Rejects a request from `Middleware::on_request` with the given status.
+#[derive(Debug, Clone)]
+pub struct MiddlewareError {
+    pub status_code: StatusCode,
+    pub message: String,
+}
+
+impl MiddlewareError {
+    pub fn new(status_code: StatusCode, message: impl Into<String>) -> Self {
+        Self {
+            status_code,
+            message: message.into(),
+        }
+    }
+
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, message)
+    }
+
+    pub fn forbidden(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::FORBIDDEN, message)
+    }
+}
+
+impl fmt::Display for MiddlewareError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for MiddlewareError {}
+
+/// Hooks run around every backend call. Request hooks run in registration
+/// order, global middleware first; response and error hooks run in reverse.
+#[async_trait]
+pub trait Middleware: Send + Sync {
+    /// Called before validation and the backend call. The request payload and
+    /// the outgoing gRPC metadata can be changed, or the call rejected. The
+    /// service and method cannot be changed, rate limits and metrics are
+    /// already keyed on them; use `RoutingTable` to send calls elsewhere.
+    async fn on_request(
+        &self,
+        _req: &mut RequestType,
+        _metadata: &mut MetadataMap,
+    ) -> Result<(), MiddlewareError> {
+        Ok(())
+    }
+
+    /// Called with the JSON data of successful responses.
+    async fn on_response(&self, _call: &CallInfo, _data: &mut Value) {}
+
+    /// Called for failed calls, the message and status can be rewritten.
+    async fn on_error(&self, _call: &CallInfo, _response: &mut Response) {}
+}
+
+/// Ordered middleware, global and per service.
+#[derive(Default)]
+pub struct MiddlewareChain {
+    global: RwLock<Vec<Arc<dyn Middleware>>>,
+    services: RwLock<HashMap<String, Vec<Arc<dyn Middleware>>>>,
+}
+
+impl fmt::Debug for MiddlewareChain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let global = self.global.read().map(|g| g.len()).unwrap_or_default();
+        let services = self.services.read().map(|s| s.len()).unwrap_or_default();
+        f.debug_struct("MiddlewareChain")
+            .field("global", &global)
+            .field("services", &services)
+            .finish()
+    }
+}
+
+impl MiddlewareChain {
+    /// Appends middleware run for every service.
+    pub fn add_global(&self, middleware: Arc<dyn Middleware>) {
+        if let Ok(mut global) = self.global.write() {
+            global.push(middleware);
+        }
+    }
+
+    /// Appends middleware run for one service, after the global ones.
+    pub fn add_for_service(&self, service: &str, middleware: Arc<dyn Middleware>) {
+        if let Ok(mut services) = self.services.write() {
+            services
+                .entry(service.to_string())
+                .or_default()
+                .push(middleware);
+        }
+    }
+
+    pub fn clear_service(&self, service: &str) {
+        if let Ok(mut services) = self.services.write() {
+            services.remove(service);
+        }
+    }
+
+    /// Middleware applying to `service`, in request order.
+    pub fn for_service(&self, service: &str) -> Vec<Arc<dyn Middleware>> {
+        let mut chain = self
+            .global
+            .read()
+            .map(|global| global.clone())
+            .unwrap_or_default();
+        if let Ok(services) = self.services.read()
+            && let Some(service_chain) = services.get(service)
+        {
+            chain.extend(service_chain.iter().cloned());
+        }
+        chain
+    }
+}
+
+pub(crate) async fn run_request(
+    chain: &[Arc<dyn Middleware>],
+    req: &mut RequestType,
+    metadata: &mut MetadataMap,
+) -> Result<(), MiddlewareError> {
+    let (service, method) = (req.service.to_string(), req.method.to_string());
+    for middleware in chain {
+        middleware.on_request(req, metadata).await?;
+        if req.service != service || req.method != method {
+            tracing::error!(
+                service = %service,
+                method = %method,
+                "middleware renamed the called method"
+            );
+            return Err(MiddlewareError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "middleware cannot change the called service or method",
+            ));
+        }
+    }
+    Ok(())
+}
+
+pub(crate) async fn run_response(
+    chain: &[Arc<dyn Middleware>],
+    call: &CallInfo,
+    response: &mut Response,
+) {
+    if response.status_code.is_success() {
+        if let Some(data) = response.data.as_mut() {
+            for middleware in chain.iter().rev() {
+                middleware.on_response(call, data).await;
+            }
+        }
+    } else if response.status_code.is_client_error() || response.status_code.is_server_error() {
+        for middleware in chain.iter().rev() {
+            middleware.on_error(call, response).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    struct Rename;
+
+    #[async_trait]
+    impl Middleware for Rename {
+        async fn on_request(
+            &self,
+            req: &mut RequestType,
+            _metadata: &mut MetadataMap,
+        ) -> Result<(), MiddlewareError> {
+            req.method = "DeleteUser".to_string();
+            Ok(())
+        }
+    }
+
+    struct Tag;
+
+    #[async_trait]
+    impl Middleware for Tag {
+        async fn on_request(
+            &self,
+            req: &mut RequestType,
+            _metadata: &mut MetadataMap,
+        ) -> Result<(), MiddlewareError> {
+            req.data["tag"] = json!("seen");
+            Ok(())
+        }
+    }
+
+    fn request() -> RequestType {
+        RequestType {
+            service: "users.Users".to_string(),
+            method: "GetUser".to_string(),
+            data: json!({}),
+        }
+    }
+
+    #[tokio::test]
+    async fn request_hooks_can_change_the_payload() {
+        let mut req = request();
+
+        let result = run_request(&[Arc::new(Tag)], &mut req, &mut MetadataMap::new()).await;
+
+        assert!(result.is_ok());
+        assert_eq!(req.data, json!({ "tag": "seen" }));
+    }
+
+    #[tokio::test]
+    async fn request_hooks_cannot_rename_the_method() {
+        let mut req = request();
+        let chain: Vec<Arc<dyn Middleware>> = vec![Arc::new(Rename), Arc::new(Tag)];
+
+        let error = run_request(&chain, &mut req, &mut MetadataMap::new())
+            .await
+            .unwrap_err();
+
+        assert_eq!(error.status_code, StatusCode::INTERNAL_SERVER_ERROR);
+        // later hooks do not run
+        assert_eq!(req.data, json!({}));
+    }
+}
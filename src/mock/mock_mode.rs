@@ -0,0 +1,262 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::Duration;
+
+use prost_reflect::{DescriptorPool, MethodDescriptor};
+use reqwest::StatusCode;
+use serde::Deserialize;
+use serde_json::Value;
+use tonic::Code;
+
+use crate::Gateway;
+use crate::gateway::json_format::JsonFormat;
+use crate::mock::sample::sample_message;
+use crate::utils::errors::ResponseErrors;
+use crate::utils::model::RequestType;
+use crate::utils::response::Response;
+
+/// Response header telling how a mocked response was produced: `fixture`,
+/// `generated` or `injected-error`.
+pub const MOCK_HEADER: &str = "x-gateway-mock";
+
+/// How a mocked service answers.
+#[derive(Debug, Clone)]
+pub struct MockModeConfig {
+    /// directory holding one `{service}/{method}.json` fixture per method
+    pub fixtures_dir: Option<PathBuf>,
+    /// descriptors for generating responses when no fixture matches, the
+    /// backend's reflected descriptors are used when it is reachable
+    pub descriptors: Option<DescriptorPool>,
+    /// added to every answer
+    pub latency: Duration,
+    /// share of calls failed with `error_code`, from 0 to 1
+    pub error_rate: f64,
+    pub error_code: Code,
+}
+
+impl Default for MockModeConfig {
+    fn default() -> Self {
+        Self {
+            fixtures_dir: None,
+            descriptors: None,
+            latency: Duration::ZERO,
+            error_rate: 0.0,
+            error_code: Code::Unavailable,
+        }
+    }
+}
+
+/// Services answered by the gateway itself instead of their backend.
+#[derive(Debug, Default)]
+pub struct MockMode {
+    services: RwLock<HashMap<String, MockModeConfig>>,
+}
+
+impl MockMode {
+    pub fn enable(&self, service: &str, config: MockModeConfig) {
+        if let Ok(mut services) = self.services.write() {
+            services.insert(service.to_string(), config);
+        }
+    }
+
+    pub fn disable(&self, service: &str) {
+        if let Ok(mut services) = self.services.write() {
+            services.remove(service);
+        }
+    }
+
+    pub fn config_for(&self, service: &str) -> Option<MockModeConfig> {
+        self.services
+            .read()
+            .ok()
+            .and_then(|services| services.get(service).cloned())
+    }
+
+    /// Descriptor of a method from the descriptors given to a mocked service.
+    pub fn method_descriptor(&self, service: &str, method: &str) -> Option<MethodDescriptor> {
+        self.config_for(service)?
+            .descriptors?
+            .get_service_by_name(service)?
+            .methods()
+            .find(|m| m.name() == method)
+    }
+}
+
+// One entry of a fixture file. Entries are tried in order and the first
+// whose `match` is contained in the request answers.
+#[derive(Debug, Default, Deserialize)]
+struct FixtureRule {
+    #[serde(default, rename = "match")]
+    matches: Option<Value>,
+    #[serde(default)]
+    response: Option<Value>,
+    #[serde(default)]
+    error: Option<FixtureError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FixtureError {
+    code: Value,
+    #[serde(default)]
+    message: String,
+}
+
+/// Answers a call to a mocked service from its fixtures, or with a generated
+/// sample of the output message. Returns the response and the gRPC code
+/// reported in metrics.
+pub(crate) async fn respond(
+    gateway: &Gateway,
+    config: &MockModeConfig,
+    req: &RequestType,
+    format: &JsonFormat,
+) -> (Response, String) {
+    if !config.latency.is_zero() {
+        tokio::time::sleep(config.latency).await;
+    }
+    if config.error_rate > 0.0 && rand::random::<f64>() < config.error_rate {
+        return error_response(config.error_code, "injected failure", "injected-error");
+    }
+
+    let rules = match &config.fixtures_dir {
+        // names come from the caller and must not leave the fixtures directory
+        Some(_) if !is_path_segment(&req.service) || !is_path_segment(&req.method) => {
+            return error_response(
+                Code::InvalidArgument,
+                &format!("invalid method name {}/{}", req.service, req.method),
+                "fixture",
+            );
+        }
+        Some(dir) => match load_fixture(dir, &req.service, &req.method).await {
+            Ok(rules) => rules,
+            Err(e) => {
+                tracing::warn!(error = %e, "invalid mock fixture");
+                return error_response(Code::Internal, &e, "fixture");
+            }
+        },
+        None => Vec::new(),
+    };
+    if let Some(rule) = rules.into_iter().find(|rule| {
+        rule.matches
+            .as_ref()
+            .is_none_or(|pattern| contains(&req.data, pattern))
+    }) {
+        if let Some(error) = rule.error {
+            let code = parse_code(&error.code).unwrap_or(Code::Unknown);
+            return error_response(code, &error.message, "fixture");
+        }
+        return success_response(rule.response.unwrap_or_default(), "fixture");
+    }
+
+    let method = match gateway.method_descriptor(&req.service, &req.method).await {
+        Some(method) => method,
+        None => {
+            return error_response(
+                Code::Unimplemented,
+                &format!(
+                    "no mock fixture or descriptor for {}/{}",
+                    req.service, req.method
+                ),
+                "fixture",
+            );
+        }
+    };
+    match format.to_json(&sample_message(&method.output())) {
+        Ok(data) => success_response(data, "generated"),
+        Err(e) => error_response(Code::Internal, &e.to_string(), "generated"),
+    }
+}
+
+// A fixture file holds either the response itself or a list of rules
+async fn load_fixture(dir: &Path, service: &str, method: &str) -> Result<Vec<FixtureRule>, String> {
+    let path = dir.join(service).join(format!("{}.json", method));
+    let content = match tokio::fs::read_to_string(&path).await {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("{}: {}", path.display(), e)),
+    };
+    let fixture: Value =
+        serde_json::from_str(&content).map_err(|e| format!("{}: {}", path.display(), e))?;
+    match fixture {
+        Value::Array(_) => {
+            serde_json::from_value(fixture).map_err(|e| format!("{}: {}", path.display(), e))
+        }
+        response => Ok(vec![FixtureRule {
+            response: Some(response),
+            ..Default::default()
+        }]),
+    }
+}
+
+// A name usable as a single file or directory name
+fn is_path_segment(name: &str) -> bool {
+    !name.is_empty() && name != "." && name != ".." && !name.contains(['/', '\\', '\0'])
+}
+
+// Whether every field of `pattern` is present in `data` with the same value
+fn contains(data: &Value, pattern: &Value) -> bool {
+    match (data, pattern) {
+        (Value::Object(data), Value::Object(pattern)) => pattern.iter().all(|(key, expected)| {
+            data.get(key)
+                .is_some_and(|actual| contains(actual, expected))
+        }),
+        _ => data == pattern,
+    }
+}
+
+// gRPC code from its number or name, e.g. `5`, `NOT_FOUND` or `NotFound`
+fn parse_code(code: &Value) -> Option<Code> {
+    if let Some(number) = code.as_i64() {
+        return Some(Code::from_i32(number as i32));
+    }
+    let name = code.as_str()?.replace('_', "").to_uppercase();
+    let name = if name == "CANCELED" {
+        String::from("CANCELLED")
+    } else {
+        name
+    };
+    (0..=16)
+        .map(Code::from_i32)
+        .find(|code| format!("{:?}", code).to_uppercase() == name)
+}
+
+fn success_response(data: Value, source: &str) -> (Response, String) {
+    (
+        Response {
+            message: ResponseErrors::Success.message(),
+            status: ResponseErrors::Success.message(),
+            data: Some(data),
+            status_code: StatusCode::OK,
+            headers: HashMap::from([(MOCK_HEADER.to_string(), source.to_string())]),
+            ..Default::default()
+        },
+        format!("{:?}", Code::Ok),
+    )
+}
+
+// mirrors how backend errors are reported
+fn error_response(code: Code, message: &str, source: &str) -> (Response, String) {
+    let (message, status_code) = if code == Code::Unavailable {
+        (
+            ResponseErrors::ServiceUnAvailable.message(),
+            StatusCode::SERVICE_UNAVAILABLE,
+        )
+    } else {
+        (
+            std::borrow::Cow::Owned(tonic::Status::new(code, message).to_string()),
+            StatusCode::BAD_REQUEST,
+        )
+    };
+    (
+        Response {
+            message,
+            status: ResponseErrors::Error.message(),
+            data: None,
+            status_code,
+            headers: HashMap::from([(MOCK_HEADER.to_string(), source.to_string())]),
+            grpc_code: Some(code),
+            ..Default::default()
+        },
+        format!("{:?}", code),
+    )
+}
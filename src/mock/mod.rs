@@ -0,0 +1,2 @@
+pub mod mock_mode;
+pub mod sample;
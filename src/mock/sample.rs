@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+
+use prost_reflect::{DynamicMessage, FieldDescriptor, Kind, MapKey, MessageDescriptor, Value};
+
+// nested messages deeper than this are left empty, which also ends recursion
+const MAX_DEPTH: usize = 4;
+
+/// A message of the given type with every field set to a plausible value:
+/// one element for repeated fields and maps, the first field of each oneof
+/// and the first non-zero value of enums. The same type always yields the
+/// same message.
+pub fn sample_message(descriptor: &MessageDescriptor) -> DynamicMessage {
+    build(descriptor, 0)
+}
+
+fn build(descriptor: &MessageDescriptor, depth: usize) -> DynamicMessage {
+    let mut message = DynamicMessage::new(descriptor.clone());
+    if depth >= MAX_DEPTH {
+        return message;
+    }
+    match descriptor.full_name() {
+        "google.protobuf.Timestamp" => {
+            // 2024-01-01T00:00:00Z
+            message.set_field_by_name("seconds", Value::I64(1_704_067_200));
+            return message;
+        }
+        "google.protobuf.Duration" => {
+            message.set_field_by_name("seconds", Value::I64(1));
+            return message;
+        }
+        "google.protobuf.Value" => {
+            message.set_field_by_name("string_value", Value::String("sample".to_string()));
+            return message;
+        }
+        "google.protobuf.Any"
+        | "google.protobuf.Struct"
+        | "google.protobuf.ListValue"
+        | "google.protobuf.FieldMask"
+        | "google.protobuf.Empty" => return message,
+        _ => {}
+    }
+
+    for field in descriptor.fields() {
+        let first_of_oneof = field
+            .containing_oneof()
+            .is_none_or(|oneof| oneof.fields().next().as_ref() == Some(&field));
+        if !first_of_oneof {
+            continue;
+        }
+        let value = if field.is_map() {
+            sample_map(&field, depth)
+        } else if field.is_list() {
+            Value::List(vec![sample_value(&field.kind(), field.name(), depth)])
+        } else {
+            sample_value(&field.kind(), field.name(), depth)
+        };
+        message.set_field(&field, value);
+    }
+    message
+}
+
+fn sample_map(field: &FieldDescriptor, depth: usize) -> Value {
+    let Kind::Message(entry) = field.kind() else {
+        return Value::Map(HashMap::new());
+    };
+    let key = match entry.map_entry_key_field().kind() {
+        Kind::Bool => MapKey::Bool(true),
+        Kind::Int32 | Kind::Sint32 | Kind::Sfixed32 => MapKey::I32(1),
+        Kind::Int64 | Kind::Sint64 | Kind::Sfixed64 => MapKey::I64(1),
+        Kind::Uint32 | Kind::Fixed32 => MapKey::U32(1),
+        Kind::Uint64 | Kind::Fixed64 => MapKey::U64(1),
+        _ => MapKey::String("key".to_string()),
+    };
+    let value = sample_value(&entry.map_entry_value_field().kind(), field.name(), depth);
+    Value::Map(HashMap::from([(key, value)]))
+}
+
+fn sample_value(kind: &Kind, name: &str, depth: usize) -> Value {
+    match kind {
+        Kind::Double => Value::F64(1.5),
+        Kind::Float => Value::F32(1.5),
+        Kind::Int32 | Kind::Sint32 | Kind::Sfixed32 => Value::I32(1),
+        Kind::Int64 | Kind::Sint64 | Kind::Sfixed64 => Value::I64(1),
+        Kind::Uint32 | Kind::Fixed32 => Value::U32(1),
+        Kind::Uint64 | Kind::Fixed64 => Value::U64(1),
+        Kind::Bool => Value::Bool(true),
+        Kind::String => Value::String(sample_string(name)),
+        Kind::Bytes => Value::Bytes(bytes::Bytes::from_static(b"sample")),
+        Kind::Message(message) => Value::Message(build(message, depth + 1)),
+        Kind::Enum(enum_desc) => Value::EnumNumber(
+            enum_desc
+                .values()
+                .map(|v| v.number())
+                .find(|n| *n != 0)
+                .unwrap_or_default(),
+        ),
+    }
+}
+
+// readable placeholders for common field names
+fn sample_string(name: &str) -> String {
+    let name = name.to_lowercase();
+    if name.contains("email") {
+        String::from("user@example.com")
+    } else if name.contains("url") || name.contains("uri") {
+        String::from("https://example.com")
+    } else if name == "id" || name.ends_with("_id") {
+        String::from("00000000-0000-4000-8000-000000000001")
+    } else if name.contains("phone") {
+        String::from("+15555550100")
+    } else {
+        format!("sample {}", name)
+    }
+}
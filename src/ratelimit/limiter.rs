@@ -0,0 +1,272 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use anyhow::Result;
+use jsonwebtoken::{DecodingKey, Validation};
+use serde::Deserialize;
+
+use crate::ratelimit::store::{InMemoryRateLimitStore, RateLimitStore};
+use crate::utils::model::RequestContext;
+use crate::utils::validation_errors::ValidationError;
+
+const ANONYMOUS_CLIENT: &str = "anonymous";
+
+/// What identifies a client for limiting purposes.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RateLimitKey {
+    /// peer address of the call, or the forwarded client address when the
+    /// peer is one of the gateway's trusted proxies
+    ClientIp,
+    /// value of the given API key header
+    ApiKey(String),
+    /// `sub` claim of the bearer token in `authorization`; the signature is
+    /// not verified, the claim is only used to bucket requests
+    JwtSubject,
+    /// value of an arbitrary request header
+    Header(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RateLimitAlgorithm {
+    TokenBucket,
+    SlidingWindow,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateLimitPolicy {
+    /// requests allowed per `per`
+    pub requests: u32,
+    pub per: Duration,
+    pub algorithm: RateLimitAlgorithm,
+    pub key: RateLimitKey,
+}
+
+impl RateLimitPolicy {
+    pub fn per_second(requests: u32, key: RateLimitKey) -> Self {
+        Self {
+            requests,
+            per: Duration::from_secs(1),
+            algorithm: RateLimitAlgorithm::TokenBucket,
+            key,
+        }
+    }
+
+    pub fn per_minute(requests: u32, key: RateLimitKey) -> Self {
+        Self {
+            requests,
+            per: Duration::from_secs(60),
+            algorithm: RateLimitAlgorithm::SlidingWindow,
+            key,
+        }
+    }
+
+    /// A policy needs at least one request over a non-zero period; block a
+    /// method with middleware rather than a zero limit.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if self.requests == 0 {
+            return Err(ValidationError(String::from(
+                "rate limit must allow at least one request",
+            )));
+        }
+        if self.per.is_zero() {
+            return Err(ValidationError(String::from(
+                "rate limit period must be longer than zero",
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RateLimitDecision {
+    Allowed,
+    Limited { retry_after: Duration },
+}
+
+#[derive(Debug, Default)]
+struct RateLimitRules {
+    services: HashMap<String, RateLimitPolicy>,
+    methods: HashMap<String, RateLimitPolicy>,
+}
+
+#[derive(Debug)]
+pub struct RateLimiter {
+    rules: RwLock<RateLimitRules>,
+    store: Arc<dyn RateLimitStore>,
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new(Arc::new(InMemoryRateLimitStore::new()))
+    }
+}
+
+impl RateLimiter {
+    pub fn new(store: Arc<dyn RateLimitStore>) -> Self {
+        Self {
+            rules: RwLock::new(RateLimitRules::default()),
+            store,
+        }
+    }
+
+    pub fn set_service_limit(
+        &self,
+        service: &str,
+        policy: RateLimitPolicy,
+    ) -> Result<(), ValidationError> {
+        policy.validate()?;
+        if let Ok(mut rules) = self.rules.write() {
+            rules.services.insert(service.to_string(), policy);
+        }
+        Ok(())
+    }
+
+    pub fn set_method_limit(
+        &self,
+        service: &str,
+        method: &str,
+        policy: RateLimitPolicy,
+    ) -> Result<(), ValidationError> {
+        policy.validate()?;
+        if let Ok(mut rules) = self.rules.write() {
+            rules
+                .methods
+                .insert(format!("{}.{}", service, method), policy);
+        }
+        Ok(())
+    }
+
+    pub fn remove_service_limit(&self, service: &str) {
+        if let Ok(mut rules) = self.rules.write() {
+            rules.services.remove(service);
+        }
+    }
+
+    /// Checks the method limit first and then the service limit; the request
+    /// is allowed only when both have capacity.
+    pub async fn check(
+        &self,
+        service: &str,
+        method: &str,
+        ctx: &RequestContext,
+    ) -> Result<RateLimitDecision> {
+        let method_key = format!("{}.{}", service, method);
+        let policies = match self.rules.read() {
+            Ok(rules) => [
+                rules
+                    .methods
+                    .get(&method_key)
+                    .map(|p| (format!("method:{}", method_key), p.clone())),
+                rules
+                    .services
+                    .get(service)
+                    .map(|p| (format!("service:{}", service), p.clone())),
+            ],
+            Err(e) => return Err(anyhow::anyhow!(e.to_string())),
+        };
+
+        for (scope, policy) in policies.into_iter().flatten() {
+            let key = format!("{}:{}", scope, client_key(&policy.key, ctx));
+            let decision = self.store.acquire(&key, &policy).await?;
+            if decision != RateLimitDecision::Allowed {
+                return Ok(decision);
+            }
+        }
+        Ok(RateLimitDecision::Allowed)
+    }
+}
+
+#[derive(Deserialize)]
+struct SubjectClaim {
+    sub: Option<String>,
+}
+
+fn client_key(key: &RateLimitKey, ctx: &RequestContext) -> String {
+    let value = match key {
+        RateLimitKey::ClientIp => ctx.client_ip.clone(),
+        RateLimitKey::ApiKey(header) | RateLimitKey::Header(header) => {
+            ctx.headers.get(&header.to_lowercase()).cloned()
+        }
+        RateLimitKey::JwtSubject => ctx
+            .headers
+            .get("authorization")
+            .and_then(|v| v.strip_prefix("Bearer ").or(v.strip_prefix("bearer ")))
+            .and_then(jwt_subject),
+    };
+    value.unwrap_or_else(|| ANONYMOUS_CLIENT.to_string())
+}
+
+fn jwt_subject(token: &str) -> Option<String> {
+    let mut validation = Validation::default();
+    validation.insecure_disable_signature_validation();
+    validation.validate_exp = false;
+    validation.required_spec_claims.clear();
+
+    jsonwebtoken::decode::<SubjectClaim>(token, &DecodingKey::from_secret(&[]), &validation)
+        .ok()
+        .and_then(|data| data.claims.sub)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_degenerate_policies() {
+        let limiter = RateLimiter::default();
+        let none_allowed = RateLimitPolicy::per_second(0, RateLimitKey::ClientIp);
+        let no_period = RateLimitPolicy {
+            per: Duration::ZERO,
+            ..RateLimitPolicy::per_minute(10, RateLimitKey::ClientIp)
+        };
+
+        assert!(
+            limiter
+                .set_service_limit("a.Service", none_allowed)
+                .is_err()
+        );
+        assert!(
+            limiter
+                .set_method_limit("a.Service", "Get", no_period)
+                .is_err()
+        );
+        assert!(
+            limiter
+                .set_service_limit(
+                    "a.Service",
+                    RateLimitPolicy::per_second(1, RateLimitKey::ClientIp)
+                )
+                .is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn both_the_method_and_service_limit_apply() {
+        let limiter = RateLimiter::default();
+        let ctx = RequestContext::default();
+        limiter
+            .set_service_limit(
+                "a.Service",
+                RateLimitPolicy::per_minute(2, RateLimitKey::ClientIp),
+            )
+            .unwrap();
+        limiter
+            .set_method_limit(
+                "a.Service",
+                "Get",
+                RateLimitPolicy::per_minute(1, RateLimitKey::ClientIp),
+            )
+            .unwrap();
+
+        let first = limiter.check("a.Service", "Get", &ctx).await.unwrap();
+        let second = limiter.check("a.Service", "Get", &ctx).await.unwrap();
+        let other = limiter.check("a.Service", "List", &ctx).await.unwrap();
+        let over = limiter.check("a.Service", "List", &ctx).await.unwrap();
+
+        assert_eq!(first, RateLimitDecision::Allowed);
+        assert!(matches!(second, RateLimitDecision::Limited { .. }));
+        assert_eq!(other, RateLimitDecision::Allowed);
+        assert!(matches!(over, RateLimitDecision::Limited { .. }));
+    }
+}
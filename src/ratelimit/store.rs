@@ -0,0 +1,362 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use async_trait::async_trait;
+
+use crate::ratelimit::limiter::{RateLimitAlgorithm, RateLimitDecision, RateLimitPolicy};
+
+// buckets idle for longer than this many of their own windows are dropped
+// from memory
+const IDLE_WINDOWS_BEFORE_EVICTION: u32 = 2;
+const EVICTION_THRESHOLD: usize = 10_000;
+// idle buckets are looked for at most this often
+const EVICTION_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Backend holding limiter state. Implement this over a shared store (e.g.
+/// Redis) to enforce limits across several gateway instances.
+#[async_trait]
+pub trait RateLimitStore: Send + Sync + std::fmt::Debug {
+    async fn acquire(&self, key: &str, policy: &RateLimitPolicy) -> Result<RateLimitDecision>;
+}
+
+#[derive(Debug, Clone)]
+enum BucketState {
+    TokenBucket {
+        tokens: f64,
+        refilled_at: Instant,
+    },
+    SlidingWindow {
+        window_start: Instant,
+        previous_count: u32,
+        current_count: u32,
+    },
+}
+
+impl BucketState {
+    fn last_seen(&self) -> Instant {
+        match self {
+            BucketState::TokenBucket { refilled_at, .. } => *refilled_at,
+            BucketState::SlidingWindow { window_start, .. } => *window_start,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    state: BucketState,
+    // window of the policy the bucket was last used with
+    window: Duration,
+}
+
+impl Bucket {
+    fn is_idle(&self, now: Instant) -> bool {
+        now.duration_since(self.state.last_seen()) >= self.window * IDLE_WINDOWS_BEFORE_EVICTION
+    }
+}
+
+#[derive(Debug)]
+struct Buckets {
+    entries: HashMap<String, Bucket>,
+    swept_at: Instant,
+}
+
+/// Process local store, limits are enforced per gateway instance.
+#[derive(Debug)]
+pub struct InMemoryRateLimitStore {
+    buckets: Mutex<Buckets>,
+}
+
+impl Default for InMemoryRateLimitStore {
+    fn default() -> Self {
+        Self {
+            buckets: Mutex::new(Buckets {
+                entries: HashMap::new(),
+                swept_at: Instant::now(),
+            }),
+        }
+    }
+}
+
+impl InMemoryRateLimitStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl RateLimitStore for InMemoryRateLimitStore {
+    async fn acquire(&self, key: &str, policy: &RateLimitPolicy) -> Result<RateLimitDecision> {
+        let mut buckets = self
+            .buckets
+            .lock()
+            .map_err(|e| anyhow::anyhow!(e.to_string()))?;
+
+        let now = Instant::now();
+        if buckets.entries.len() > EVICTION_THRESHOLD
+            && now.duration_since(buckets.swept_at) >= EVICTION_INTERVAL
+        {
+            buckets.entries.retain(|_, bucket| !bucket.is_idle(now));
+            buckets.swept_at = now;
+        }
+
+        let bucket = buckets
+            .entries
+            .entry(key.to_string())
+            .or_insert_with(|| Bucket {
+                state: match policy.algorithm {
+                    RateLimitAlgorithm::TokenBucket => BucketState::TokenBucket {
+                        tokens: policy.requests as f64,
+                        refilled_at: now,
+                    },
+                    RateLimitAlgorithm::SlidingWindow => BucketState::SlidingWindow {
+                        window_start: now,
+                        previous_count: 0,
+                        current_count: 0,
+                    },
+                },
+                window: policy.per,
+            });
+        bucket.window = policy.per;
+        let state = &mut bucket.state;
+
+        Ok(match state {
+            BucketState::TokenBucket {
+                tokens,
+                refilled_at,
+            } => {
+                let rate = policy.requests as f64 / policy.per.as_secs_f64();
+                let elapsed = now.duration_since(*refilled_at).as_secs_f64();
+                *tokens = (*tokens + elapsed * rate).min(policy.requests as f64);
+                *refilled_at = now;
+
+                if *tokens >= 1.0 {
+                    *tokens -= 1.0;
+                    RateLimitDecision::Allowed
+                } else {
+                    RateLimitDecision::Limited {
+                        retry_after: Duration::from_secs_f64((1.0 - *tokens) / rate),
+                    }
+                }
+            }
+            BucketState::SlidingWindow {
+                window_start,
+                previous_count,
+                current_count,
+            } => {
+                let mut elapsed = now.duration_since(*window_start);
+                if elapsed >= policy.per {
+                    let windows_passed = elapsed.as_nanos() / policy.per.as_nanos();
+                    *previous_count = if windows_passed == 1 {
+                        *current_count
+                    } else {
+                        0
+                    };
+                    *current_count = 0;
+                    *window_start += policy.per * windows_passed as u32;
+                    elapsed = now.duration_since(*window_start);
+                }
+
+                // weight the previous window by how much of it still overlaps
+                let overlap = 1.0 - elapsed.as_secs_f64() / policy.per.as_secs_f64();
+                let estimated = *previous_count as f64 * overlap + *current_count as f64;
+
+                if estimated < policy.requests as f64 {
+                    *current_count += 1;
+                    RateLimitDecision::Allowed
+                } else {
+                    RateLimitDecision::Limited {
+                        retry_after: policy.per - elapsed,
+                    }
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ratelimit::limiter::RateLimitKey;
+
+    fn token_bucket(requests: u32) -> RateLimitPolicy {
+        RateLimitPolicy::per_second(requests, RateLimitKey::ClientIp)
+    }
+
+    fn sliding_window(requests: u32) -> RateLimitPolicy {
+        RateLimitPolicy::per_minute(requests, RateLimitKey::ClientIp)
+    }
+
+    fn ago(duration: Duration) -> Instant {
+        Instant::now().checked_sub(duration).unwrap()
+    }
+
+    fn insert(store: &InMemoryRateLimitStore, key: &str, state: BucketState, window: Duration) {
+        store
+            .buckets
+            .lock()
+            .unwrap()
+            .entries
+            .insert(key.to_string(), Bucket { state, window });
+    }
+
+    fn retry_after(decision: RateLimitDecision) -> Duration {
+        match decision {
+            RateLimitDecision::Limited { retry_after } => retry_after,
+            RateLimitDecision::Allowed => panic!("expected the call to be limited"),
+        }
+    }
+
+    #[tokio::test]
+    async fn token_bucket_allows_a_burst_then_waits_for_one_token() {
+        let store = InMemoryRateLimitStore::new();
+        let policy = token_bucket(4);
+
+        for _ in 0..4 {
+            assert_eq!(
+                store.acquire("client", &policy).await.unwrap(),
+                RateLimitDecision::Allowed
+            );
+        }
+        let wait = retry_after(store.acquire("client", &policy).await.unwrap());
+
+        // one token refills every 250ms
+        assert!(wait > Duration::from_millis(200) && wait <= Duration::from_millis(250));
+    }
+
+    #[tokio::test]
+    async fn token_bucket_refills_with_elapsed_time() {
+        let store = InMemoryRateLimitStore::new();
+        insert(
+            &store,
+            "client",
+            BucketState::TokenBucket {
+                tokens: 0.0,
+                refilled_at: ago(Duration::from_millis(600)),
+            },
+            Duration::from_secs(1),
+        );
+        let policy = token_bucket(2);
+
+        let refilled = store.acquire("client", &policy).await.unwrap();
+        let empty = store.acquire("client", &policy).await.unwrap();
+
+        assert_eq!(refilled, RateLimitDecision::Allowed);
+        // 0.2 of a token is left, 0.8 more takes 400ms
+        let wait = retry_after(empty);
+        assert!(wait > Duration::from_millis(350) && wait <= Duration::from_millis(400));
+    }
+
+    #[tokio::test]
+    async fn sliding_window_limits_within_the_window() {
+        let store = InMemoryRateLimitStore::new();
+        let policy = sliding_window(2);
+
+        store.acquire("client", &policy).await.unwrap();
+        store.acquire("client", &policy).await.unwrap();
+        let wait = retry_after(store.acquire("client", &policy).await.unwrap());
+
+        assert!(wait > Duration::from_secs(59) && wait <= Duration::from_secs(60));
+    }
+
+    #[tokio::test]
+    async fn sliding_window_weights_the_previous_window_by_its_overlap() {
+        let store = InMemoryRateLimitStore::new();
+        // the previous window was full and two thirds of it still overlap
+        insert(
+            &store,
+            "client",
+            BucketState::SlidingWindow {
+                window_start: ago(Duration::from_secs(80)),
+                previous_count: 0,
+                current_count: 10,
+            },
+            Duration::from_secs(60),
+        );
+        let policy = sliding_window(10);
+
+        let mut allowed = 0;
+        while store.acquire("client", &policy).await.unwrap() == RateLimitDecision::Allowed {
+            allowed += 1;
+        }
+
+        assert_eq!(allowed, 4);
+    }
+
+    #[tokio::test]
+    async fn sliding_window_forgets_windows_older_than_the_previous() {
+        let store = InMemoryRateLimitStore::new();
+        insert(
+            &store,
+            "client",
+            BucketState::SlidingWindow {
+                window_start: ago(Duration::from_secs(150)),
+                previous_count: 10,
+                current_count: 10,
+            },
+            Duration::from_secs(60),
+        );
+        let policy = sliding_window(10);
+
+        let mut allowed = 0;
+        while store.acquire("client", &policy).await.unwrap() == RateLimitDecision::Allowed {
+            allowed += 1;
+        }
+
+        assert_eq!(allowed, 10);
+    }
+
+    // idle by a per-second window, three seconds after their last use
+    fn insert_idle(store: &InMemoryRateLimitStore, count: usize) {
+        for n in 0..count {
+            insert(
+                store,
+                &format!("idle-{}", n),
+                BucketState::TokenBucket {
+                    tokens: 1.0,
+                    refilled_at: ago(Duration::from_secs(3)),
+                },
+                Duration::from_secs(1),
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn idle_buckets_are_evicted_by_their_own_window() {
+        let store = InMemoryRateLimitStore::new();
+        // a per-hour bucket used five minutes ago is still counting
+        insert(
+            &store,
+            "hourly",
+            BucketState::SlidingWindow {
+                window_start: ago(Duration::from_secs(300)),
+                previous_count: 0,
+                current_count: 10,
+            },
+            Duration::from_secs(3600),
+        );
+        insert_idle(&store, EVICTION_THRESHOLD);
+        store.buckets.lock().unwrap().swept_at = ago(EVICTION_INTERVAL);
+
+        store.acquire("client", &token_bucket(1)).await.unwrap();
+
+        let buckets = store.buckets.lock().unwrap();
+        assert_eq!(buckets.entries.len(), 2);
+        assert!(buckets.entries.contains_key("hourly"));
+    }
+
+    #[tokio::test]
+    async fn idle_buckets_are_swept_at_most_once_per_interval() {
+        let store = InMemoryRateLimitStore::new();
+        insert_idle(&store, EVICTION_THRESHOLD + 1);
+
+        // the store was created less than an interval ago
+        store.acquire("client", &token_bucket(1)).await.unwrap();
+
+        assert_eq!(
+            store.buckets.lock().unwrap().entries.len(),
+            EVICTION_THRESHOLD + 2
+        );
+    }
+}
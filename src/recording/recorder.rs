@@ -0,0 +1,306 @@
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::thread::{self, JoinHandle};
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use chrono::{DateTime, Utc};
+use prost::Message;
+use prost_reflect::{DynamicMessage, MethodDescriptor};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tonic::metadata::{KeyAndValueRef, MetadataMap};
+
+use crate::gateway::field_mask::FieldMask;
+use crate::gateway::json_format::JsonFormat;
+use crate::metrics::gateway_metrics;
+
+/// One recorded call, written as a line of the recording.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedCall {
+    pub recorded_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    pub service: String,
+    pub method: String,
+    /// the JSON request, after request middleware
+    pub request: Value,
+    /// metadata sent to the backend, without the service's credentials
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+    /// the request message as sent to the backend, base64 encoded protobuf
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_protobuf: Option<String>,
+    /// JSON mapping of the request and response, as a `$alt` value
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+    /// response fields kept by a `fields` parameter or the request's read
+    /// mask, as a `fields` value
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fields: Option<String>,
+    pub status: u16,
+    /// gRPC status of the backend call, as reported in metrics
+    pub grpc_code: String,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response: Option<Value>,
+    pub latency_ms: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct RecorderConfig {
+    /// file calls are appended to, rotated to `{path}.1`, `{path}.2`, ...
+    pub path: PathBuf,
+    /// share of calls recorded, from 0 to 1
+    pub sample_rate: f64,
+    /// services to record, every service when empty
+    pub services: Vec<String>,
+    /// size after which the file is rotated
+    pub max_file_bytes: u64,
+    /// rotated files kept besides the current one
+    pub max_files: usize,
+    /// calls waiting to be written, calls recorded while it is full are
+    /// dropped
+    pub queue_capacity: usize,
+}
+
+impl Default for RecorderConfig {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from("recordings/traffic.jsonl"),
+            sample_rate: 1.0,
+            services: Vec::new(),
+            max_file_bytes: 64 * 1024 * 1024,
+            max_files: 5,
+            queue_capacity: 1024,
+        }
+    }
+}
+
+/// A sampled call waiting to be written. The request's protobuf encoding is
+/// computed by the writer.
+#[derive(Debug)]
+pub(crate) struct PendingCall {
+    pub call: RecordedCall,
+    pub method: Option<MethodDescriptor>,
+    pub format: JsonFormat,
+}
+
+/// Opt-in recorder writing sampled calls as JSON lines to a rotating file.
+/// Calls are written by a background thread so the file never slows down
+/// the request they belong to.
+#[derive(Debug, Default)]
+pub struct TrafficRecorder {
+    config: RwLock<Option<RecorderConfig>>,
+    writer: RwLock<Option<Writer>>,
+}
+
+#[derive(Debug)]
+struct Writer {
+    sender: SyncSender<PendingCall>,
+    thread: JoinHandle<()>,
+}
+
+impl TrafficRecorder {
+    /// Starts recording, appending to the configured file.
+    pub fn enable(&self, config: RecorderConfig) -> io::Result<()> {
+        let file = RotatingFile::open(&config)?;
+        let (sender, receiver) = mpsc::sync_channel(config.queue_capacity.max(1));
+        let thread = thread::Builder::new()
+            .name("traffic-recorder".to_string())
+            .spawn(move || write_calls(file, receiver))?;
+        let previous = match self.writer.write() {
+            Ok(mut writer) => writer.replace(Writer { sender, thread }),
+            Err(_) => None,
+        };
+        if let Ok(mut current) = self.config.write() {
+            *current = Some(config);
+        }
+        if let Some(previous) = previous {
+            previous.stop();
+        }
+        Ok(())
+    }
+
+    /// Stops recording once the calls already queued are written.
+    pub fn disable(&self) {
+        if let Ok(mut current) = self.config.write() {
+            *current = None;
+        }
+        let writer = self
+            .writer
+            .write()
+            .ok()
+            .and_then(|mut writer| writer.take());
+        if let Some(writer) = writer {
+            writer.stop();
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.read().is_ok_and(|config| config.is_some())
+    }
+
+    /// Whether this call of the service is sampled for recording.
+    pub(crate) fn should_record(&self, service: &str) -> bool {
+        let Ok(config) = self.config.read() else {
+            return false;
+        };
+        config.as_ref().is_some_and(|config| {
+            (config.services.is_empty() || config.services.iter().any(|s| s == service))
+                && config.sample_rate > 0.0
+                && rand::random::<f64>() < config.sample_rate
+        })
+    }
+
+    /// Queues a call for writing, dropping it when the queue is full.
+    pub(crate) fn record(&self, pending: PendingCall) {
+        let Ok(writer) = self.writer.read() else {
+            return;
+        };
+        let Some(writer) = writer.as_ref() else {
+            return;
+        };
+        if let Err(TrySendError::Full(pending)) = writer.sender.try_send(pending) {
+            gateway_metrics::record_recording_dropped(&pending.call.service);
+        }
+    }
+}
+
+impl Writer {
+    // closes the queue and waits for the calls in it to be written
+    fn stop(self) {
+        drop(self.sender);
+        if self.thread.join().is_err() {
+            tracing::warn!("traffic recorder thread panicked");
+        }
+    }
+}
+
+fn write_calls(mut file: RotatingFile, receiver: Receiver<PendingCall>) {
+    for pending in receiver {
+        let PendingCall {
+            mut call,
+            method,
+            format,
+        } = pending;
+        call.request_protobuf = method
+            .as_ref()
+            .and_then(|method| encode_request(method, &call.request, &format));
+        call.format = Some(format.to_string());
+        let fields = match &method {
+            Some(method) if format.fields.is_empty() => {
+                FieldMask::from_request(&method.input(), &call.request)
+            }
+            _ => Some(format.fields).filter(|fields| !fields.is_empty()),
+        };
+        call.fields = fields.map(|fields| fields.paths().join(","));
+        let line = match serde_json::to_string(&call) {
+            Ok(line) => line,
+            Err(e) => {
+                tracing::warn!(error = %e, "failed to serialize recorded call");
+                continue;
+            }
+        };
+        if let Err(e) = file.write_line(&line) {
+            tracing::warn!(error = %e, "failed to write recorded call");
+        }
+    }
+}
+
+/// The request message's protobuf encoding as recorded, base64 encoded.
+pub(crate) fn encode_request(
+    method: &MethodDescriptor,
+    data: &Value,
+    format: &JsonFormat,
+) -> Option<String> {
+    let mut message = DynamicMessage::new(method.input());
+    format.from_json(data, &mut message).ok()?;
+    Some(STANDARD.encode(message.encode_to_vec()))
+}
+
+/// Text metadata entries keyed by name, binary entries are left out.
+pub(crate) fn metadata_map(metadata: &MetadataMap) -> HashMap<String, String> {
+    metadata
+        .iter()
+        .filter_map(|entry| match entry {
+            KeyAndValueRef::Ascii(key, value) => value
+                .to_str()
+                .ok()
+                .map(|v| (key.as_str().to_string(), v.to_string())),
+            KeyAndValueRef::Binary(..) => None,
+        })
+        .collect()
+}
+
+#[derive(Debug)]
+struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    written: u64,
+    max_bytes: u64,
+    max_files: usize,
+}
+
+impl RotatingFile {
+    fn open(config: &RecorderConfig) -> io::Result<Self> {
+        if let Some(parent) = config.path.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            fs::create_dir_all(parent)?;
+        }
+        let file = append(&config.path)?;
+        Ok(Self {
+            path: config.path.clone(),
+            written: file.metadata()?.len(),
+            file,
+            max_bytes: config.max_file_bytes,
+            max_files: config.max_files,
+        })
+    }
+
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        let len = line.len() as u64 + 1;
+        if self.written > 0 && self.written + len > self.max_bytes {
+            self.rotate()?;
+        }
+        self.file.write_all(format!("{}\n", line).as_bytes())?;
+        self.written += len;
+        Ok(())
+    }
+
+    // shifts `{path}.n` to `{path}.n+1`, dropping the oldest
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        if self.max_files == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            let _ = fs::remove_file(rotated(&self.path, self.max_files));
+            for n in (1..self.max_files).rev() {
+                let from = rotated(&self.path, n);
+                if from.exists() {
+                    fs::rename(&from, rotated(&self.path, n + 1))?;
+                }
+            }
+            fs::rename(&self.path, rotated(&self.path, 1))?;
+        }
+        self.file = append(&self.path)?;
+        self.written = 0;
+        Ok(())
+    }
+}
+
+fn append(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+/// Path of the `n`th rotated file of a recording.
+pub fn rotated(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", n));
+    PathBuf::from(name)
+}
@@ -0,0 +1,247 @@
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+use std::str::FromStr;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use serde::Serialize;
+use serde_json::Value;
+use tonic::metadata::{MetadataKey, MetadataMap, MetadataValue};
+
+use crate::gateway::field_mask::{FIELDS_PARAM, FieldMask};
+use crate::gateway::gateway::GrpcGateway;
+use crate::gateway::json_format::{ALT_PARAM, JsonFormat};
+use crate::recording::recorder::RecordedCall;
+use crate::registry::model::ServiceConfig;
+use crate::validation::validator::pointer;
+
+/// Where recorded calls are sent again.
+#[derive(Debug, Clone)]
+pub enum ReplayTarget {
+    /// a gateway's base URL, calls go through its `POST /invoke`
+    Gateway(String),
+    /// a backend's gRPC endpoint, called with the recorded protobuf request
+    /// and metadata
+    Backend(String),
+}
+
+/// A value that differs between the recorded and the replayed response.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Difference {
+    /// JSON pointer into the response
+    pub path: String,
+    pub recorded: Option<Value>,
+    pub replayed: Option<Value>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplayResult {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    pub service: String,
+    pub method: String,
+    pub recorded_status: u16,
+    pub replayed_status: u16,
+    pub differences: Vec<Difference>,
+}
+
+impl ReplayResult {
+    pub fn matches(&self) -> bool {
+        self.recorded_status == self.replayed_status && self.differences.is_empty()
+    }
+}
+
+/// Reads a recording, one call per line.
+pub fn read_recording(path: &Path) -> io::Result<Vec<RecordedCall>> {
+    let reader = BufReader::new(std::fs::File::open(path)?);
+    let mut calls = Vec::new();
+    for (number, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let call = serde_json::from_str(&line).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}:{}: {}", path.display(), number + 1, e),
+            )
+        })?;
+        calls.push(call);
+    }
+    Ok(calls)
+}
+
+/// Sends the calls again, one after the other, and compares each answer
+/// with the recorded one.
+pub async fn replay(calls: &[RecordedCall], target: &ReplayTarget) -> Vec<ReplayResult> {
+    let http = reqwest::Client::new();
+    let mut backend = None;
+    let mut results = Vec::with_capacity(calls.len());
+
+    for call in calls {
+        let (status, response) = match target {
+            ReplayTarget::Gateway(base_url) => replay_through_gateway(&http, base_url, call).await,
+            ReplayTarget::Backend(endpoint) => {
+                if backend.is_none() {
+                    backend = GrpcGateway::new(endpoint).await.ok();
+                }
+                match &backend {
+                    Some(client) => replay_to_backend(client, endpoint, call).await,
+                    None => (reqwest::StatusCode::BAD_GATEWAY.as_u16(), None),
+                }
+            }
+        };
+        results.push(ReplayResult {
+            request_id: call.request_id.clone(),
+            service: call.service.to_string(),
+            method: call.method.to_string(),
+            recorded_status: call.status,
+            replayed_status: status,
+            differences: diff(
+                call.response.as_ref().unwrap_or(&Value::Null),
+                response.as_ref().unwrap_or(&Value::Null),
+            ),
+        });
+    }
+    results
+}
+
+async fn replay_through_gateway(
+    http: &reqwest::Client,
+    base_url: &str,
+    call: &RecordedCall,
+) -> (u16, Option<Value>) {
+    let body = serde_json::json!({
+        "service": call.service,
+        "method": call.method,
+        "data": call.request,
+    });
+    let mut request = http
+        .post(format!("{}/invoke", base_url.trim_end_matches('/')))
+        .json(&body);
+    if let Some(alt) = &call.format {
+        request = request.query(&[(ALT_PARAM, alt)]);
+    }
+    if let Some(fields) = &call.fields {
+        request = request.query(&[(FIELDS_PARAM, fields)]);
+    }
+    for (name, value) in &call.metadata {
+        request = request.header(name.as_str(), value.as_str());
+    }
+    match request.send().await {
+        Ok(response) => {
+            let status = response.status().as_u16();
+            let envelope: Option<Value> = response.json().await.ok();
+            let data = envelope
+                .and_then(|mut envelope| envelope.get_mut("data").map(Value::take))
+                .filter(|data| !data.is_null());
+            (status, data)
+        }
+        Err(e) => {
+            tracing::warn!(error = %e, "replayed call failed");
+            (reqwest::StatusCode::BAD_GATEWAY.as_u16(), None)
+        }
+    }
+}
+
+// statuses follow how the gateway reports backend results
+async fn replay_to_backend(
+    client: &GrpcGateway,
+    endpoint: &str,
+    call: &RecordedCall,
+) -> (u16, Option<Value>) {
+    let format = recorded_format(call);
+    let request = match &call.request_protobuf {
+        Some(encoded) => STANDARD.decode(encoded).map_err(anyhow::Error::from),
+        None => {
+            client
+                .encode_request(&call.service, &call.method, &call.request, &format)
+                .await
+        }
+    };
+    let mut metadata = MetadataMap::new();
+    for (name, value) in &call.metadata {
+        if let (Ok(key), Ok(value)) = (MetadataKey::from_str(name), MetadataValue::from_str(value))
+        {
+            metadata.insert(key, value);
+        }
+    }
+    let config = ServiceConfig {
+        endpoint: endpoint.to_string(),
+        service_name: call.service.to_string(),
+        auth_config: None,
+        breaker: None,
+    };
+
+    let result = match request {
+        Ok(request) => {
+            client
+                .invoke_encoded(
+                    &call.service,
+                    &call.method,
+                    request,
+                    config,
+                    metadata,
+                    &format,
+                )
+                .await
+        }
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(response) => (reqwest::StatusCode::OK.as_u16(), Some(response)),
+        Err(e) if e.to_string().to_lowercase().contains("status: unavailable") => {
+            (reqwest::StatusCode::SERVICE_UNAVAILABLE.as_u16(), None)
+        }
+        Err(_) => (reqwest::StatusCode::BAD_REQUEST.as_u16(), None),
+    }
+}
+
+// the JSON mapping and projection the call was recorded with, the defaults
+// for recordings that predate them
+fn recorded_format(call: &RecordedCall) -> JsonFormat {
+    let mut format = call
+        .format
+        .as_deref()
+        .and_then(|alt| JsonFormat::default().with_alt(alt).ok())
+        .unwrap_or_default();
+    if let Some(fields) = &call.fields {
+        format.fields = FieldMask::parse(fields);
+    }
+    format
+}
+
+/// Values that differ between two JSON documents, by JSON pointer. Objects
+/// are compared key by key, everything else as a whole.
+pub fn diff(recorded: &Value, replayed: &Value) -> Vec<Difference> {
+    let mut differences = Vec::new();
+    diff_at("", recorded, replayed, &mut differences);
+    differences
+}
+
+fn diff_at(path: &str, recorded: &Value, replayed: &Value, out: &mut Vec<Difference>) {
+    match (recorded, replayed) {
+        (Value::Object(recorded), Value::Object(replayed)) => {
+            let mut keys: Vec<&String> = recorded.keys().chain(replayed.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                let field_path = pointer(path, key);
+                match (recorded.get(key), replayed.get(key)) {
+                    (Some(a), Some(b)) => diff_at(&field_path, a, b, out),
+                    (a, b) => out.push(Difference {
+                        path: field_path,
+                        recorded: a.cloned(),
+                        replayed: b.cloned(),
+                    }),
+                }
+            }
+        }
+        (a, b) if a != b => out.push(Difference {
+            path: path.to_string(),
+            recorded: Some(a.clone()),
+            replayed: Some(b.clone()),
+        }),
+        _ => {}
+    }
+}
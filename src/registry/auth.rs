@@ -57,7 +57,7 @@ impl Refreshable for AuthConfig {
                     .duration_since(std::time::UNIX_EPOCH)?
                     .as_secs();
 
-                if now < jwt.expired_at {
+                if now >= jwt.expired_at {
                     let result = jwt.refresh_token(service_endpoint).await;
                     if result.is_err() {
                         return Err(Box::new(ValidationError(result.err().unwrap().to_string())));
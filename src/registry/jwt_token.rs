@@ -1,6 +1,7 @@
 use super::auth::Auth;
 use crate::gateway::gateway::GrpcGateway;
 use crate::grpc_client_map;
+use crate::metrics::gateway_metrics;
 
 use crate::registry::model::RefreshAuthTokenJson;
 use crate::utils::errors::ResponseErrors;
@@ -46,6 +47,11 @@ impl JWTTokenAuth {
             method,
         }
     }
+    #[tracing::instrument(
+        name = "auth.refresh",
+        skip(self),
+        fields(refresh_service = %self.service_name, refresh_method = %self.method)
+    )]
     pub async fn refresh_token(
         &mut self,
         service_endpoint: &str,
@@ -84,6 +90,11 @@ impl JWTTokenAuth {
                 }),
             )
             .await;
+        gateway_metrics::record_auth_refresh(service_endpoint, response.is_ok());
+        match &response {
+            Ok(_) => tracing::info!("access token refreshed"),
+            Err(e) => tracing::warn!(error = %e, "access token refresh failed"),
+        }
 
         if response.is_err() {
             return Err(Box::new(ValidationError(
@@ -33,6 +33,32 @@ pub struct ServiceConfig {
     pub breaker: Option<CircuitBreaker>,
 }
 
+/// Which of the services reflected from an endpoint a registration claims.
+#[derive(Debug, Clone)]
+pub enum ServiceFilter {
+    /// every service, except the `grpc.*` infrastructure services such as
+    /// reflection and health checking
+    All,
+    /// services of these packages, e.g. `users` or `users.v1`
+    Packages(Vec<String>),
+    /// these fully qualified services
+    Names(Vec<String>),
+}
+
+impl ServiceFilter {
+    pub fn matches(&self, service: &str) -> bool {
+        match self {
+            ServiceFilter::All => !service.starts_with("grpc."),
+            ServiceFilter::Packages(packages) => packages.iter().any(|package| {
+                service
+                    .strip_prefix(package.trim_end_matches('.'))
+                    .is_some_and(|rest| rest.starts_with('.'))
+            }),
+            ServiceFilter::Names(names) => names.iter().any(|name| name == service),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct RefreshAuthTokenJson {
     #[serde(rename = "accessToken")]
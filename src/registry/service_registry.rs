@@ -1,5 +1,5 @@
-use super::model::{AuthType, ServiceConfig};
-use crate::circuitbreaker::breaker::{self, CircuitBreaker, CircuitBreakerConfig};
+use super::model::{AuthType, ServiceConfig, ServiceFilter};
+use crate::circuitbreaker::breaker::{CircuitBreaker, CircuitBreakerConfig};
 use crate::gateway::gateway::GrpcGateway;
 use crate::registry::api_key::APIKeyAuth;
 use crate::registry::auth::AuthConfig;
@@ -11,13 +11,15 @@ use crate::utils::validation_errors::ValidationError;
 use anyhow::Result;
 use serde_json::json;
 use std::error::Error;
-use std::time::Duration;
 use std::{collections::HashMap, sync::Mutex};
 
 use lazy_static::lazy_static;
 
 lazy_static! {
     static ref GLOBAL_MAP: Mutex<HashMap<String, ServiceConfig>> = Mutex::new(HashMap::new());
+    // versioned backends of a logical service, by service then version
+    static ref VERSION_MAP: Mutex<HashMap<String, HashMap<String, ServiceConfig>>> =
+        Mutex::new(HashMap::new());
 }
 
 pub trait RegistryTrait {
@@ -35,11 +37,122 @@ pub trait RegistryTrait {
 
 pub struct ServiceRegistry {}
 
-impl RegistryTrait for ServiceRegistry {
-    async fn register(
+impl ServiceRegistry {
+    /// Every registered service configuration.
+    pub fn services(&self) -> Vec<ServiceConfig> {
+        match GLOBAL_MAP.lock() {
+            Ok(mp) => mp.values().cloned().collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// Registers one version of a logical service next to the others, each
+    /// with its own endpoint, credentials and breaker. Traffic is split
+    /// between versions through `Gateway::traffic_split`; the first version
+    /// registered also answers `discover` until the service is registered
+    /// without a version.
+    pub async fn register_version(
         &self,
         req: ServiceRegisterRequest,
+        version: &str,
     ) -> Result<Option<String>, Box<dyn Error>> {
+        let config = self.build_config(&req).await?;
+        let endpoint = config.endpoint.to_string();
+
+        if let Ok(mut mp) = GLOBAL_MAP.lock() {
+            mp.entry(req.service_name.to_string())
+                .or_insert_with(|| config.clone());
+        }
+        match VERSION_MAP.lock() {
+            Ok(mut mp) => {
+                mp.entry(req.service_name.to_string())
+                    .or_default()
+                    .insert(version.to_string(), config);
+                Ok(Some(endpoint))
+            }
+            Err(e) => Err(Box::new(ValidationError(e.to_string()))),
+        }
+    }
+
+    /// Registers every service reflected from the endpoint that `filter`
+    /// matches, along with `req.service_name` when it is not empty, so one
+    /// process hosting several services is registered once. The services
+    /// share the registration's credentials and breaker. Returns the names
+    /// registered.
+    pub async fn register_services(
+        &self,
+        req: ServiceRegisterRequest,
+        filter: ServiceFilter,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let config = self.build_config(&req).await?;
+        let reflected = match GrpcGateway::new(&config.endpoint).await {
+            Ok(gateway) => gateway.reflected_services().await,
+            Err(e) => return Err(Box::new(ValidationError(e.to_string()))),
+        };
+
+        let mut services: Vec<String> = reflected
+            .iter()
+            .map(|service| service.full_name().to_string())
+            .filter(|service| filter.matches(service))
+            .collect();
+        if !req.service_name.is_empty() && !services.contains(&req.service_name) {
+            services.push(req.service_name.to_string());
+        }
+        services.sort();
+        if services.is_empty() {
+            return Err(Box::new(ValidationError(format!(
+                "no service reflected from {} matches the registration",
+                config.endpoint
+            ))));
+        }
+
+        match GLOBAL_MAP.lock() {
+            Ok(mut mp) => {
+                for service in &services {
+                    let mut service_config = config.clone();
+                    service_config.service_name = service.to_string();
+                    mp.insert(service.to_string(), service_config);
+                }
+                tracing::info!(endpoint = %config.endpoint, services = ?services, "registered reflected services");
+                Ok(services)
+            }
+            Err(e) => Err(Box::new(ValidationError(e.to_string()))),
+        }
+    }
+
+    pub fn discover_version(&self, service_name: &str, version: &str) -> Option<ServiceConfig> {
+        match VERSION_MAP.lock() {
+            Ok(mp) => mp.get(service_name)?.get(version).cloned(),
+            Err(_) => None,
+        }
+    }
+
+    /// Names of the registered versions of a service.
+    pub fn versions(&self, service_name: &str) -> Vec<String> {
+        let mut versions: Vec<String> = match VERSION_MAP.lock() {
+            Ok(mp) => mp
+                .get(service_name)
+                .map(|versions| versions.keys().cloned().collect())
+                .unwrap_or_default(),
+            Err(_) => Vec::new(),
+        };
+        versions.sort();
+        versions
+    }
+
+    pub fn deregister_version(&self, service_name: &str, version: &str) {
+        if let Ok(mut mp) = VERSION_MAP.lock()
+            && let Some(versions) = mp.get_mut(service_name)
+        {
+            versions.remove(version);
+        }
+    }
+
+    // validates the credentials and builds the configuration of a backend
+    async fn build_config(
+        &self,
+        req: &ServiceRegisterRequest,
+    ) -> Result<ServiceConfig, Box<dyn Error>> {
         let val = format!("http://{}:{}", req.host, req.port);
         let mut config = ServiceConfig {
             endpoint: val.to_string(),
@@ -58,7 +171,7 @@ impl RegistryTrait for ServiceRegistry {
             )));
         }
 
-        let auth_config = req.oauth_config.auth_refresh_config.unwrap();
+        let auth_config = req.oauth_config.auth_refresh_config.clone().unwrap();
         match req.oauth_config.auth_type {
             AuthType::APIKey => {
                 config.auth_config = Some(AuthConfig::APIKeyAuth(APIKeyAuth::new(
@@ -80,6 +193,17 @@ impl RegistryTrait for ServiceRegistry {
         // add breaker with default config
         let breaker = CircuitBreaker::new(CircuitBreakerConfig::default());
         config.breaker = Some(breaker);
+        Ok(config)
+    }
+}
+
+impl RegistryTrait for ServiceRegistry {
+    async fn register(
+        &self,
+        req: ServiceRegisterRequest,
+    ) -> Result<Option<String>, Box<dyn Error>> {
+        let config = self.build_config(&req).await?;
+        let val = config.endpoint.to_string();
 
         match GLOBAL_MAP.lock() {
             Ok(mut mp) => {
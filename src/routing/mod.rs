@@ -0,0 +1,2 @@
+pub mod table;
+pub mod traffic_split;
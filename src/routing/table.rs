@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+/// Rules mapping the service and method a client calls to the registered
+/// backend answering it.
+#[derive(Debug, Default)]
+pub struct RoutingTable {
+    // old service name to its new full name
+    aliases: RwLock<HashMap<String, String>>,
+    // (service, method) to the registered backend answering it
+    methods: RwLock<HashMap<(String, String), String>>,
+    // package prefix to the registered backend answering its services
+    prefixes: RwLock<HashMap<String, String>>,
+}
+
+impl RoutingTable {
+    /// Calls to `old_name` are made to `new_name`, which is also the name
+    /// used in the gRPC path, so clients keep working after a package is
+    /// renamed.
+    pub fn add_alias(&self, old_name: &str, new_name: &str) {
+        if let Ok(mut aliases) = self.aliases.write() {
+            aliases.insert(old_name.to_string(), new_name.to_string());
+        }
+    }
+
+    pub fn remove_alias(&self, old_name: &str) {
+        if let Ok(mut aliases) = self.aliases.write() {
+            aliases.remove(old_name);
+        }
+    }
+
+    /// Sends one method to the backend registered as `backend` instead of
+    /// the service's own, keeping the gRPC path.
+    pub fn route_method(&self, service: &str, method: &str, backend: &str) {
+        if let Ok(mut methods) = self.methods.write() {
+            methods.insert(
+                (service.to_string(), method.to_string()),
+                backend.to_string(),
+            );
+        }
+    }
+
+    pub fn remove_method_route(&self, service: &str, method: &str) {
+        if let Ok(mut methods) = self.methods.write() {
+            methods.remove(&(service.to_string(), method.to_string()));
+        }
+    }
+
+    /// Sends every service of a package, e.g. `billing` or `billing.v2`,
+    /// that has no registration of its own to the backend registered as
+    /// `backend`.
+    pub fn route_package(&self, package: &str, backend: &str) {
+        if let Ok(mut prefixes) = self.prefixes.write() {
+            prefixes.insert(
+                package.trim_end_matches('.').to_string(),
+                backend.to_string(),
+            );
+        }
+    }
+
+    pub fn remove_package_route(&self, package: &str) {
+        if let Ok(mut prefixes) = self.prefixes.write() {
+            prefixes.remove(package.trim_end_matches('.'));
+        }
+    }
+
+    /// The name a service is called by, after aliases.
+    pub fn resolve_service(&self, service: &str) -> String {
+        self.aliases
+            .read()
+            .ok()
+            .and_then(|aliases| aliases.get(service).cloned())
+            .unwrap_or_else(|| service.to_string())
+    }
+
+    /// Backend a method is routed to by a method-level rule.
+    pub fn method_backend(&self, service: &str, method: &str) -> Option<String> {
+        self.methods
+            .read()
+            .ok()?
+            .get(&(service.to_string(), method.to_string()))
+            .cloned()
+    }
+
+    /// Backend of the longest package prefix the service belongs to.
+    pub fn package_backend(&self, service: &str) -> Option<String> {
+        let prefixes = self.prefixes.read().ok()?;
+        prefixes
+            .iter()
+            .filter(|(package, _)| {
+                service
+                    .strip_prefix(package.as_str())
+                    .is_some_and(|rest| rest.starts_with('.'))
+            })
+            .max_by_key(|(package, _)| package.len())
+            .map(|(_, backend)| backend.to_string())
+    }
+}
@@ -0,0 +1,316 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+use crate::metrics::gateway_metrics;
+use crate::utils::model::RequestContext;
+
+/// Response header naming the backend version that answered.
+pub const BACKEND_VERSION_HEADER: &str = "x-backend-version";
+
+/// A registered version of a service and its share of the traffic.
+#[derive(Debug, Clone)]
+pub struct BackendVersion {
+    pub version: String,
+    /// relative to the weights of the other versions
+    pub weight: u32,
+}
+
+impl BackendVersion {
+    pub fn new(version: &str, weight: u32) -> Self {
+        Self {
+            version: version.to_string(),
+            weight,
+        }
+    }
+}
+
+/// Where a value is read from the incoming call.
+#[derive(Debug, Clone)]
+pub enum RequestKey {
+    Header(String),
+    Cookie(String),
+}
+
+impl RequestKey {
+    fn value<'a>(&self, ctx: &'a RequestContext) -> Option<&'a str> {
+        match self {
+            RequestKey::Header(name) => ctx.headers.get(&name.to_lowercase()).map(String::as_str),
+            RequestKey::Cookie(name) => ctx
+                .headers
+                .get("cookie")?
+                .split(';')
+                .filter_map(|cookie| cookie.trim().split_once('='))
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value),
+        }
+    }
+}
+
+/// Sends calls carrying a header or cookie value to one version, such as
+/// `x-canary: true` to the canary.
+#[derive(Debug, Clone)]
+pub struct VersionRoute {
+    pub key: RequestKey,
+    pub value: String,
+    pub version: String,
+}
+
+impl VersionRoute {
+    pub fn header(name: &str, value: &str, version: &str) -> Self {
+        Self {
+            key: RequestKey::Header(name.to_string()),
+            value: value.to_string(),
+            version: version.to_string(),
+        }
+    }
+
+    pub fn cookie(name: &str, value: &str, version: &str) -> Self {
+        Self {
+            key: RequestKey::Cookie(name.to_string()),
+            value: value.to_string(),
+            version: version.to_string(),
+        }
+    }
+}
+
+/// When a version's weight is moved back to the stable version.
+#[derive(Debug, Clone)]
+pub struct RollbackPolicy {
+    /// share of failed calls within `window` that rolls the version back
+    pub error_rate: f64,
+    /// calls needed within `window` before the error rate is trusted
+    pub min_calls: u64,
+    pub window: Duration,
+    /// rolls back as soon as the version's breaker opens
+    pub on_breaker_open: bool,
+}
+
+impl Default for RollbackPolicy {
+    fn default() -> Self {
+        Self {
+            error_rate: 0.2,
+            min_calls: 20,
+            window: Duration::from_secs(60),
+            on_breaker_open: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SplitConfig {
+    pub versions: Vec<BackendVersion>,
+    /// version never rolled back, which gets all traffic when no other
+    /// version has weight
+    pub stable: String,
+    /// checked in order before the weights, routes to a version without
+    /// weight are skipped
+    pub routes: Vec<VersionRoute>,
+    /// calls with the same value of this header or cookie always land on
+    /// the same version while the weights stay the same
+    pub sticky_key: Option<RequestKey>,
+    pub rollback: Option<RollbackPolicy>,
+}
+
+#[derive(Debug)]
+struct SplitState {
+    config: SplitConfig,
+    // current weights, changed by rollbacks and `set_weight`
+    weights: Vec<BackendVersion>,
+    windows: HashMap<String, ErrorWindow>,
+}
+
+impl SplitState {
+    // whether the version currently takes traffic
+    fn serves(&self, version: &str) -> bool {
+        version == self.config.stable
+            || self
+                .weights
+                .iter()
+                .any(|v| v.version == version && v.weight > 0)
+    }
+}
+
+#[derive(Debug)]
+struct ErrorWindow {
+    started_at: Instant,
+    calls: u64,
+    failures: u64,
+}
+
+/// Services whose traffic is split between registered versions.
+#[derive(Debug, Default)]
+pub struct TrafficSplit {
+    services: RwLock<HashMap<String, SplitState>>,
+}
+
+impl TrafficSplit {
+    pub fn enable(&self, service: &str, config: SplitConfig) {
+        for version in &config.versions {
+            gateway_metrics::record_backend_weight(service, &version.version, version.weight);
+        }
+        if let Ok(mut services) = self.services.write() {
+            services.insert(
+                service.to_string(),
+                SplitState {
+                    weights: config.versions.clone(),
+                    config,
+                    windows: HashMap::new(),
+                },
+            );
+        }
+    }
+
+    pub fn disable(&self, service: &str) {
+        if let Ok(mut services) = self.services.write() {
+            services.remove(service);
+        }
+    }
+
+    pub fn config_for(&self, service: &str) -> Option<SplitConfig> {
+        self.services
+            .read()
+            .ok()
+            .and_then(|services| services.get(service).map(|state| state.config.clone()))
+    }
+
+    /// Current weights of the service's versions, after rollbacks.
+    pub fn weights(&self, service: &str) -> Vec<BackendVersion> {
+        self.services
+            .read()
+            .ok()
+            .and_then(|services| services.get(service).map(|state| state.weights.clone()))
+            .unwrap_or_default()
+    }
+
+    /// Changes a version's weight, e.g. to promote a canary step by step or
+    /// to restore it after a rollback.
+    pub fn set_weight(&self, service: &str, version: &str, weight: u32) {
+        let Ok(mut services) = self.services.write() else {
+            return;
+        };
+        let Some(state) = services.get_mut(service) else {
+            return;
+        };
+        match state.weights.iter_mut().find(|v| v.version == version) {
+            Some(current) => current.weight = weight,
+            None => state.weights.push(BackendVersion::new(version, weight)),
+        }
+        state.windows.remove(version);
+        gateway_metrics::record_backend_weight(service, version, weight);
+    }
+
+    /// The version answering this call, when the service's traffic is split.
+    pub(crate) fn choose(&self, service: &str, ctx: &RequestContext) -> Option<String> {
+        let services = self.services.read().ok()?;
+        let state = services.get(service)?;
+
+        // a rolled back version keeps no traffic, even pinned traffic
+        if let Some(route) = state.config.routes.iter().find(|route| {
+            route.key.value(ctx) == Some(route.value.as_str()) && state.serves(&route.version)
+        }) {
+            return Some(route.version.to_string());
+        }
+
+        let total: u64 = state.weights.iter().map(|v| v.weight as u64).sum();
+        if total == 0 {
+            return Some(state.config.stable.to_string());
+        }
+        let point = match state
+            .config
+            .sticky_key
+            .as_ref()
+            .and_then(|key| key.value(ctx))
+        {
+            Some(value) => sticky_hash(value) % total,
+            None => rand::random_range(0..total),
+        };
+        let mut upper = 0;
+        for version in &state.weights {
+            upper += version.weight as u64;
+            if point < upper {
+                return Some(version.version.to_string());
+            }
+        }
+        Some(state.config.stable.to_string())
+    }
+
+    /// Counts a call answered by a version and rolls the version back when
+    /// it breaks the service's rollback policy.
+    pub(crate) fn observe(&self, service: &str, version: &str, failed: bool, breaker_open: bool) {
+        let Ok(mut services) = self.services.write() else {
+            return;
+        };
+        let Some(state) = services.get_mut(service) else {
+            return;
+        };
+        let Some(policy) = state.config.rollback.clone() else {
+            return;
+        };
+        if version == state.config.stable
+            || !state
+                .weights
+                .iter()
+                .any(|v| v.version == version && v.weight > 0)
+        {
+            return;
+        }
+
+        let now = Instant::now();
+        let window = state
+            .windows
+            .entry(version.to_string())
+            .or_insert(ErrorWindow {
+                started_at: now,
+                calls: 0,
+                failures: 0,
+            });
+        if now.duration_since(window.started_at) > policy.window {
+            *window = ErrorWindow {
+                started_at: now,
+                calls: 0,
+                failures: 0,
+            };
+        }
+        window.calls += 1;
+        if failed {
+            window.failures += 1;
+        }
+
+        let reason = if policy.on_breaker_open && breaker_open {
+            "breaker_open"
+        } else if window.calls >= policy.min_calls
+            && window.failures as f64 / window.calls as f64 > policy.error_rate
+        {
+            "error_rate"
+        } else {
+            return;
+        };
+
+        let (calls, failures) = (window.calls, window.failures);
+        state.windows.remove(version);
+        if let Some(current) = state.weights.iter_mut().find(|v| v.version == version) {
+            current.weight = 0;
+        }
+        gateway_metrics::record_backend_weight(service, version, 0);
+        gateway_metrics::record_rollback(service, version, reason);
+        tracing::warn!(
+            service = %service,
+            version = %version,
+            reason = %reason,
+            calls,
+            failures,
+            "rolled back backend version"
+        );
+    }
+}
+
+// 64-bit FNV-1a of a sticky key. The hash is fixed, unlike `DefaultHasher`,
+// so a key lands on the same version across restarts, builds and replicas
+fn sticky_hash(value: &str) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+    value.bytes().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(PRIME)
+    })
+}
@@ -0,0 +1,108 @@
+use prost_reflect::{DynamicMessage, Kind, MessageDescriptor, SerializeOptions, ServiceDescriptor};
+use serde_json::{Value, json};
+
+use crate::schema::json_schema::SchemaGenerator;
+
+// nested messages are expanded in examples up to this depth
+const EXAMPLE_MAX_DEPTH: usize = 3;
+
+pub fn service_list(services: &[ServiceDescriptor]) -> Value {
+    let services: Vec<Value> = services
+        .iter()
+        .map(|service| {
+            json!({
+                "name": service.full_name(),
+                "package": service.package_name(),
+                "methods": service.methods().count(),
+            })
+        })
+        .collect();
+    Value::Array(services)
+}
+
+/// Methods of a service with their message types, streaming flags and options.
+pub fn service_detail(service: &ServiceDescriptor) -> Value {
+    let methods: Vec<Value> = service
+        .methods()
+        .map(|method| {
+            json!({
+                "name": method.name(),
+                "full_name": method.full_name(),
+                "input_type": method.input().full_name(),
+                "output_type": method.output().full_name(),
+                "client_streaming": method.is_client_streaming(),
+                "server_streaming": method.is_server_streaming(),
+                "options": serde_json::to_value(method.options()).unwrap_or_default(),
+            })
+        })
+        .collect();
+
+    json!({
+        "name": service.full_name(),
+        "package": service.package_name(),
+        "options": serde_json::to_value(service.options()).unwrap_or_default(),
+        "methods": methods,
+    })
+}
+
+/// JSON Schema of a message together with an example payload holding the
+/// default value of every field.
+pub fn message_detail(message: &MessageDescriptor) -> Value {
+    let mut generator = SchemaGenerator::new("#/$defs/");
+    let root = generator.message_ref(message);
+    let definitions = generator.into_definitions();
+
+    let mut schema = json!({ "$schema": "https://json-schema.org/draft/2020-12/schema" });
+    match root.get("$ref") {
+        // inline the root definition, keep the rest under $defs
+        Some(_) => {
+            if let Some(Value::Object(fields)) = definitions.get(message.full_name()) {
+                for (key, value) in fields {
+                    schema[key] = value.clone();
+                }
+            }
+        }
+        None => {
+            if let Value::Object(fields) = root {
+                for (key, value) in fields {
+                    schema[key] = value;
+                }
+            }
+        }
+    }
+    schema["$defs"] = Value::Object(definitions.into_iter().collect());
+
+    let options = SerializeOptions::new().skip_default_fields(false);
+    let example = example_message(message, 0)
+        .serialize_with_options(serde_json::value::Serializer, &options)
+        .unwrap_or_default();
+
+    json!({
+        "name": message.full_name(),
+        "schema": schema,
+        "example": example,
+    })
+}
+
+fn example_message(message: &MessageDescriptor, depth: usize) -> DynamicMessage {
+    let mut example = DynamicMessage::new(message.clone());
+    if depth >= EXAMPLE_MAX_DEPTH {
+        return example;
+    }
+
+    for field in message.fields() {
+        if field.is_list() || field.is_map() || field.containing_oneof().is_some() {
+            continue;
+        }
+        match field.kind() {
+            Kind::Message(nested) if !nested.full_name().starts_with("google.protobuf.") => {
+                let value = prost_reflect::Value::Message(example_message(&nested, depth + 1));
+                example.set_field(&field, value);
+            }
+            Kind::Message(_) => {}
+            // set explicitly so fields with presence are emitted too
+            _ => example.set_field(&field, field.default_value()),
+        }
+    }
+    example
+}
@@ -0,0 +1,217 @@
+use std::collections::BTreeMap;
+
+use prost_reflect::{EnumDescriptor, FieldDescriptor, Kind, MessageDescriptor};
+use serde_json::{Map, Value, json};
+
+/// Builds JSON Schemas for protobuf messages following the proto3 JSON
+/// mapping. Named messages and enums are collected as definitions and
+/// referenced through `ref_prefix`, e.g. `#/components/schemas/`.
+pub struct SchemaGenerator {
+    ref_prefix: String,
+    definitions: BTreeMap<String, Value>,
+}
+
+impl SchemaGenerator {
+    pub fn new(ref_prefix: &str) -> Self {
+        Self {
+            ref_prefix: ref_prefix.to_string(),
+            definitions: BTreeMap::new(),
+        }
+    }
+
+    /// Returns a schema referencing `message`, registering it and every type
+    /// it depends on as definitions.
+    pub fn message_ref(&mut self, message: &MessageDescriptor) -> Value {
+        if let Some(schema) = well_known_schema(message.full_name()) {
+            return schema;
+        }
+
+        let name = message.full_name().to_string();
+        if !self.definitions.contains_key(&name) {
+            // placeholder first so recursive messages terminate
+            self.definitions.insert(name.clone(), Value::Null);
+            let schema = self.message_schema(message);
+            self.definitions.insert(name.clone(), schema);
+        }
+        json!({ "$ref": format!("{}{}", self.ref_prefix, name) })
+    }
+
+    pub fn into_definitions(self) -> BTreeMap<String, Value> {
+        self.definitions
+    }
+
+    fn message_schema(&mut self, message: &MessageDescriptor) -> Value {
+        let mut properties = Map::new();
+        for field in message.fields() {
+            properties.insert(field.json_name().to_string(), self.field_schema(&field));
+        }
+
+        let mut schema = json!({
+            "type": "object",
+            "title": message.name(),
+            "properties": properties,
+        });
+
+        // at most one member of each oneof may be set
+        let oneofs: Map<String, Value> = message
+            .oneofs()
+            .filter(|oneof| !oneof.is_synthetic())
+            .map(|oneof| {
+                let members = oneof
+                    .fields()
+                    .map(|f| Value::String(f.json_name().to_string()))
+                    .collect();
+                (oneof.name().to_string(), Value::Array(members))
+            })
+            .collect();
+        if !oneofs.is_empty() {
+            schema["x-oneof"] = Value::Object(oneofs);
+        }
+        schema
+    }
+
+    fn field_schema(&mut self, field: &FieldDescriptor) -> Value {
+        if field.is_map() {
+            let entry = field.kind();
+            let value_field = entry
+                .as_message()
+                .map(|entry| entry.map_entry_value_field());
+            let values = match value_field {
+                Some(value_field) => self.kind_schema(&value_field.kind()),
+                None => json!({}),
+            };
+            return json!({ "type": "object", "additionalProperties": values });
+        }
+
+        let item = self.kind_schema(&field.kind());
+        if field.is_list() {
+            json!({ "type": "array", "items": item })
+        } else {
+            item
+        }
+    }
+
+    fn kind_schema(&mut self, kind: &Kind) -> Value {
+        match kind {
+            Kind::Double | Kind::Float => json!({ "type": "number" }),
+            Kind::Int32 | Kind::Sint32 | Kind::Sfixed32 => {
+                json!({ "type": "integer", "format": "int32" })
+            }
+            Kind::Uint32 | Kind::Fixed32 => {
+                json!({ "type": "integer", "format": "int64", "minimum": 0, "maximum": u32::MAX })
+            }
+            // 64-bit integers are encoded as JSON strings
+            Kind::Int64 | Kind::Sint64 | Kind::Sfixed64 => {
+                json!({ "type": "string", "format": "int64", "pattern": "^-?[0-9]+$" })
+            }
+            Kind::Uint64 | Kind::Fixed64 => {
+                json!({ "type": "string", "format": "uint64", "pattern": "^[0-9]+$" })
+            }
+            Kind::Bool => json!({ "type": "boolean" }),
+            Kind::String => json!({ "type": "string" }),
+            Kind::Bytes => json!({ "type": "string", "format": "byte" }),
+            Kind::Message(message) => self.message_ref(message),
+            Kind::Enum(enum_desc) => self.enum_ref(enum_desc),
+        }
+    }
+
+    fn enum_ref(&mut self, enum_desc: &EnumDescriptor) -> Value {
+        if enum_desc.full_name() == "google.protobuf.NullValue" {
+            return json!({ "type": "null" });
+        }
+
+        let name = enum_desc.full_name().to_string();
+        if !self.definitions.contains_key(&name) {
+            let values: Vec<Value> = enum_desc
+                .values()
+                .map(|v| Value::String(v.name().to_string()))
+                .collect();
+            self.definitions.insert(
+                name.clone(),
+                json!({ "type": "string", "title": enum_desc.name(), "enum": values }),
+            );
+        }
+        json!({ "$ref": format!("{}{}", self.ref_prefix, name) })
+    }
+}
+
+// Well-known types have dedicated JSON representations
+fn well_known_schema(full_name: &str) -> Option<Value> {
+    let schema = match full_name {
+        "google.protobuf.Timestamp" => json!({ "type": "string", "format": "date-time" }),
+        "google.protobuf.Duration" => {
+            json!({ "type": "string", "pattern": "^-?[0-9]+(\\.[0-9]{1,9})?s$" })
+        }
+        "google.protobuf.FieldMask" => json!({ "type": "string" }),
+        "google.protobuf.Struct" => json!({ "type": "object", "additionalProperties": true }),
+        "google.protobuf.ListValue" => json!({ "type": "array", "items": {} }),
+        "google.protobuf.Value" => json!({}),
+        "google.protobuf.Empty" => json!({ "type": "object" }),
+        "google.protobuf.Any" => json!({
+            "type": "object",
+            "properties": { "@type": { "type": "string" } },
+            "required": ["@type"],
+            "additionalProperties": true
+        }),
+        "google.protobuf.DoubleValue" | "google.protobuf.FloatValue" => {
+            json!({ "type": ["number", "null"] })
+        }
+        "google.protobuf.Int32Value" => json!({ "type": ["integer", "null"], "format": "int32" }),
+        "google.protobuf.UInt32Value" => json!({
+            "type": ["integer", "null"],
+            "format": "int64",
+            "minimum": 0,
+            "maximum": u32::MAX
+        }),
+        "google.protobuf.Int64Value" | "google.protobuf.UInt64Value" => {
+            json!({ "type": ["string", "null"], "format": "int64" })
+        }
+        "google.protobuf.BoolValue" => json!({ "type": ["boolean", "null"] }),
+        "google.protobuf.StringValue" => json!({ "type": ["string", "null"] }),
+        "google.protobuf.BytesValue" => json!({ "type": ["string", "null"], "format": "byte" }),
+        _ => return None,
+    };
+    Some(schema)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_descriptors;
+
+    const COUNTERS_PROTO: &str = r#"
+        name: "counters.proto" package: "counters" syntax: "proto3"
+        dependency: "google/protobuf/wrappers.proto"
+        message_type {
+            name: "Counter"
+            field { name: "signed" number: 1 type: TYPE_INT32 label: LABEL_OPTIONAL json_name: "signed" }
+            field { name: "unsigned" number: 2 type: TYPE_UINT32 label: LABEL_OPTIONAL json_name: "unsigned" }
+            field { name: "fixed" number: 3 type: TYPE_FIXED32 label: LABEL_OPTIONAL json_name: "fixed" }
+            field { name: "wrapped" number: 4 type: TYPE_MESSAGE label: LABEL_OPTIONAL type_name: ".google.protobuf.UInt32Value" json_name: "wrapped" }
+        }
+    "#;
+
+    fn counter_properties() -> Value {
+        let pool = test_descriptors::pool(&[COUNTERS_PROTO]);
+        let mut generator = SchemaGenerator::new("#/");
+        generator.message_ref(&pool.get_message_by_name("counters.Counter").unwrap());
+        generator.into_definitions()["counters.Counter"]["properties"].clone()
+    }
+
+    #[test]
+    fn unsigned_32_bit_integers_allow_values_above_int32() {
+        let properties = counter_properties();
+
+        for name in ["unsigned", "fixed"] {
+            assert_eq!(
+                properties[name],
+                json!({ "type": "integer", "format": "int64", "minimum": 0, "maximum": 4294967295u32 })
+            );
+        }
+        assert_eq!(properties["wrapped"]["maximum"], json!(4294967295u32));
+        assert_eq!(
+            properties["signed"],
+            json!({ "type": "integer", "format": "int32" })
+        );
+    }
+}
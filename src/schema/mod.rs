@@ -0,0 +1,3 @@
+pub mod discovery;
+pub mod json_schema;
+pub mod openapi;
@@ -0,0 +1,196 @@
+use std::sync::{Arc, RwLock};
+use std::time::Instant;
+
+use prost_reflect::{MethodDescriptor, ServiceDescriptor};
+use serde_json::{Map, Value, json};
+
+use crate::schema::json_schema::SchemaGenerator;
+
+const SCHEMA_REF_PREFIX: &str = "#/components/schemas/";
+const ENVELOPE_SCHEMA: &str = "grpc_gateway.Response";
+
+/// Identifies the descriptors a document was generated from: the service
+/// name and the time its endpoint's descriptors were last refreshed.
+pub type DescriptorFingerprint = Vec<(String, Instant)>;
+
+/// Keeps the last generated document until the registered services or their
+/// descriptors change.
+#[derive(Debug, Default)]
+pub struct OpenApiCache {
+    document: RwLock<Option<(DescriptorFingerprint, Arc<Value>)>>,
+}
+
+impl OpenApiCache {
+    pub fn get_or_build<F>(&self, fingerprint: DescriptorFingerprint, build: F) -> Arc<Value>
+    where
+        F: FnOnce() -> Value,
+    {
+        if let Ok(cached) = self.document.read()
+            && let Some((cached_fingerprint, document)) = cached.as_ref()
+            && *cached_fingerprint == fingerprint
+        {
+            return document.clone();
+        }
+
+        let document = Arc::new(build());
+        if let Ok(mut cached) = self.document.write() {
+            *cached = Some((fingerprint, document.clone()));
+        }
+        document
+    }
+}
+
+/// Generates an OpenAPI 3.1 document with one `POST /{service}/{method}`
+/// operation per unary method of the given services.
+pub fn build_document(services: &[ServiceDescriptor]) -> Value {
+    let mut generator = SchemaGenerator::new(SCHEMA_REF_PREFIX);
+    let mut paths = Map::new();
+    let mut tags = Vec::new();
+
+    for service in services {
+        tags.push(json!({ "name": service.full_name() }));
+
+        for method in service.methods() {
+            // streaming methods cannot be called over the JSON endpoint
+            if method.is_client_streaming() || method.is_server_streaming() {
+                continue;
+            }
+
+            let path = format!("/{}/{}", service.full_name(), method.name());
+            paths.insert(
+                path,
+                json!({ "post": operation(service, &method, &mut generator) }),
+            );
+        }
+    }
+
+    let mut schemas: Map<String, Value> = generator.into_definitions().into_iter().collect();
+    schemas.insert(ENVELOPE_SCHEMA.to_string(), envelope_schema());
+
+    json!({
+        "openapi": "3.1.0",
+        "info": {
+            "title": "gRPC Gateway",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "tags": tags,
+        "paths": paths,
+        "components": { "schemas": schemas },
+    })
+}
+
+fn operation(
+    service: &ServiceDescriptor,
+    method: &MethodDescriptor,
+    generator: &mut SchemaGenerator,
+) -> Value {
+    let input = generator.message_ref(&method.input());
+    let output = generator.message_ref(&method.output());
+    let envelope = json!({ "$ref": format!("{}{}", SCHEMA_REF_PREFIX, ENVELOPE_SCHEMA) });
+
+    let mut operation = json!({
+        "operationId": format!("{}_{}", service.full_name().replace('.', "_"), method.name()),
+        "tags": [service.full_name()],
+        "requestBody": {
+            "required": true,
+            "content": { "application/json": { "schema": input } },
+        },
+        "responses": {
+            "200": {
+                "description": "Successful response",
+                "content": {
+                    "application/json": {
+                        "schema": {
+                            "allOf": [envelope, { "properties": { "data": output } }]
+                        }
+                    }
+                },
+            },
+            "default": {
+                "description": "Error response",
+                "content": { "application/json": { "schema": envelope } },
+            },
+        },
+    });
+
+    if let Some(rule) = http_rule(method) {
+        operation["x-google-api-http"] = rule;
+    }
+    operation
+}
+
+// `google.api.http` annotation of the method, when its descriptor was reflected
+fn http_rule(method: &MethodDescriptor) -> Option<Value> {
+    let extension = method
+        .parent_pool()
+        .get_extension_by_name("google.api.http")?;
+    let options = method.options();
+    if !options.has_extension(&extension) {
+        return None;
+    }
+    let rule = options.get_extension(&extension);
+    rule.as_message()
+        .and_then(|rule| serde_json::to_value(rule).ok())
+}
+
+fn envelope_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "message": { "type": "string" },
+            "status": { "type": "string" },
+            "data": {},
+            "request_id": { "type": "string" },
+        },
+        "required": ["message", "status"],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_descriptors;
+
+    // `Users.Get` in two packages
+    fn services() -> Vec<ServiceDescriptor> {
+        let file = |package: &str| {
+            format!(
+                r#"
+                name: "{package}.proto" package: "{package}" syntax: "proto3"
+                message_type {{ name: "GetRequest" field {{ name: "id" number: 1 type: TYPE_STRING label: LABEL_OPTIONAL json_name: "id" }} }}
+                service {{ name: "Users" method {{ name: "Get" input_type: ".{package}.GetRequest" output_type: ".{package}.GetRequest" }} }}
+                "#
+            )
+        };
+        let pool = test_descriptors::pool(&[&file("accounts.v1"), &file("billing.v1")]);
+        pool.services().collect()
+    }
+
+    #[test]
+    fn operation_ids_are_unique_across_packages() {
+        let document = build_document(&services());
+
+        let accounts = &document["paths"]["/accounts.v1.Users/Get"]["post"]["operationId"];
+        let billing = &document["paths"]["/billing.v1.Users/Get"]["post"]["operationId"];
+        assert_eq!(accounts, "accounts_v1_Users_Get");
+        assert_eq!(billing, "billing_v1_Users_Get");
+    }
+
+    #[test]
+    fn cached_document_is_reused_for_the_same_descriptors() {
+        let cache = OpenApiCache::default();
+        let fingerprint = vec![("accounts.v1.Users".to_string(), Instant::now())];
+        let mut builds = 0;
+
+        cache.get_or_build(fingerprint.clone(), || {
+            builds += 1;
+            json!({})
+        });
+        cache.get_or_build(fingerprint, || {
+            builds += 1;
+            json!({})
+        });
+
+        assert_eq!(builds, 1);
+    }
+}
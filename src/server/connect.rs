@@ -0,0 +1,759 @@
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::time::{Duration, Instant};
+
+use actix_web::guard::{self, GuardContext};
+use actix_web::http::{Method, StatusCode, header};
+use actix_web::{HttpRequest, HttpResponse, web};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use bytes::{BufMut, Bytes, BytesMut};
+use futures::StreamExt;
+use futures::stream::{self, BoxStream};
+use prost_reflect::{MessageDescriptor, MethodDescriptor};
+use serde_json::{Value, json};
+use tonic::Code;
+
+use crate::Gateway;
+use crate::gateway::json_format::ALT_PARAM;
+use crate::graphql::schema::is_read_only;
+use crate::logging::access_log::AccessLogEntry;
+use crate::server::encoding::PayloadEncoding;
+use crate::server::routes::request_context;
+use crate::utils::model::{RequestContext, RequestType};
+use crate::utils::request_id::REQUEST_ID_HEADER;
+use crate::utils::response::Response;
+
+pub const PROTOCOL_VERSION_HEADER: &str = "connect-protocol-version";
+pub const TIMEOUT_HEADER: &str = "connect-timeout-ms";
+const STREAM_ENCODING_HEADER: &str = "connect-content-encoding";
+
+const STREAM_CONTENT_TYPE: &str = "application/connect+";
+const FLAG_COMPRESSED: u8 = 0x01;
+const FLAG_END_STREAM: u8 = 0x02;
+
+// Connect clients speak canonical protobuf JSON whatever the service default
+const CONNECT_ALT: &str = "json;defaults=false;names=json;enum=string;int64=string;any=resolve";
+
+/// Registers the Connect protocol endpoints, `POST /{package.Service}/{Method}`
+/// and `GET` for `NO_SIDE_EFFECTS` methods. Only requests carrying the
+/// `Connect-Protocol-Version` header, a `application/connect+*` content type
+/// or `?connect=v1` are routed here, other calls fall through to the
+/// gateway's own `POST /{service}/{method}`. Included in `routes::configure`.
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::resource("/{service}/{method}")
+            .guard(guard::fn_guard(is_connect_request))
+            .route(web::post().to(post))
+            .route(web::get().to(get)),
+    );
+}
+
+fn is_connect_request(ctx: &GuardContext<'_>) -> bool {
+    let head = ctx.head();
+    if head.headers().contains_key(PROTOCOL_VERSION_HEADER) {
+        return true;
+    }
+    let streaming = head
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.to_lowercase().starts_with(STREAM_CONTENT_TYPE));
+    streaming
+        || head.method == Method::GET
+            && head
+                .uri
+                .query()
+                .is_some_and(|q| q.split('&').any(|p| p == "connect=v1"))
+}
+
+/// Message codec negotiated by the Connect content type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Codec {
+    Json,
+    Proto,
+}
+
+impl Codec {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "json" => Some(Self::Json),
+            "proto" => Some(Self::Proto),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Proto => "proto",
+        }
+    }
+
+    fn decode(&self, body: &[u8], input: Option<&MessageDescriptor>) -> Result<Value, String> {
+        match self {
+            // an empty body is the empty message
+            Self::Json if body.is_empty() => Ok(json!({})),
+            Self::Json => PayloadEncoding::Json.decode(body, input),
+            Self::Proto => PayloadEncoding::Protobuf.decode(body, input),
+        }
+    }
+
+    fn encode(&self, data: &Value, output: Option<&MessageDescriptor>) -> Result<Vec<u8>, String> {
+        match self {
+            Self::Json => serde_json::to_vec(data).map_err(|e| e.to_string()),
+            Self::Proto => {
+                PayloadEncoding::Protobuf.encode_message(data, output.ok_or("unknown method")?)
+            }
+        }
+    }
+}
+
+/// An error in the Connect wire format.
+#[derive(Debug)]
+struct ConnectError {
+    code: Code,
+    message: String,
+}
+
+impl ConnectError {
+    fn new(code: Code, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    // the backend status when the call failed there, the HTTP status otherwise
+    fn from_response(response: &Response) -> Self {
+        let mut message = response.message.to_string();
+        if let Some(violations) = response
+            .data
+            .as_ref()
+            .and_then(|data| data.get("violations"))
+            .and_then(Value::as_array)
+        {
+            let details: Vec<String> = violations
+                .iter()
+                .map(|v| {
+                    format!(
+                        "{}: {}",
+                        v["path"].as_str().unwrap_or_default(),
+                        v["message"].as_str().unwrap_or_default()
+                    )
+                })
+                .collect();
+            message = format!("{}: {}", message, details.join("; "));
+        }
+        let code = response
+            .grpc_code
+            .unwrap_or_else(|| code_for_http(response.status_code.as_u16()));
+        Self::new(code, message)
+    }
+
+    fn from_stream_error(e: &anyhow::Error) -> Self {
+        match e.downcast_ref::<tonic::Status>() {
+            Some(status) => Self::new(status.code(), status.message()),
+            None => Self::new(Code::Unknown, e.to_string()),
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        json!({ "code": code_name(self.code), "message": self.message })
+    }
+
+    fn into_response(self) -> HttpResponse {
+        HttpResponse::build(http_status(self.code))
+            .content_type("application/json")
+            .body(self.to_json().to_string())
+    }
+}
+
+fn code_name(code: Code) -> &'static str {
+    match code {
+        Code::Ok => "ok",
+        Code::Cancelled => "canceled",
+        Code::Unknown => "unknown",
+        Code::InvalidArgument => "invalid_argument",
+        Code::DeadlineExceeded => "deadline_exceeded",
+        Code::NotFound => "not_found",
+        Code::AlreadyExists => "already_exists",
+        Code::PermissionDenied => "permission_denied",
+        Code::ResourceExhausted => "resource_exhausted",
+        Code::FailedPrecondition => "failed_precondition",
+        Code::Aborted => "aborted",
+        Code::OutOfRange => "out_of_range",
+        Code::Unimplemented => "unimplemented",
+        Code::Internal => "internal",
+        Code::Unavailable => "unavailable",
+        Code::DataLoss => "data_loss",
+        Code::Unauthenticated => "unauthenticated",
+    }
+}
+
+// HTTP status of a unary error, as fixed by the Connect protocol
+fn http_status(code: Code) -> StatusCode {
+    match code {
+        Code::Ok => StatusCode::OK,
+        Code::Cancelled => StatusCode::from_u16(499).unwrap_or(StatusCode::REQUEST_TIMEOUT),
+        Code::InvalidArgument | Code::FailedPrecondition | Code::OutOfRange => {
+            StatusCode::BAD_REQUEST
+        }
+        Code::DeadlineExceeded => StatusCode::GATEWAY_TIMEOUT,
+        Code::NotFound => StatusCode::NOT_FOUND,
+        Code::AlreadyExists | Code::Aborted => StatusCode::CONFLICT,
+        Code::PermissionDenied => StatusCode::FORBIDDEN,
+        Code::ResourceExhausted => StatusCode::TOO_MANY_REQUESTS,
+        Code::Unimplemented => StatusCode::NOT_IMPLEMENTED,
+        Code::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+        Code::Unauthenticated => StatusCode::UNAUTHORIZED,
+        Code::Unknown | Code::Internal | Code::DataLoss => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+// code for errors raised by the gateway itself, which only carry an HTTP status
+fn code_for_http(status: u16) -> Code {
+    match status {
+        400 | 415 => Code::InvalidArgument,
+        401 => Code::Unauthenticated,
+        403 => Code::PermissionDenied,
+        404 => Code::NotFound,
+        408 | 504 => Code::DeadlineExceeded,
+        409 => Code::Aborted,
+        412 => Code::FailedPrecondition,
+        429 => Code::ResourceExhausted,
+        501 => Code::Unimplemented,
+        502 | 503 => Code::Unavailable,
+        500 => Code::Internal,
+        _ => Code::Unknown,
+    }
+}
+
+fn header_value<'a>(http_req: &'a HttpRequest, name: &str) -> Option<&'a str> {
+    http_req.headers().get(name).and_then(|v| v.to_str().ok())
+}
+
+// rejects anything but the identity encoding, the gateway does not decompress
+fn check_identity(encoding: Option<&str>) -> Result<(), ConnectError> {
+    match encoding {
+        None | Some("identity") | Some("") => Ok(()),
+        Some(other) => Err(ConnectError::new(
+            Code::Unimplemented,
+            format!("unsupported compression {}", other),
+        )),
+    }
+}
+
+fn check_version(http_req: &HttpRequest) -> Result<(), ConnectError> {
+    match header_value(http_req, PROTOCOL_VERSION_HEADER) {
+        None | Some("1") => Ok(()),
+        Some(other) => Err(ConnectError::new(
+            Code::InvalidArgument,
+            format!("unsupported connect protocol version {}", other),
+        )),
+    }
+}
+
+fn timeout(http_req: &HttpRequest) -> Result<Option<Duration>, ConnectError> {
+    header_value(http_req, TIMEOUT_HEADER)
+        .map(|v| match v.parse::<u64>() {
+            Ok(ms) if v.len() <= 10 => Ok(Duration::from_millis(ms)),
+            _ => Err(ConnectError::new(
+                Code::InvalidArgument,
+                format!("invalid {} {}", TIMEOUT_HEADER, v),
+            )),
+        })
+        .transpose()
+}
+
+// the context handed to the gateway, with the JSON mapping Connect expects
+fn call_context(http_req: &HttpRequest) -> RequestContext {
+    let mut ctx = request_context(http_req);
+    ctx.query = HashMap::from([(ALT_PARAM.to_string(), CONNECT_ALT.to_string())]);
+    ctx.headers.remove("if-none-match");
+    ctx
+}
+
+async fn post(
+    http_req: HttpRequest,
+    gateway: web::Data<Gateway>,
+    path: web::Path<(String, String)>,
+    body: web::Bytes,
+) -> HttpResponse {
+    let (service, method) = path.into_inner();
+    let content_type = header_value(&http_req, header::CONTENT_TYPE.as_str())
+        .unwrap_or_default()
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_lowercase();
+
+    if let Some(codec) = content_type.strip_prefix(STREAM_CONTENT_TYPE) {
+        return match Codec::from_name(codec) {
+            Some(codec) => server_stream(&http_req, &gateway, service, method, codec, &body).await,
+            None => unsupported_media_type(),
+        };
+    }
+    let Some(codec) = content_type
+        .strip_prefix("application/")
+        .and_then(Codec::from_name)
+    else {
+        return unsupported_media_type();
+    };
+    if let Err(e) = check_version(&http_req)
+        .and_then(|_| check_identity(header_value(&http_req, header::CONTENT_ENCODING.as_str())))
+    {
+        return e.into_response();
+    }
+    let descriptor = gateway.method_descriptor(&service, &method).await;
+    unary(
+        &http_req, &gateway, service, method, codec, &body, descriptor,
+    )
+    .await
+}
+
+// `GET` with the message in the query string, for `NO_SIDE_EFFECTS` methods
+async fn get(
+    http_req: HttpRequest,
+    gateway: web::Data<Gateway>,
+    path: web::Path<(String, String)>,
+) -> HttpResponse {
+    let (service, method) = path.into_inner();
+    let query: HashMap<String, String> =
+        url::form_urlencoded::parse(http_req.query_string().as_bytes())
+            .into_owned()
+            .collect();
+
+    let Some(codec) = query.get("encoding").and_then(|e| Codec::from_name(e)) else {
+        return ConnectError::new(Code::InvalidArgument, "missing or unsupported encoding")
+            .into_response();
+    };
+    if let Err(e) = check_identity(query.get("compression").map(String::as_str)) {
+        return e.into_response();
+    }
+    let descriptor = gateway.method_descriptor(&service, &method).await;
+    if descriptor.as_ref().is_some_and(|m| !is_read_only(m)) {
+        let mut response = ConnectError::new(
+            Code::Unimplemented,
+            format!(
+                "{}/{} has side effects and must be called with POST",
+                service, method
+            ),
+        )
+        .into_response();
+        *response.status_mut() = StatusCode::METHOD_NOT_ALLOWED;
+        response
+            .headers_mut()
+            .insert(header::ALLOW, header::HeaderValue::from_static("POST"));
+        return response;
+    }
+
+    let message = query.get("message").map(String::as_str).unwrap_or_default();
+    let body = if query.get("base64").is_some_and(|b| b == "1") {
+        match URL_SAFE_NO_PAD.decode(message.trim_end_matches('=')) {
+            Ok(body) => body,
+            Err(e) => {
+                return ConnectError::new(Code::InvalidArgument, format!("invalid message: {}", e))
+                    .into_response();
+            }
+        }
+    } else {
+        message.as_bytes().to_vec()
+    };
+    unary(
+        &http_req, &gateway, service, method, codec, &body, descriptor,
+    )
+    .await
+}
+
+fn unsupported_media_type() -> HttpResponse {
+    HttpResponse::UnsupportedMediaType()
+        .insert_header((
+            "accept-post",
+            "application/json, application/proto, application/connect+json, application/connect+proto",
+        ))
+        .finish()
+}
+
+async fn unary(
+    http_req: &HttpRequest,
+    gateway: &Gateway,
+    service: String,
+    method: String,
+    codec: Codec,
+    body: &[u8],
+    descriptor: Option<MethodDescriptor>,
+) -> HttpResponse {
+    let started_at = Instant::now();
+    if descriptor
+        .as_ref()
+        .is_some_and(|m| m.is_client_streaming() || m.is_server_streaming())
+    {
+        return ConnectError::new(
+            Code::Unimplemented,
+            format!("{}/{} is a streaming method", service, method),
+        )
+        .into_response();
+    }
+    let data = match codec.decode(body, descriptor.as_ref().map(|m| m.input()).as_ref()) {
+        Ok(data) => data,
+        Err(e) => {
+            return ConnectError::new(Code::InvalidArgument, format!("invalid message: {}", e))
+                .into_response();
+        }
+    };
+    let deadline = match timeout(http_req) {
+        Ok(deadline) => deadline,
+        Err(e) => return e.into_response(),
+    };
+
+    let ctx = call_context(http_req);
+    let client_ip = ctx.client_ip.clone();
+    let req = RequestType {
+        service: service.to_string(),
+        method: method.to_string(),
+        data,
+    };
+    let request_body = gateway
+        .access_log
+        .include_request_body
+        .then(|| req.data.clone());
+    let call = gateway.invoke_with_context(req, ctx);
+    let response = match deadline {
+        Some(deadline) => match tokio::time::timeout(deadline, call).await {
+            Ok(response) => Ok(response),
+            Err(_) => Err(ConnectError::new(
+                Code::DeadlineExceeded,
+                "deadline exceeded",
+            )),
+        },
+        None => Ok(call.await),
+    };
+
+    let (mut builder, payload) = match response {
+        Ok(response) if response.status_code.is_success() => {
+            let output = descriptor.as_ref().map(|m| m.output());
+            let data = response.data.clone().unwrap_or_else(|| json!({}));
+            match codec.encode(&data, output.as_ref()) {
+                Ok(payload) => {
+                    let mut builder = HttpResponse::Ok();
+                    builder.content_type(format!("application/{}", codec.name()));
+                    add_headers(&mut builder, &response);
+                    (builder, payload)
+                }
+                Err(e) => error_parts(
+                    ConnectError::new(Code::Internal, format!("failed to encode response: {}", e)),
+                    Some(&response),
+                ),
+            }
+        }
+        Ok(response) => error_parts(ConnectError::from_response(&response), Some(&response)),
+        Err(e) => error_parts(e, None),
+    };
+
+    let bytes = payload.len();
+    let http_response = builder.body(payload);
+    gateway.access_log.emit(AccessLogEntry {
+        request_id: http_response
+            .headers()
+            .get(REQUEST_ID_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string),
+        service,
+        method,
+        status: http_response.status().as_u16(),
+        latency_ms: started_at.elapsed().as_secs_f64() * 1000.0,
+        bytes,
+        client_ip,
+        request: request_body,
+    });
+    http_response
+}
+
+fn error_parts(
+    error: ConnectError,
+    response: Option<&Response>,
+) -> (actix_web::HttpResponseBuilder, Vec<u8>) {
+    let mut builder = HttpResponse::build(http_status(error.code));
+    builder.content_type("application/json");
+    if let Some(response) = response {
+        add_headers(&mut builder, response);
+    }
+    (builder, error.to_json().to_string().into_bytes())
+}
+
+fn add_headers(builder: &mut actix_web::HttpResponseBuilder, response: &Response) {
+    if let Some(request_id) = &response.request_id {
+        builder.insert_header((REQUEST_ID_HEADER, request_id.as_str()));
+    }
+    for (name, value) in &response.headers {
+        builder.insert_header((name.as_str(), value.as_str()));
+    }
+}
+
+// Server streaming calls: one enveloped request message in, enveloped
+// response messages out, closed by an end-of-stream message. Errors go in
+// the end-of-stream message with a 200 status.
+async fn server_stream(
+    http_req: &HttpRequest,
+    gateway: &Gateway,
+    service: String,
+    method: String,
+    codec: Codec,
+    body: &[u8],
+) -> HttpResponse {
+    let mut response = HttpResponse::Ok();
+    response.content_type(format!("{}{}", STREAM_CONTENT_TYPE, codec.name()));
+
+    let opened = open_stream(http_req, gateway, service, method, codec, body).await;
+    let (messages, deadline) = match opened {
+        Ok(opened) => opened,
+        Err(e) => {
+            let end = stream::once(async move { Ok::<_, Infallible>(end_stream(Some(e))) });
+            return response.streaming(end);
+        }
+    };
+
+    let frames = stream::unfold(Some(messages), move |state| async move {
+        let mut messages = state?;
+        let next = match deadline {
+            Some(deadline) => match tokio::time::timeout_at(deadline, messages.next()).await {
+                Ok(next) => next,
+                Err(_) => Some(Err(ConnectError::new(
+                    Code::DeadlineExceeded,
+                    "deadline exceeded",
+                ))),
+            },
+            None => messages.next().await,
+        };
+        match next {
+            Some(Ok(frame)) => Some((Ok::<_, Infallible>(frame), Some(messages))),
+            Some(Err(e)) => Some((Ok(end_stream(Some(e))), None)),
+            None => Some((Ok(end_stream(None)), None)),
+        }
+    });
+    response.streaming(frames)
+}
+
+type MessageFrames = BoxStream<'static, Result<Bytes, ConnectError>>;
+
+async fn open_stream(
+    http_req: &HttpRequest,
+    gateway: &Gateway,
+    service: String,
+    method: String,
+    codec: Codec,
+    body: &[u8],
+) -> Result<(MessageFrames, Option<tokio::time::Instant>), ConnectError> {
+    check_version(http_req)?;
+    check_identity(header_value(http_req, STREAM_ENCODING_HEADER))?;
+    let deadline = timeout(http_req)?.map(|t| tokio::time::Instant::now() + t);
+
+    let Some(descriptor) = gateway.method_descriptor(&service, &method).await else {
+        return Err(ConnectError::new(
+            Code::Unimplemented,
+            format!("unknown method {}/{}", service, method),
+        ));
+    };
+    if descriptor.is_client_streaming() {
+        return Err(ConnectError::new(
+            Code::Unimplemented,
+            "client and bidirectional streaming are not supported",
+        ));
+    }
+    let message = read_envelope(body)?;
+    let data = codec
+        .decode(message, Some(&descriptor.input()))
+        .map_err(|e| ConnectError::new(Code::InvalidArgument, format!("invalid message: {}", e)))?;
+
+    let req = RequestType {
+        service,
+        method,
+        data,
+    };
+    let messages = gateway
+        .invoke_stream(req, call_context(http_req))
+        .await
+        .map_err(|response| ConnectError::from_response(&response))?;
+
+    let output = descriptor.output();
+    let frames = messages.map(move |item| match item {
+        Ok(value) => codec
+            .encode(&value, Some(&output))
+            .map(|payload| envelope(0, &payload))
+            .map_err(|e| {
+                ConnectError::new(Code::Internal, format!("failed to encode message: {}", e))
+            }),
+        Err(e) => Err(ConnectError::from_stream_error(&e)),
+    });
+    Ok((frames.boxed(), deadline))
+}
+
+// the single message of an enveloped request body
+fn read_envelope(body: &[u8]) -> Result<&[u8], ConnectError> {
+    let invalid = || ConnectError::new(Code::InvalidArgument, "malformed message envelope");
+    if body.len() < 5 {
+        return Err(invalid());
+    }
+    if body[0] & FLAG_COMPRESSED != 0 {
+        return Err(ConnectError::new(
+            Code::Unimplemented,
+            "compressed messages are not supported",
+        ));
+    }
+    let length = u32::from_be_bytes([body[1], body[2], body[3], body[4]]) as usize;
+    body.get(5..5 + length).ok_or_else(invalid)
+}
+
+fn envelope(flags: u8, payload: &[u8]) -> Bytes {
+    let mut frame = BytesMut::with_capacity(5 + payload.len());
+    frame.put_u8(flags);
+    frame.put_u32(payload.len() as u32);
+    frame.put_slice(payload);
+    frame.freeze()
+}
+
+// the end-of-stream message is always JSON, whatever the codec
+fn end_stream(error: Option<ConnectError>) -> Bytes {
+    let end = match error {
+        Some(e) => json!({ "error": e.to_json() }),
+        None => json!({}),
+    };
+    envelope(FLAG_END_STREAM, end.to_string().as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+    use reqwest::StatusCode as HttpStatus;
+
+    #[test]
+    fn envelopes_round_trip() {
+        let frame = envelope(0, b"{\"id\":\"1\"}");
+
+        assert_eq!(&frame[..5], [0, 0, 0, 0, 10]);
+        assert_eq!(read_envelope(&frame).unwrap(), b"{\"id\":\"1\"}");
+    }
+
+    #[test]
+    fn malformed_envelopes_are_rejected() {
+        // declares 10 bytes but carries 2
+        let truncated = [0, 0, 0, 0, 10, b'{', b'}'];
+        let compressed = envelope(FLAG_COMPRESSED, b"{}");
+
+        assert_eq!(
+            read_envelope(&truncated).unwrap_err().code,
+            Code::InvalidArgument
+        );
+        assert_eq!(
+            read_envelope(&[0, 0]).unwrap_err().code,
+            Code::InvalidArgument
+        );
+        assert_eq!(
+            read_envelope(&compressed).unwrap_err().code,
+            Code::Unimplemented
+        );
+    }
+
+    #[test]
+    fn the_end_of_stream_carries_the_error() {
+        let end = end_stream(Some(ConnectError::new(Code::NotFound, "missing")));
+
+        assert_eq!(end[0], FLAG_END_STREAM);
+        let message: Value = serde_json::from_slice(&end[5..]).unwrap();
+        assert_eq!(
+            message,
+            json!({ "error": { "code": "not_found", "message": "missing" } })
+        );
+        assert_eq!(&end_stream(None)[5..], b"{}");
+    }
+
+    #[test]
+    fn backend_codes_win_over_the_http_status() {
+        let response = Response {
+            message: "no such user".into(),
+            status_code: HttpStatus::BAD_REQUEST,
+            grpc_code: Some(Code::NotFound),
+            ..Default::default()
+        };
+
+        let error = ConnectError::from_response(&response);
+
+        assert_eq!(error.code, Code::NotFound);
+        assert_eq!(http_status(error.code), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn gateway_errors_map_their_http_status() {
+        let limited = Response {
+            message: "rate limit exceeded".into(),
+            status_code: HttpStatus::TOO_MANY_REQUESTS,
+            ..Default::default()
+        };
+
+        let error = ConnectError::from_response(&limited);
+
+        assert_eq!(error.code, Code::ResourceExhausted);
+        assert_eq!(code_for_http(503), Code::Unavailable);
+        assert_eq!(code_for_http(418), Code::Unknown);
+    }
+
+    #[test]
+    fn violations_are_listed_in_the_message() {
+        let response = Response {
+            message: "request validation failed".into(),
+            status_code: HttpStatus::BAD_REQUEST,
+            data: Some(json!({
+                "violations": [
+                    { "path": "id", "message": "is required" },
+                    { "path": "age", "message": "must be positive" },
+                ]
+            })),
+            ..Default::default()
+        };
+
+        let error = ConnectError::from_response(&response);
+
+        assert_eq!(error.code, Code::InvalidArgument);
+        assert_eq!(
+            error.message,
+            "request validation failed: id: is required; age: must be positive"
+        );
+    }
+
+    #[test]
+    fn stream_errors_keep_the_backend_status() {
+        let status = anyhow::Error::new(tonic::Status::permission_denied("nope"));
+        let other = anyhow::anyhow!("connection reset");
+
+        let error = ConnectError::from_stream_error(&status);
+
+        assert_eq!(error.code, Code::PermissionDenied);
+        assert_eq!(error.message, "nope");
+        assert_eq!(ConnectError::from_stream_error(&other).code, Code::Unknown);
+    }
+
+    #[test]
+    fn request_headers_are_checked() {
+        let timed = TestRequest::default()
+            .insert_header((TIMEOUT_HEADER, "250"))
+            .to_http_request();
+        let too_long = TestRequest::default()
+            .insert_header((TIMEOUT_HEADER, "12345678901"))
+            .to_http_request();
+        let version = TestRequest::default()
+            .insert_header((PROTOCOL_VERSION_HEADER, "2"))
+            .to_http_request();
+
+        assert_eq!(timeout(&timed).unwrap(), Some(Duration::from_millis(250)));
+        assert!(timeout(&too_long).is_err());
+        assert!(check_version(&version).is_err());
+        assert!(check_identity(Some("identity")).is_ok());
+        assert_eq!(
+            check_identity(Some("gzip")).unwrap_err().code,
+            Code::Unimplemented
+        );
+    }
+}
@@ -0,0 +1,236 @@
+use prost::Message;
+use prost_reflect::{DynamicMessage, MessageDescriptor};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::gateway::json_format::JsonFormat;
+
+/// Wire format of an HTTP request or response body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PayloadEncoding {
+    Json,
+    /// the message's binary protobuf encoding
+    Protobuf,
+    /// the protobuf text format
+    ProtobufText,
+    MessagePack,
+}
+
+impl PayloadEncoding {
+    /// Encoding named by a `Content-Type` value, `None` when unsupported.
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        let media_type = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_lowercase();
+        match media_type.as_str() {
+            "" | "application/json" => Some(Self::Json),
+            "application/x-protobuf" | "application/protobuf" | "application/octet-stream" => {
+                Some(Self::Protobuf)
+            }
+            "application/x-protobuf-text" | "text/x-protobuf" => Some(Self::ProtobufText),
+            "application/msgpack" | "application/x-msgpack" | "application/vnd.msgpack" => {
+                Some(Self::MessagePack)
+            }
+            _ => None,
+        }
+    }
+
+    /// First supported encoding listed in an `Accept` value, or `fallback`
+    /// for wildcards and unsupported types.
+    pub fn from_accept(accept: &str, fallback: Self) -> Self {
+        accept
+            .split(',')
+            .filter_map(|media_type| {
+                let media_type = media_type.split(';').next().unwrap_or_default().trim();
+                if media_type.is_empty() || media_type == "*/*" {
+                    None
+                } else {
+                    Self::from_content_type(media_type)
+                }
+            })
+            .next()
+            .unwrap_or(fallback)
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Json => "application/json",
+            Self::Protobuf => "application/x-protobuf",
+            Self::ProtobufText => "application/x-protobuf-text",
+            Self::MessagePack => "application/msgpack",
+        }
+    }
+
+    /// Whether the body is the bare message instead of the gateway's JSON
+    /// envelope.
+    pub fn is_protobuf(&self) -> bool {
+        matches!(self, Self::Protobuf | Self::ProtobufText)
+    }
+
+    /// Decodes a request body into the JSON payload the gateway works with.
+    /// Protobuf encodings need the method's input message.
+    pub fn decode(&self, body: &[u8], input: Option<&MessageDescriptor>) -> Result<Value, String> {
+        match self {
+            Self::Json => serde_json::from_slice(body).map_err(|e| e.to_string()),
+            Self::MessagePack => rmp_serde::from_slice(body).map_err(|e| e.to_string()),
+            Self::Protobuf | Self::ProtobufText => {
+                let input = input.ok_or("unknown method")?;
+                let message = if *self == Self::Protobuf {
+                    DynamicMessage::decode(input.clone(), body).map_err(|e| e.to_string())?
+                } else {
+                    let text = std::str::from_utf8(body).map_err(|e| e.to_string())?;
+                    DynamicMessage::parse_text_format(input.clone(), text)
+                        .map_err(|e| e.to_string())?
+                };
+                JsonFormat::default()
+                    .to_json(&message)
+                    .map_err(|e| e.to_string())
+            }
+        }
+    }
+
+    /// Encodes a gateway envelope, for the JSON-like encodings.
+    pub fn encode_envelope<T: Serialize>(&self, envelope: &T) -> Result<Vec<u8>, String> {
+        match self {
+            Self::MessagePack => rmp_serde::to_vec_named(envelope).map_err(|e| e.to_string()),
+            _ => serde_json::to_vec(envelope).map_err(|e| e.to_string()),
+        }
+    }
+
+    /// Encodes response data as the output message, for the protobuf
+    /// encodings.
+    pub fn encode_message(
+        &self,
+        data: &Value,
+        output: &MessageDescriptor,
+    ) -> Result<Vec<u8>, String> {
+        let mut message = DynamicMessage::new(output.clone());
+        JsonFormat {
+            ignore_unknown_fields: true,
+            ..Default::default()
+        }
+        .from_json(data, &mut message)
+        .map_err(|e| e.to_string())?;
+
+        match self {
+            Self::ProtobufText => Ok(message.to_text_format().into_bytes()),
+            _ => Ok(message.encode_to_vec()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_descriptors;
+    use serde_json::json;
+
+    const USERS_PROTO: &str = r#"
+        name: "users.proto" package: "users" syntax: "proto3"
+        message_type {
+            name: "User"
+            field { name: "id" number: 1 type: TYPE_STRING label: LABEL_OPTIONAL json_name: "id" }
+            field { name: "age" number: 3 type: TYPE_INT32 label: LABEL_OPTIONAL json_name: "age" }
+        }
+    "#;
+
+    fn user() -> MessageDescriptor {
+        test_descriptors::pool(&[USERS_PROTO])
+            .get_message_by_name("users.User")
+            .unwrap()
+    }
+
+    #[test]
+    fn content_types_name_encodings() {
+        assert_eq!(
+            PayloadEncoding::from_content_type(""),
+            Some(PayloadEncoding::Json)
+        );
+        assert_eq!(
+            PayloadEncoding::from_content_type("Application/Protobuf; proto=users.User"),
+            Some(PayloadEncoding::Protobuf)
+        );
+        assert_eq!(
+            PayloadEncoding::from_content_type("text/x-protobuf"),
+            Some(PayloadEncoding::ProtobufText)
+        );
+        assert_eq!(
+            PayloadEncoding::from_content_type("application/vnd.msgpack"),
+            Some(PayloadEncoding::MessagePack)
+        );
+        assert_eq!(PayloadEncoding::from_content_type("text/html"), None);
+    }
+
+    #[test]
+    fn accept_picks_the_first_supported_encoding() {
+        let fallback = PayloadEncoding::Json;
+
+        assert_eq!(
+            PayloadEncoding::from_accept("text/html, application/msgpack;q=0.9", fallback),
+            PayloadEncoding::MessagePack
+        );
+        assert_eq!(
+            PayloadEncoding::from_accept("*/*", PayloadEncoding::Protobuf),
+            PayloadEncoding::Protobuf
+        );
+        assert_eq!(PayloadEncoding::from_accept("", fallback), fallback);
+    }
+
+    #[test]
+    fn protobuf_encodings_round_trip_through_json() {
+        let data = json!({ "id": "7", "age": 36 });
+
+        for encoding in [PayloadEncoding::Protobuf, PayloadEncoding::ProtobufText] {
+            let body = encoding.encode_message(&data, &user()).unwrap();
+
+            assert_eq!(encoding.decode(&body, Some(&user())).unwrap(), data);
+        }
+        assert_eq!(
+            PayloadEncoding::Protobuf
+                .encode_message(&data, &user())
+                .unwrap(),
+            [0x0a, 0x01, b'7', 0x18, 0x24]
+        );
+    }
+
+    #[test]
+    fn response_fields_missing_from_the_message_are_dropped() {
+        let data = json!({ "id": "7", "nickname": "ada" });
+
+        let body = PayloadEncoding::Protobuf
+            .encode_message(&data, &user())
+            .unwrap();
+
+        assert_eq!(body, [0x0a, 0x01, b'7']);
+    }
+
+    #[test]
+    fn protobuf_bodies_need_the_input_message() {
+        assert_eq!(
+            PayloadEncoding::Protobuf.decode(&[0x0a, 0x01, b'7'], None),
+            Err("unknown method".to_string())
+        );
+        assert!(
+            PayloadEncoding::Protobuf
+                .decode(&[0x0a, 0x05], Some(&user()))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn message_pack_envelopes_decode_to_the_same_json() {
+        let envelope = json!({ "service": "users.Users", "data": { "id": "7" } });
+
+        let body = PayloadEncoding::MessagePack
+            .encode_envelope(&envelope)
+            .unwrap();
+
+        assert_eq!(
+            PayloadEncoding::MessagePack.decode(&body, None).unwrap(),
+            envelope
+        );
+    }
+}
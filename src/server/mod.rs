@@ -0,0 +1,4 @@
+pub mod connect;
+pub mod encoding;
+pub mod proxies;
+pub mod routes;
@@ -0,0 +1,155 @@
+use std::collections::HashSet;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::RwLock;
+
+use actix_web::HttpRequest;
+
+/// Reverse proxies whose `X-Forwarded-For` and `Forwarded` headers are
+/// believed. Calls from anyone else are attributed to the connection's peer
+/// address, so clients cannot pick the IP they are limited and logged by.
+#[derive(Debug, Default)]
+pub struct TrustedProxies {
+    proxies: RwLock<HashSet<IpAddr>>,
+}
+
+impl TrustedProxies {
+    pub fn trust(&self, proxy: IpAddr) {
+        if let Ok(mut proxies) = self.proxies.write() {
+            proxies.insert(proxy);
+        }
+    }
+
+    pub fn distrust(&self, proxy: IpAddr) {
+        if let Ok(mut proxies) = self.proxies.write() {
+            proxies.remove(&proxy);
+        }
+    }
+
+    pub fn is_trusted(&self, ip: &IpAddr) -> bool {
+        self.proxies
+            .read()
+            .is_ok_and(|proxies| proxies.contains(ip))
+    }
+
+    /// The IP of the client behind the call: the peer address, or when the
+    /// peer is a trusted proxy, the last forwarded address that is not one.
+    pub fn client_ip(&self, http_req: &HttpRequest) -> Option<IpAddr> {
+        let peer = http_req.peer_addr()?.ip();
+        if !self.is_trusted(&peer) {
+            return Some(peer);
+        }
+        let forwarded = forwarded_for(http_req);
+        // each proxy appends the address it received the call from, so the
+        // list is read from the end, skipping the proxies we trust
+        let client = forwarded
+            .iter()
+            .rev()
+            .find(|ip| !self.is_trusted(ip))
+            .or(forwarded.first())
+            .copied();
+        Some(client.unwrap_or(peer))
+    }
+}
+
+// addresses listed by `Forwarded: for=...`, or by `X-Forwarded-For` when the
+// call has no `Forwarded` header
+fn forwarded_for(http_req: &HttpRequest) -> Vec<IpAddr> {
+    let headers = http_req.headers();
+    let forwarded: Vec<&str> = headers
+        .get_all("forwarded")
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|element| {
+            element.split(';').find_map(|pair| {
+                let (name, value) = pair.trim().split_once('=')?;
+                name.eq_ignore_ascii_case("for").then_some(value)
+            })
+        })
+        .collect();
+    if !forwarded.is_empty() {
+        return forwarded.into_iter().filter_map(parse_node).collect();
+    }
+    headers
+        .get_all("x-forwarded-for")
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(parse_node)
+        .collect()
+}
+
+// `192.0.2.1`, `"[2001:db8::1]:4711"` or `192.0.2.1:4711`; obfuscated and
+// `unknown` nodes are skipped
+fn parse_node(node: &str) -> Option<IpAddr> {
+    let node = node.trim().trim_matches('"');
+    node.parse::<IpAddr>()
+        .ok()
+        .or_else(|| node.parse::<SocketAddr>().ok().map(|addr| addr.ip()))
+        .or_else(|| {
+            node.strip_prefix('[')?
+                .strip_suffix(']')?
+                .parse::<IpAddr>()
+                .ok()
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    const PROXY: &str = "10.0.0.1:443";
+
+    fn trusted() -> TrustedProxies {
+        let proxies = TrustedProxies::default();
+        proxies.trust("10.0.0.1".parse().unwrap());
+        proxies.trust("10.0.0.2".parse().unwrap());
+        proxies
+    }
+
+    fn ip(value: &str) -> Option<IpAddr> {
+        Some(value.parse().unwrap())
+    }
+
+    #[test]
+    fn forwarded_headers_from_untrusted_peers_are_ignored() {
+        let req = TestRequest::default()
+            .peer_addr("203.0.113.9:5000".parse().unwrap())
+            .insert_header(("x-forwarded-for", "198.51.100.1"))
+            .to_http_request();
+
+        assert_eq!(trusted().client_ip(&req), ip("203.0.113.9"));
+    }
+
+    #[test]
+    fn trusted_proxies_are_skipped_from_the_end() {
+        let req = TestRequest::default()
+            .peer_addr(PROXY.parse().unwrap())
+            .insert_header(("x-forwarded-for", "198.51.100.1, 192.0.2.7, 10.0.0.2"))
+            .to_http_request();
+
+        // the first entry was set by the client and is not believed
+        assert_eq!(trusted().client_ip(&req), ip("192.0.2.7"));
+    }
+
+    #[test]
+    fn reads_the_forwarded_header() {
+        let req = TestRequest::default()
+            .peer_addr(PROXY.parse().unwrap())
+            .insert_header((
+                "forwarded",
+                r#"for="[2001:db8::1]:4711";proto=https, for=10.0.0.2"#,
+            ))
+            .to_http_request();
+
+        assert_eq!(trusted().client_ip(&req), ip("2001:db8::1"));
+    }
+
+    #[test]
+    fn a_trusted_peer_without_forwarded_headers_is_the_client() {
+        let req = TestRequest::default()
+            .peer_addr(PROXY.parse().unwrap())
+            .to_http_request();
+
+        assert_eq!(trusted().client_ip(&req), ip("10.0.0.1"));
+    }
+}
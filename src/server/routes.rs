@@ -0,0 +1,377 @@
+use std::net::IpAddr;
+use std::time::Instant;
+
+use actix_web::http::{StatusCode, header};
+use actix_web::{HttpRequest, HttpResponse, web};
+use futures::StreamExt;
+use prost_reflect::MessageDescriptor;
+
+use crate::Gateway;
+use crate::batch::executor::{self, BatchRequest};
+use crate::logging::access_log::AccessLogEntry;
+use crate::metrics::gateway_metrics;
+use crate::schema::discovery;
+use crate::server::connect;
+use crate::server::encoding::PayloadEncoding;
+use crate::utils::errors::ResponseErrors;
+use crate::utils::model::{RequestContext, RequestType};
+use crate::utils::request_id::REQUEST_ID_HEADER;
+use crate::utils::response_builder::ResponseBuilder;
+
+/// Registers the gateway's HTTP endpoints on an actix-web app. The app must
+/// provide the shared `Gateway` as `web::Data`.
+///
+/// ```rust,ignore
+/// let gateway = web::Data::new(Gateway::new());
+/// HttpServer::new(move || {
+///     App::new()
+///         .app_data(gateway.clone())
+///         .configure(grpc_gateway::server::routes::configure)
+/// })
+/// ```
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    // Connect calls share `/{service}/{method}` and are told apart by headers
+    cfg.configure(connect::configure);
+    cfg.route("/invoke", web::post().to(invoke))
+        .route("/_batch", web::post().to(batch))
+        .route("/graphql", web::post().to(graphql))
+        .route("/graphql/stream", web::post().to(graphql_stream))
+        .route("/graphql/schema", web::get().to(graphql_sdl))
+        .route("/metrics", web::get().to(metrics))
+        .route("/openapi.json", web::get().to(openapi))
+        .route("/_schema/services", web::get().to(schema_services))
+        .route("/_schema/services/{service}", web::get().to(schema_service))
+        .route("/_schema/messages/{message}", web::get().to(schema_message))
+        .route("/{service}/{method}", web::post().to(invoke_method));
+}
+
+async fn invoke(
+    http_req: HttpRequest,
+    gateway: web::Data<Gateway>,
+    body: web::Json<RequestType>,
+) -> HttpResponse {
+    respond(
+        &http_req,
+        &gateway,
+        body.into_inner(),
+        PayloadEncoding::Json,
+        None,
+        None,
+    )
+    .await
+}
+
+// `POST /_batch` with several calls, answered together
+async fn batch(
+    http_req: HttpRequest,
+    gateway: web::Data<Gateway>,
+    body: web::Json<BatchRequest>,
+) -> HttpResponse {
+    let ctx = request_context(&http_req);
+    match executor::execute(&gateway, body.into_inner(), ctx).await {
+        Ok(results) => HttpResponse::Ok().json(ResponseBuilder::success(
+            ResponseErrors::Success.to_string(),
+            results,
+        )),
+        Err(e) => HttpResponse::BadRequest().json(ResponseBuilder::<()>::bad_request(e)),
+    }
+}
+
+async fn graphql(
+    http_req: HttpRequest,
+    gateway: web::Data<Gateway>,
+    body: web::Json<async_graphql::Request>,
+) -> HttpResponse {
+    let schema = match gateway.graphql_schema().await {
+        Ok(schema) => schema,
+        Err(e) => {
+            return HttpResponse::InternalServerError()
+                .json(ResponseBuilder::<()>::internal_error(e));
+        }
+    };
+    let request = body
+        .into_inner()
+        .data(gateway.into_inner())
+        .data(request_context(&http_req));
+    HttpResponse::Ok().json(schema.execute(request).await)
+}
+
+// subscriptions, streamed as server-sent events with one GraphQL response each
+async fn graphql_stream(
+    http_req: HttpRequest,
+    gateway: web::Data<Gateway>,
+    body: web::Json<async_graphql::Request>,
+) -> HttpResponse {
+    let schema = match gateway.graphql_schema().await {
+        Ok(schema) => schema,
+        Err(e) => {
+            return HttpResponse::InternalServerError()
+                .json(ResponseBuilder::<()>::internal_error(e));
+        }
+    };
+    let request = body
+        .into_inner()
+        .data(gateway.into_inner())
+        .data(request_context(&http_req));
+    let events = schema.execute_stream(request).map(|response| {
+        let payload = serde_json::to_string(&response).unwrap_or_default();
+        Ok::<_, std::convert::Infallible>(web::Bytes::from(format!("data: {}\n\n", payload)))
+    });
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        .streaming(events)
+}
+
+async fn graphql_sdl(gateway: web::Data<Gateway>) -> HttpResponse {
+    match gateway.graphql_schema().await {
+        Ok(schema) => HttpResponse::Ok()
+            .content_type("text/plain")
+            .body(schema.sdl()),
+        Err(e) => {
+            HttpResponse::InternalServerError().json(ResponseBuilder::<()>::internal_error(e))
+        }
+    }
+}
+
+// `POST /{service}/{method}` with the request message as the body, encoded
+// as named by `Content-Type`
+async fn invoke_method(
+    http_req: HttpRequest,
+    gateway: web::Data<Gateway>,
+    path: web::Path<(String, String)>,
+    body: web::Bytes,
+) -> HttpResponse {
+    let (service, method) = path.into_inner();
+    let header = |name| {
+        http_req
+            .headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+    };
+
+    let content_type = header(header::CONTENT_TYPE);
+    let Some(encoding) = PayloadEncoding::from_content_type(content_type) else {
+        return HttpResponse::UnsupportedMediaType().json(ResponseBuilder::<()>::bad_request(
+            format!("unsupported content type {}", content_type),
+        ));
+    };
+    let accept = PayloadEncoding::from_accept(header(header::ACCEPT), encoding);
+
+    let descriptor = if encoding.is_protobuf() || accept.is_protobuf() {
+        gateway.method_descriptor(&service, &method).await
+    } else {
+        None
+    };
+    let input = descriptor.as_ref().map(|m| m.input());
+    let data = match encoding.decode(&body, input.as_ref()) {
+        Ok(data) => data,
+        Err(e) => {
+            return HttpResponse::BadRequest().json(ResponseBuilder::<()>::bad_request(format!(
+                "invalid request body: {}",
+                e
+            )));
+        }
+    };
+
+    // binary messages in both directions skip the JSON mapping
+    let protobuf_body = (encoding == PayloadEncoding::Protobuf
+        && accept == PayloadEncoding::Protobuf)
+        .then_some(body);
+
+    let req = RequestType {
+        service,
+        method,
+        data,
+    };
+    respond(
+        &http_req,
+        &gateway,
+        req,
+        accept,
+        descriptor.map(|m| m.output()),
+        protobuf_body,
+    )
+    .await
+}
+
+async fn respond(
+    http_req: &HttpRequest,
+    gateway: &Gateway,
+    req: RequestType,
+    accept: PayloadEncoding,
+    output: Option<MessageDescriptor>,
+    protobuf_body: Option<web::Bytes>,
+) -> HttpResponse {
+    let started_at = Instant::now();
+    let mut ctx = request_context(http_req);
+    ctx.protobuf_body = protobuf_body;
+    let client_ip = ctx.client_ip.clone();
+    let service = req.service.to_string();
+    let method = req.method.to_string();
+    let request_body = gateway
+        .access_log
+        .include_request_body
+        .then(|| req.data.clone());
+
+    let mut response = gateway.invoke_with_context(req, ctx).await;
+
+    // protobuf bodies carry the bare message, errors keep the JSON envelope
+    let (content_type, encoded) = match (&output, &response.data, &response.protobuf_body) {
+        (_, _, Some(body)) if response.status_code.is_success() => {
+            (accept.content_type(), Ok(body.to_vec()))
+        }
+        (Some(output), Some(data), _)
+            if accept.is_protobuf() && response.status_code.is_success() =>
+        {
+            (accept.content_type(), accept.encode_message(data, output))
+        }
+        _ if accept.is_protobuf() => (
+            PayloadEncoding::Json.content_type(),
+            PayloadEncoding::Json.encode_envelope(&response),
+        ),
+        _ => (accept.content_type(), accept.encode_envelope(&response)),
+    };
+    let (content_type, payload) = match encoded {
+        Ok(payload) => (content_type, payload),
+        Err(e) => {
+            tracing::warn!(error = %e, "failed to encode response");
+            response.status_code = reqwest::StatusCode::INTERNAL_SERVER_ERROR;
+            let error =
+                ResponseBuilder::<()>::internal_error(format!("failed to encode response: {}", e));
+            (
+                PayloadEncoding::Json.content_type(),
+                serde_json::to_vec(&error).unwrap_or_default(),
+            )
+        }
+    };
+
+    gateway.access_log.emit(AccessLogEntry {
+        request_id: response.request_id.clone(),
+        service,
+        method,
+        status: response.status_code.as_u16(),
+        latency_ms: started_at.elapsed().as_secs_f64() * 1000.0,
+        bytes: payload.len(),
+        client_ip,
+        request: request_body,
+    });
+
+    let mut builder = HttpResponse::build(
+        StatusCode::from_u16(response.status_code.as_u16())
+            .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+    );
+    if let Some(request_id) = &response.request_id {
+        builder.insert_header((REQUEST_ID_HEADER, request_id.as_str()));
+    }
+    for (name, value) in &response.headers {
+        builder.insert_header((name.as_str(), value.as_str()));
+    }
+    if response.status_code == reqwest::StatusCode::NOT_MODIFIED {
+        return builder.finish();
+    }
+    builder.content_type(content_type).body(payload)
+}
+
+/// Registers operational endpoints. Mount these on an internal listener or
+/// behind your own authentication, they are not protected by the gateway.
+pub fn configure_admin(cfg: &mut web::ServiceConfig) {
+    cfg.route("/_admin/cache", web::delete().to(purge_cache))
+        .route("/_admin/cache/{service}", web::delete().to(purge_cache))
+        .route(
+            "/_admin/cache/{service}/{method}",
+            web::delete().to(purge_cache),
+        );
+}
+
+async fn purge_cache(http_req: HttpRequest, gateway: web::Data<Gateway>) -> HttpResponse {
+    let purged = gateway.response_cache.purge(
+        http_req.match_info().get("service"),
+        http_req.match_info().get("method"),
+    );
+    HttpResponse::Ok().json(ResponseBuilder::success(
+        format!("{} cached responses purged", purged),
+        purged,
+    ))
+}
+
+pub(crate) fn request_context(http_req: &HttpRequest) -> RequestContext {
+    let headers = http_req
+        .headers()
+        .iter()
+        .filter_map(|(name, value)| {
+            value
+                .to_str()
+                .ok()
+                .map(|v| (name.as_str().to_lowercase(), v.to_string()))
+        })
+        .collect();
+
+    RequestContext {
+        headers,
+        client_ip: client_ip(http_req).map(|ip| ip.to_string()),
+        query: url::form_urlencoded::parse(http_req.query_string().as_bytes())
+            .into_owned()
+            .collect(),
+        protobuf_body: None,
+    }
+}
+
+// forwarded headers are only believed from the gateway's trusted proxies
+fn client_ip(http_req: &HttpRequest) -> Option<IpAddr> {
+    match http_req.app_data::<web::Data<Gateway>>() {
+        Some(gateway) => gateway.trusted_proxies.client_ip(http_req),
+        None => http_req.peer_addr().map(|addr| addr.ip()),
+    }
+}
+
+async fn openapi(gateway: web::Data<Gateway>) -> HttpResponse {
+    HttpResponse::Ok().json(gateway.openapi_document().await.as_ref())
+}
+
+async fn schema_services(gateway: web::Data<Gateway>) -> HttpResponse {
+    let services = gateway.discovered_services().await;
+    HttpResponse::Ok().json(ResponseBuilder::success(
+        ResponseErrors::Success.to_string(),
+        discovery::service_list(&services),
+    ))
+}
+
+async fn schema_service(gateway: web::Data<Gateway>, path: web::Path<String>) -> HttpResponse {
+    let name = path.into_inner();
+    let services = gateway.discovered_services().await;
+    match services.iter().find(|s| s.full_name() == name) {
+        Some(service) => HttpResponse::Ok().json(ResponseBuilder::success(
+            ResponseErrors::Success.to_string(),
+            discovery::service_detail(service),
+        )),
+        None => HttpResponse::NotFound().json(ResponseBuilder::<()>::bad_request(format!(
+            "service {} not found",
+            name
+        ))),
+    }
+}
+
+async fn schema_message(gateway: web::Data<Gateway>, path: web::Path<String>) -> HttpResponse {
+    let name = path.into_inner();
+    match gateway.find_message(&name).await {
+        Some(message) => HttpResponse::Ok().json(ResponseBuilder::success(
+            ResponseErrors::Success.to_string(),
+            discovery::message_detail(&message),
+        )),
+        None => HttpResponse::NotFound().json(ResponseBuilder::<()>::bad_request(format!(
+            "message {} not found",
+            name
+        ))),
+    }
+}
+
+async fn metrics() -> HttpResponse {
+    match gateway_metrics::gather() {
+        Ok(body) => HttpResponse::Ok()
+            .content_type("text/plain; version=0.0.4")
+            .body(body),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
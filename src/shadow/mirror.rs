@@ -0,0 +1,277 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use lazy_static::lazy_static;
+use serde_json::Value;
+use tonic::metadata::{MetadataMap, MetadataValue};
+
+use crate::gateway::gateway::GrpcGateway;
+use crate::gateway::json_format::JsonFormat;
+use crate::metrics::gateway_metrics;
+use crate::recording::replay;
+use crate::registry::auth::AuthConfig;
+use crate::registry::model::ServiceConfig;
+
+/// Metadata marking calls sent to a shadow backend.
+pub const SHADOW_HEADER: &str = "x-gateway-shadow";
+
+lazy_static! {
+    // shadow backends get their own clients, apart from the primary ones
+    static ref shadow_client_map: Mutex<HashMap<String, GrpcGateway>> =
+        Mutex::new(HashMap::new());
+}
+
+/// Secondary backend receiving a copy of a service's calls.
+#[derive(Debug, Clone)]
+pub struct ShadowConfig {
+    pub endpoint: String,
+    /// share of calls mirrored, from 0 to 1
+    pub sample_rate: f64,
+    /// sends the primary backend's credentials along with the copy, off by
+    /// default so a shadow only gets credentials it was explicitly trusted
+    /// with
+    pub forward_credentials: bool,
+    /// JSON pointers left out of the response comparison, such as
+    /// timestamps or generated ids
+    pub ignored_fields: Vec<String>,
+    /// shadow calls taking longer are abandoned and counted as errors
+    pub timeout: Duration,
+    /// shadow calls running at once, sampled calls beyond it are skipped
+    pub max_in_flight: usize,
+}
+
+impl ShadowConfig {
+    pub fn new(endpoint: &str) -> Self {
+        Self {
+            endpoint: endpoint.to_string(),
+            sample_rate: 1.0,
+            forward_credentials: false,
+            ignored_fields: Vec::new(),
+            timeout: Duration::from_secs(5),
+            max_in_flight: 64,
+        }
+    }
+}
+
+/// Services whose calls are mirrored to a shadow backend.
+#[derive(Debug, Default)]
+pub struct ShadowTraffic {
+    services: RwLock<HashMap<String, ShadowConfig>>,
+    // shadow calls running per service
+    in_flight: RwLock<HashMap<String, Arc<AtomicUsize>>>,
+}
+
+impl ShadowTraffic {
+    pub fn enable(&self, service: &str, config: ShadowConfig) {
+        if let Ok(mut services) = self.services.write() {
+            services.insert(service.to_string(), config);
+        }
+        if let Ok(mut in_flight) = self.in_flight.write() {
+            in_flight.entry(service.to_string()).or_default();
+        }
+    }
+
+    pub fn disable(&self, service: &str) {
+        if let Ok(mut services) = self.services.write() {
+            services.remove(service);
+        }
+    }
+
+    pub fn config_for(&self, service: &str) -> Option<ShadowConfig> {
+        self.services
+            .read()
+            .ok()
+            .and_then(|services| services.get(service).cloned())
+    }
+
+    /// The service's shadow configuration when this call is sampled.
+    pub(crate) fn sample(&self, service: &str) -> Option<ShadowConfig> {
+        self.config_for(service)
+            .filter(|config| config.sample_rate > 0.0 && rand::random::<f64>() < config.sample_rate)
+    }
+
+    /// Sends the call to the shadow backend in the background, or counts it
+    /// as skipped when the service already has `max_in_flight` shadow calls
+    /// running.
+    pub(crate) fn mirror(&self, config: ShadowConfig, call: ShadowCall, primary: PrimaryOutcome) {
+        let counter = self
+            .in_flight
+            .read()
+            .ok()
+            .and_then(|in_flight| in_flight.get(&call.service).cloned());
+        let Some(counter) = counter else {
+            return;
+        };
+        let Some(slot) = InFlight::acquire(counter, config.max_in_flight) else {
+            tracing::debug!(
+                service = %call.service,
+                method = %call.method,
+                "shadow call skipped, too many in flight"
+            );
+            gateway_metrics::record_shadow(&call.service, &call.method, "skipped");
+            return;
+        };
+        spawn(config, call, primary, slot);
+    }
+}
+
+// a running shadow call, released when dropped
+struct InFlight(Arc<AtomicUsize>);
+
+impl InFlight {
+    fn acquire(counter: Arc<AtomicUsize>, max: usize) -> Option<Self> {
+        counter
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |running| {
+                (running < max).then_some(running + 1)
+            })
+            .ok()?;
+        Some(Self(counter))
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// A call sent to the primary backend, to be repeated against the shadow.
+pub(crate) struct ShadowCall {
+    pub service: String,
+    pub method: String,
+    pub data: Value,
+    pub metadata: MetadataMap,
+    pub format: JsonFormat,
+    pub auth_config: Option<AuthConfig>,
+}
+
+/// What the primary backend answered.
+pub(crate) struct PrimaryOutcome {
+    pub grpc_code: String,
+    pub response: Option<Value>,
+    pub elapsed: Duration,
+}
+
+// Sends the call to the shadow backend in the background and records how
+// its answer compares with the primary's. The caller never waits on it
+fn spawn(config: ShadowConfig, call: ShadowCall, primary: PrimaryOutcome, slot: InFlight) {
+    tokio::spawn(async move {
+        let _slot = slot;
+        let started_at = Instant::now();
+        let result = tokio::time::timeout(config.timeout, invoke(&config, &call)).await;
+        let elapsed = started_at.elapsed();
+
+        let outcome = match result {
+            Ok(Ok(response)) => Ok((format!("{:?}", tonic::Code::Ok), Some(response))),
+            Ok(Err(e)) => match e.downcast_ref::<tonic::Status>() {
+                Some(status) => Ok((format!("{:?}", status.code()), None)),
+                None => Err(e.to_string()),
+            },
+            Err(_) => Err(String::from("shadow call timed out")),
+        };
+        let result = match outcome {
+            Err(error) => {
+                tracing::debug!(
+                    service = %call.service,
+                    method = %call.method,
+                    error = %error,
+                    "shadow call failed"
+                );
+                "error"
+            }
+            Ok((grpc_code, _)) if grpc_code != primary.grpc_code => {
+                tracing::info!(
+                    service = %call.service,
+                    method = %call.method,
+                    primary = %primary.grpc_code,
+                    shadow = %grpc_code,
+                    "shadow status differs"
+                );
+                "status_mismatch"
+            }
+            Ok((_, response)) => {
+                let differences: Vec<String> = replay::diff(
+                    primary.response.as_ref().unwrap_or(&Value::Null),
+                    response.as_ref().unwrap_or(&Value::Null),
+                )
+                .into_iter()
+                .map(|difference| difference.path)
+                .filter(|path| !is_ignored(path, &config.ignored_fields))
+                .collect();
+                if differences.is_empty() {
+                    "match"
+                } else {
+                    tracing::info!(
+                        service = %call.service,
+                        method = %call.method,
+                        fields = ?differences,
+                        "shadow response differs"
+                    );
+                    "body_mismatch"
+                }
+            }
+        };
+
+        gateway_metrics::record_shadow(&call.service, &call.method, result);
+        if result != "error" {
+            gateway_metrics::observe_shadow_latency(
+                &call.service,
+                &call.method,
+                elapsed,
+                primary.elapsed,
+            );
+        }
+    });
+}
+
+async fn invoke(config: &ShadowConfig, call: &ShadowCall) -> anyhow::Result<Value> {
+    let client = client(&config.endpoint).await?;
+    let mut metadata = call.metadata.clone();
+    metadata.insert(SHADOW_HEADER, MetadataValue::from_static("true"));
+    let service_config = ServiceConfig {
+        endpoint: config.endpoint.to_string(),
+        service_name: call.service.to_string(),
+        auth_config: call
+            .auth_config
+            .clone()
+            .filter(|_| config.forward_credentials),
+        breaker: None,
+    };
+    client
+        .invoke(
+            &call.service,
+            &call.method,
+            call.data.clone(),
+            service_config,
+            metadata,
+            &call.format,
+        )
+        .await
+}
+
+async fn client(endpoint: &str) -> anyhow::Result<GrpcGateway> {
+    let cached = match shadow_client_map.lock() {
+        Ok(mp) => mp.get(endpoint).cloned(),
+        Err(_) => None,
+    };
+    if let Some(client) = cached {
+        return Ok(client);
+    }
+    let client = GrpcGateway::new(endpoint).await?;
+    if let Ok(mut mp) = shadow_client_map.lock() {
+        mp.insert(endpoint.to_string(), client.clone());
+    }
+    Ok(client)
+}
+
+// a field is ignored along with everything below it
+fn is_ignored(path: &str, ignored: &[String]) -> bool {
+    ignored.iter().any(|ignored| {
+        path == ignored
+            || path
+                .strip_prefix(ignored.as_str())
+                .is_some_and(|rest| rest.starts_with('/'))
+    })
+}
@@ -0,0 +1,58 @@
+use anyhow::Result;
+use opentelemetry::global;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::Resource;
+#[cfg(feature = "testing")]
+use opentelemetry_sdk::trace::InMemorySpanExporter;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+
+#[derive(Debug, Clone)]
+pub struct TelemetryConfig {
+    pub service_name: String,
+    /// OTLP/gRPC collector endpoint
+    pub otlp_endpoint: String,
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            service_name: String::from("grpc_gateway"),
+            otlp_endpoint: String::from("http://localhost:4317"),
+        }
+    }
+}
+
+/// Exports gateway spans to an OTLP collector and installs the provider
+/// globally. Keep the returned provider alive and call `shutdown` on exit to
+/// flush pending spans.
+pub fn init_otlp(config: &TelemetryConfig) -> Result<SdkTracerProvider> {
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_tonic()
+        .with_endpoint(config.otlp_endpoint.to_string())
+        .build()?;
+
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(resource(&config.service_name))
+        .build();
+    global::set_tracer_provider(provider.clone());
+    Ok(provider)
+}
+
+/// Collects gateway spans in memory, useful to assert on propagation locally.
+#[cfg(feature = "testing")]
+pub fn init_in_memory(service_name: &str) -> (SdkTracerProvider, InMemorySpanExporter) {
+    let exporter = InMemorySpanExporter::default();
+    let provider = SdkTracerProvider::builder()
+        .with_simple_exporter(exporter.clone())
+        .with_resource(resource(service_name))
+        .build();
+    global::set_tracer_provider(provider.clone());
+    (provider, exporter)
+}
+
+fn resource(service_name: &str) -> Resource {
+    Resource::builder()
+        .with_service_name(service_name.to_string())
+        .build()
+}
@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use opentelemetry::Context;
+use opentelemetry::propagation::{Extractor, Injector, TextMapPropagator};
+use opentelemetry::trace::{SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState};
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use tonic::metadata::{MetadataKey, MetadataMap, MetadataValue};
+
+// B3 headers, see https://github.com/openzipkin/b3-propagation
+const B3_SINGLE_HEADER: &str = "b3";
+const B3_TRACE_ID_HEADER: &str = "x-b3-traceid";
+const B3_SPAN_ID_HEADER: &str = "x-b3-spanid";
+const B3_SAMPLED_HEADER: &str = "x-b3-sampled";
+const B3_FLAGS_HEADER: &str = "x-b3-flags";
+
+/// Reads trace headers from incoming HTTP headers keyed by lowercase name.
+pub struct HeaderExtractor<'a>(pub &'a HashMap<String, String>);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(&key.to_lowercase()).map(|v| v.as_str())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|k| k.as_str()).collect()
+    }
+}
+
+/// Writes trace headers into outgoing gRPC metadata.
+pub struct MetadataInjector<'a>(pub &'a mut MetadataMap);
+
+impl Injector for MetadataInjector<'_> {
+    fn set(&mut self, key: &str, value: String) {
+        if let (Ok(key), Ok(value)) = (
+            MetadataKey::from_bytes(key.as_bytes()),
+            MetadataValue::from_str(&value),
+        ) {
+            self.0.insert(key, value);
+        }
+    }
+}
+
+/// Builds the parent context from W3C `traceparent`/`tracestate` headers,
+/// falling back to B3 single or multi header propagation.
+pub fn extract_context(headers: &HashMap<String, String>) -> Context {
+    let extractor = HeaderExtractor(headers);
+    let context = TraceContextPropagator::new().extract(&extractor);
+    if context.span().span_context().is_valid() {
+        return context;
+    }
+
+    match extract_b3(&extractor) {
+        Some(span_context) => Context::new().with_remote_span_context(span_context),
+        None => context,
+    }
+}
+
+/// Injects the W3C trace context of `context` into the outgoing metadata.
+pub fn inject_context(context: &Context, metadata: &mut MetadataMap) {
+    TraceContextPropagator::new().inject_context(context, &mut MetadataInjector(metadata));
+}
+
+fn extract_b3(extractor: &HeaderExtractor<'_>) -> Option<SpanContext> {
+    if let Some(single) = extractor.get(B3_SINGLE_HEADER) {
+        let mut parts = single.split('-');
+        let trace_id = parts.next()?;
+        let span_id = parts.next()?;
+        let sampled = parts.next();
+        return b3_span_context(trace_id, span_id, sampled);
+    }
+
+    let trace_id = extractor.get(B3_TRACE_ID_HEADER)?;
+    let span_id = extractor.get(B3_SPAN_ID_HEADER)?;
+    let sampled = match extractor.get(B3_FLAGS_HEADER) {
+        Some("1") => Some("d"),
+        _ => extractor.get(B3_SAMPLED_HEADER),
+    };
+    b3_span_context(trace_id, span_id, sampled)
+}
+
+fn b3_span_context(trace_id: &str, span_id: &str, sampled: Option<&str>) -> Option<SpanContext> {
+    // 64-bit B3 trace ids are left padded to the 128-bit W3C form
+    let trace_id = match trace_id.len() {
+        16 => TraceId::from_hex(&format!("{:0>32}", trace_id)).ok()?,
+        32 => TraceId::from_hex(trace_id).ok()?,
+        _ => return None,
+    };
+    let span_id = SpanId::from_hex(span_id).ok()?;
+    let flags = match sampled {
+        Some("1") | Some("d") | Some("true") => TraceFlags::SAMPLED,
+        _ => TraceFlags::default(),
+    };
+
+    let span_context = SpanContext::new(trace_id, span_id, flags, true, TraceState::default());
+    span_context.is_valid().then_some(span_context)
+}
@@ -0,0 +1,423 @@
+use std::collections::{HashMap, VecDeque};
+use std::convert::Infallible;
+use std::error::Error;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use bytes::Bytes;
+use futures::StreamExt;
+use futures::stream::BoxStream;
+use prost::Message;
+use prost_reflect::{DescriptorPool, DynamicMessage, MethodDescriptor};
+use prost_types::FileDescriptorSet;
+use serde_json::Value;
+use tokio::task::JoinHandle;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::body::Body;
+use tonic::codegen::{BoxFuture, Service, http};
+use tonic::server::{Grpc, ServerStreamingService, UnaryService};
+use tonic::{Code, Status};
+
+use crate::Gateway;
+use crate::gateway::dynamic_grpc_client::BytesCodec;
+use crate::gateway::json_format::JsonFormat;
+use crate::registry::model::{AuthRefreshConfig, AuthType, InternalAuthConfig};
+use crate::registry::service_registry::RegistryTrait;
+use crate::utils::model::ServiceRegisterRequest;
+
+/// API key header and value used by `MockServer::registration`.
+pub const MOCK_API_KEY_HEADER: &str = "x-api-key";
+pub const MOCK_API_KEY: &str = "mock-api-key";
+
+/// How the mock backend answers a call.
+#[derive(Debug, Clone)]
+pub enum MockResponse {
+    /// the output message, in protobuf JSON
+    Message(Value),
+    /// the messages of a server stream, in protobuf JSON
+    Stream(Vec<Value>),
+    Error(Code, String),
+}
+
+impl MockResponse {
+    pub fn error(code: Code, message: impl Into<String>) -> Self {
+        Self::Error(code, message.into())
+    }
+}
+
+/// A call received by the mock backend.
+#[derive(Debug, Clone)]
+pub struct MockRequest {
+    pub service: String,
+    pub method: String,
+    /// the request message, in protobuf JSON
+    pub message: Value,
+    /// the request message as received
+    pub body: Bytes,
+    /// request metadata keyed by lowercase name
+    pub metadata: HashMap<String, String>,
+}
+
+type Handler = Arc<dyn Fn(&MockRequest) -> MockResponse + Send + Sync>;
+
+// what a method answers: queued one-shot responses first, then the handler
+#[derive(Default)]
+struct MethodScript {
+    queue: VecDeque<MockResponse>,
+    handler: Option<Handler>,
+    latency: Duration,
+}
+
+struct MockState {
+    pool: DescriptorPool,
+    scripts: Mutex<HashMap<String, MethodScript>>,
+    calls: Mutex<Vec<MockRequest>>,
+}
+
+/// An in-process gRPC backend serving the services of a `FileDescriptorSet`
+/// with server reflection, answering each method as scripted. Unscripted
+/// methods answer the empty output message. The server stops when dropped.
+///
+/// ```rust,ignore
+/// let server = MockServer::start(descriptor_set).await?;
+/// server.respond("users.UserService", "GetUser", json!({ "name": "Ada" }));
+/// let gateway = mock_gateway(&server, &["users.UserService"]).await?;
+/// ```
+pub struct MockServer {
+    addr: SocketAddr,
+    state: Arc<MockState>,
+    task: JoinHandle<()>,
+}
+
+impl MockServer {
+    /// Starts serving on an ephemeral localhost port.
+    pub async fn start(descriptors: FileDescriptorSet) -> anyhow::Result<Self> {
+        let pool = DescriptorPool::from_file_descriptor_set(descriptors.clone())?;
+        let reflection = tonic_reflection::server::Builder::configure()
+            .register_file_descriptor_set(descriptors)
+            .include_reflection_service(false)
+            .build_v1()?;
+        let state = Arc::new(MockState {
+            pool,
+            scripts: Mutex::new(HashMap::new()),
+            calls: Mutex::new(Vec::new()),
+        });
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let service = MockService {
+            reflection,
+            state: state.clone(),
+        };
+        let task = tokio::spawn(async move {
+            let served = tonic::transport::Server::builder()
+                .serve_with_incoming(service, TcpListenerStream::new(listener))
+                .await;
+            if let Err(e) = served {
+                tracing::error!(error = %e, "mock backend stopped");
+            }
+        });
+
+        Ok(Self { addr, state, task })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// The endpoint the gateway connects to, e.g. `http://127.0.0.1:50051`.
+    pub fn endpoint(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Answers every call to the method with `message`.
+    pub fn respond(&self, service: &str, method: &str, message: Value) {
+        self.respond_with(service, method, move |_| {
+            MockResponse::Message(message.clone())
+        });
+    }
+
+    /// Answers every call to the method with an error status.
+    pub fn fail(&self, service: &str, method: &str, code: Code, message: &str) {
+        let response = MockResponse::error(code, message);
+        self.respond_with(service, method, move |_| response.clone());
+    }
+
+    /// Answers calls to the method with whatever `handler` returns for them.
+    pub fn respond_with<F>(&self, service: &str, method: &str, handler: F)
+    where
+        F: Fn(&MockRequest) -> MockResponse + Send + Sync + 'static,
+    {
+        self.script(service, method, |script| {
+            script.handler = Some(Arc::new(handler))
+        });
+    }
+
+    /// Answers the next call to the method with `response`, before any
+    /// standing answer. Queued responses are used in order.
+    pub fn enqueue(&self, service: &str, method: &str, response: MockResponse) {
+        self.script(service, method, |script| script.queue.push_back(response));
+    }
+
+    /// Delays every answer of the method.
+    pub fn set_latency(&self, service: &str, method: &str, latency: Duration) {
+        self.script(service, method, |script| script.latency = latency);
+    }
+
+    /// Every call received so far, oldest first.
+    pub fn calls(&self) -> Vec<MockRequest> {
+        self.state.calls.lock().unwrap().clone()
+    }
+
+    /// Calls received so far for one method.
+    pub fn calls_to(&self, service: &str, method: &str) -> Vec<MockRequest> {
+        self.calls()
+            .into_iter()
+            .filter(|call| call.service == service && call.method == method)
+            .collect()
+    }
+
+    /// A registration for `service` on this server, authenticated with
+    /// `MOCK_API_KEY`.
+    pub fn registration(&self, service: &str) -> ServiceRegisterRequest {
+        self.register_request(
+            service,
+            InternalAuthConfig {
+                auth_type: AuthType::APIKey,
+                auth_refresh_config: Some(AuthRefreshConfig {
+                    service_name: String::new(),
+                    method: String::new(),
+                    header_name: MOCK_API_KEY_HEADER.to_string(),
+                    access_token: MOCK_API_KEY.to_string(),
+                    expired_at: 0,
+                    refresh_token: String::new(),
+                }),
+            },
+        )
+    }
+
+    /// A registration for `service` on this server, authenticated with a JWT
+    /// refreshed through the method named in `refresh`.
+    pub fn jwt_registration(
+        &self,
+        service: &str,
+        refresh: AuthRefreshConfig,
+    ) -> ServiceRegisterRequest {
+        self.register_request(
+            service,
+            InternalAuthConfig {
+                auth_type: AuthType::JWTToken,
+                auth_refresh_config: Some(refresh),
+            },
+        )
+    }
+
+    fn register_request(
+        &self,
+        service: &str,
+        oauth_config: InternalAuthConfig,
+    ) -> ServiceRegisterRequest {
+        ServiceRegisterRequest {
+            service_name: service.to_string(),
+            host: self.addr.ip().to_string(),
+            port: self.addr.port().to_string(),
+            health_check_endpoint: String::new(),
+            oauth_config,
+        }
+    }
+
+    fn script(&self, service: &str, method: &str, update: impl FnOnce(&mut MethodScript)) {
+        let mut scripts = self.state.scripts.lock().unwrap();
+        update(scripts.entry(method_key(service, method)).or_default());
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// A new `Gateway` with `services` registered against the mock server.
+pub async fn mock_gateway(
+    server: &MockServer,
+    services: &[&str],
+) -> Result<Gateway, Box<dyn Error>> {
+    let gateway = Gateway::new();
+    for service in services {
+        gateway
+            .service_registry
+            .register(server.registration(service))
+            .await?;
+    }
+    Ok(gateway)
+}
+
+fn method_key(service: &str, method: &str) -> String {
+    format!("{}/{}", service, method)
+}
+
+impl MockState {
+    // decodes and records the call, then produces the scripted answer
+    async fn answer(
+        &self,
+        method: &MethodDescriptor,
+        request: tonic::Request<Bytes>,
+    ) -> Result<Vec<Vec<u8>>, Status> {
+        let metadata = request
+            .metadata()
+            .clone()
+            .into_headers()
+            .iter()
+            .filter_map(|(name, value)| {
+                value
+                    .to_str()
+                    .ok()
+                    .map(|v| (name.as_str().to_string(), v.to_string()))
+            })
+            .collect();
+        let body = request.into_inner();
+        let message = DynamicMessage::decode(method.input(), body.clone())
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let call = MockRequest {
+            service: method.parent_service().full_name().to_string(),
+            method: method.name().to_string(),
+            message: JsonFormat::default()
+                .to_json(&message)
+                .map_err(|e| Status::internal(e.to_string()))?,
+            body,
+            metadata,
+        };
+        self.calls.lock().unwrap().push(call.clone());
+
+        let (response, latency) = {
+            let mut scripts = self.scripts.lock().unwrap();
+            match scripts.get_mut(&method_key(&call.service, &call.method)) {
+                Some(script) => {
+                    let response = match (script.queue.pop_front(), &script.handler) {
+                        (Some(response), _) => Some(response),
+                        (None, Some(handler)) => Some(handler(&call)),
+                        (None, None) => None,
+                    };
+                    (response, script.latency)
+                }
+                None => (None, Duration::ZERO),
+            }
+        };
+        if !latency.is_zero() {
+            tokio::time::sleep(latency).await;
+        }
+
+        let encode = |value: &Value| {
+            let mut output = DynamicMessage::new(method.output());
+            JsonFormat::default()
+                .from_json(value, &mut output)
+                .map(|_| output.encode_to_vec())
+                .map_err(|e| Status::internal(format!("invalid scripted response: {}", e)))
+        };
+        match response {
+            None => Ok(vec![DynamicMessage::new(method.output()).encode_to_vec()]),
+            Some(MockResponse::Message(value)) => Ok(vec![encode(&value)?]),
+            Some(MockResponse::Stream(values)) => values.iter().map(encode).collect(),
+            Some(MockResponse::Error(code, message)) => Err(Status::new(code, message)),
+        }
+    }
+}
+
+// Routes reflection calls to the reflection service and everything else to
+// the scripted methods
+#[derive(Clone)]
+struct MockService<R> {
+    reflection: R,
+    state: Arc<MockState>,
+}
+
+impl<R> Service<http::Request<Body>> for MockService<R>
+where
+    R: Service<http::Request<Body>, Response = http::Response<Body>, Error = Infallible>
+        + Clone
+        + Send
+        + 'static,
+    R::Future: Send + 'static,
+{
+    type Response = http::Response<Body>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<Body>) -> Self::Future {
+        if req.uri().path().starts_with("/grpc.reflection.") {
+            let mut reflection = self.reflection.clone();
+            return Box::pin(async move { reflection.call(req).await });
+        }
+
+        let state = self.state.clone();
+        Box::pin(async move {
+            let path = req.uri().path().trim_start_matches('/').to_string();
+            let method = path.split_once('/').and_then(|(service, method)| {
+                state
+                    .pool
+                    .get_service_by_name(service)
+                    .and_then(|s| s.methods().find(|m| m.name() == method))
+            });
+            let Some(method) = method else {
+                return Ok(Status::unimplemented(format!("unknown method {}", path)).into_http());
+            };
+
+            let mut grpc = Grpc::new(BytesCodec);
+            let handler = MethodHandler { state, method };
+            Ok(
+                match (
+                    handler.method.is_client_streaming(),
+                    handler.method.is_server_streaming(),
+                ) {
+                    (false, false) => grpc.unary(handler, req).await,
+                    (false, true) => grpc.server_streaming(handler, req).await,
+                    _ => Status::unimplemented("client streaming is not supported by the mock")
+                        .into_http(),
+                },
+            )
+        })
+    }
+}
+
+struct MethodHandler {
+    state: Arc<MockState>,
+    method: MethodDescriptor,
+}
+
+impl UnaryService<Bytes> for MethodHandler {
+    type Response = Vec<u8>;
+    type Future = BoxFuture<tonic::Response<Vec<u8>>, Status>;
+
+    fn call(&mut self, request: tonic::Request<Bytes>) -> Self::Future {
+        let state = self.state.clone();
+        let method = self.method.clone();
+        Box::pin(async move {
+            let messages = state.answer(&method, request).await?;
+            let message = messages.into_iter().next().unwrap_or_default();
+            Ok(tonic::Response::new(message))
+        })
+    }
+}
+
+impl ServerStreamingService<Bytes> for MethodHandler {
+    type Response = Vec<u8>;
+    type ResponseStream = BoxStream<'static, Result<Vec<u8>, Status>>;
+    type Future = BoxFuture<tonic::Response<Self::ResponseStream>, Status>;
+
+    fn call(&mut self, request: tonic::Request<Bytes>) -> Self::Future {
+        let state = self.state.clone();
+        let method = self.method.clone();
+        Box::pin(async move {
+            let messages = state.answer(&method, request).await?;
+            let stream = futures::stream::iter(messages.into_iter().map(Ok)).boxed();
+            Ok(tonic::Response::new(stream))
+        })
+    }
+}
@@ -0,0 +1,5 @@
+pub mod mock_server;
+
+pub use mock_server::{
+    MOCK_API_KEY, MOCK_API_KEY_HEADER, MockRequest, MockResponse, MockServer, mock_gateway,
+};
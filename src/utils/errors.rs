@@ -9,6 +9,10 @@ pub enum ResponseErrors {
     Error,
     OAuthRefreshConfigMissingError,
     InternalServerError,
+    RateLimitExceeded,
+    LoadShed(String),
+    RequestValidationFailed,
+    InvalidJsonFormat(String),
 }
 
 impl ResponseErrors {
@@ -27,6 +31,14 @@ impl ResponseErrors {
                 Cow::Borrowed("oauth refresh config is missing")
             }
             ResponseErrors::InternalServerError => Cow::Borrowed("internal server error"),
+            ResponseErrors::RateLimitExceeded => Cow::Borrowed("rate limit exceeded"),
+            ResponseErrors::LoadShed(reason) => {
+                Cow::Owned(format!("service overloaded, request shed: {}", reason))
+            }
+            ResponseErrors::RequestValidationFailed => Cow::Borrowed("request validation failed"),
+            ResponseErrors::InvalidJsonFormat(reason) => {
+                Cow::Owned(format!("invalid $alt parameter: {}", reason))
+            }
         }
     }
 }
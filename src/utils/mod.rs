@@ -1,6 +1,9 @@
 pub mod errors;
 pub mod model;
+pub mod request_id;
 pub mod response;
 pub mod response_builder;
 pub mod service_status;
+#[cfg(test)]
+pub(crate) mod test_descriptors;
 pub mod validation_errors;
@@ -1,3 +1,6 @@
+use std::collections::HashMap;
+
+use bytes::Bytes;
 use serde::{Deserialize, Serialize};
 
 use crate::registry::model::InternalAuthConfig;
@@ -9,6 +12,19 @@ pub struct RequestType {
     pub data: serde_json::Value,
 }
 
+/// HTTP details of the incoming call that the gateway forwards or acts upon.
+#[derive(Debug, Clone, Default)]
+pub struct RequestContext {
+    /// incoming HTTP headers keyed by lowercase name
+    pub headers: HashMap<String, String>,
+    pub client_ip: Option<String>,
+    /// decoded query string parameters
+    pub query: HashMap<String, String>,
+    /// binary protobuf body of a caller that also accepts binary protobuf,
+    /// sent to the backend as received when nothing changed the request
+    pub protobuf_body: Option<Bytes>,
+}
+
 #[derive(Debug, Clone)]
 pub struct ServiceRegisterRequest {
     pub service_name: String,
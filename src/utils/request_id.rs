@@ -0,0 +1,22 @@
+use std::collections::HashMap;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// Returns the caller supplied `x-request-id` when it is usable as a header
+/// value, otherwise generates a new one.
+pub fn resolve(headers: &HashMap<String, String>) -> String {
+    match headers.get(REQUEST_ID_HEADER) {
+        Some(id) if is_valid(id) => id.to_string(),
+        _ => generate(),
+    }
+}
+
+pub fn generate() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+fn is_valid(id: &str) -> bool {
+    !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN && id.bytes().all(|b| b.is_ascii_graphic())
+}
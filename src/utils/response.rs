@@ -1,11 +1,27 @@
 use std::borrow::Cow;
+use std::collections::HashMap;
 
+use bytes::Bytes;
 use reqwest::StatusCode;
+use serde::Serialize;
 
-#[derive(Debug)]
+#[derive(Debug, Default, Serialize)]
 pub struct Response {
     pub message: Cow<'static, str>,
     pub status: Cow<'static, str>,
+    #[serde(skip)]
     pub status_code: StatusCode,
     pub data: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    /// extra HTTP headers for the HTTP layer, e.g. `retry-after`
+    #[serde(skip)]
+    pub headers: HashMap<String, String>,
+    /// status reported by the backend when the call failed there
+    #[serde(skip)]
+    pub grpc_code: Option<tonic::Code>,
+    /// response message exactly as the backend encoded it, for binary
+    /// protobuf callers
+    #[serde(skip)]
+    pub protobuf_body: Option<Bytes>,
 }
@@ -10,6 +10,8 @@ where
     pub status: bool,
     pub message: String,
     pub data: Option<T>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub request_id: Option<String>,
 }
 
 impl<T> ResponseBuilder<T>
@@ -21,6 +23,7 @@ where
             status: true,
             message: msg,
             data: Some(data),
+            request_id: None,
         }
     }
 
@@ -29,8 +32,23 @@ where
             status: false,
             message: msg,
             data: None,
+            request_id: None,
         }
     }
+
+    pub fn internal_error(msg: String) -> Self {
+        Self {
+            status: false,
+            message: msg,
+            data: None,
+            request_id: None,
+        }
+    }
+
+    pub fn with_request_id(mut self, request_id: String) -> Self {
+        self.request_id = Some(request_id);
+        self
+    }
 }
 
 impl<T> Display for ResponseBuilder<T>
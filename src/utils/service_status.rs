@@ -1,4 +1,4 @@
-#[allow(Debug, clone)]
+#[derive(Debug, Clone)]
 pub enum ServiceStatus {
     Enable,
     Disable,
@@ -0,0 +1,20 @@
+//! Descriptor pools for unit tests, written as `FileDescriptorProto`s in
+//! protobuf text format.
+
+use prost::Message;
+use prost_reflect::{DescriptorPool, DynamicMessage};
+
+/// Pool with the well-known types and `files`, added in order so a file can
+/// use the types and extensions of the files before it.
+pub(crate) fn pool(files: &[&str]) -> DescriptorPool {
+    let mut pool = DescriptorPool::global();
+    for file in files {
+        let descriptor = pool
+            .get_message_by_name("google.protobuf.FileDescriptorProto")
+            .unwrap();
+        let proto = DynamicMessage::parse_text_format(descriptor, file).unwrap();
+        pool.decode_file_descriptor_proto(proto.encode_to_vec().as_slice())
+            .unwrap();
+    }
+    pool
+}
@@ -0,0 +1,4 @@
+pub mod rules;
+pub mod validator;
+
+pub use validator::{FieldViolation, RequestValidationError, RequestValidator, ValidationOptions};
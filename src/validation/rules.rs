@@ -0,0 +1,785 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::net::IpAddr;
+use std::sync::Mutex;
+
+use prost_reflect::{
+    DynamicMessage, ExtensionDescriptor, FieldDescriptor, Kind, MapKey, ReflectMessage, Value,
+};
+use regex::Regex;
+
+use crate::validation::validator::{FieldViolation, pointer};
+
+/// Field option extensions carrying validation rules: protovalidate and the
+/// older protoc-gen-validate.
+const RULE_EXTENSIONS: [&str; 2] = ["buf.validate.field", "validate.rules"];
+
+const NUMERIC_RULES: [&str; 12] = [
+    "int32", "int64", "uint32", "uint64", "sint32", "sint64", "fixed32", "fixed64", "sfixed32",
+    "sfixed64", "float", "double",
+];
+
+// rule name, check and violation message for string formats
+type WellKnownFormat = (&'static str, fn(&str) -> bool, &'static str);
+
+lazy_static::lazy_static! {
+    static ref PATTERNS: Mutex<HashMap<String, Option<Regex>>> = Mutex::new(HashMap::new());
+}
+
+/// Applies the rule annotations of every field in `message`, recursing into
+/// nested messages. Descriptors without the validate extensions in their pool
+/// are skipped.
+pub fn check_message(message: &DynamicMessage, path: &str, out: &mut Vec<FieldViolation>) {
+    let descriptor = message.descriptor();
+    let pool = descriptor.parent_pool();
+    let extensions: Vec<ExtensionDescriptor> = RULE_EXTENSIONS
+        .iter()
+        .filter_map(|name| pool.get_extension_by_name(name))
+        .collect();
+
+    for field in descriptor.fields() {
+        let field_path = pointer(path, field.json_name());
+        let present = message.has_field(&field);
+        let value = message.get_field(&field);
+
+        let options = field.options();
+        for extension in &extensions {
+            if !options.has_extension(extension) {
+                continue;
+            }
+            if let Some(rules) = options.get_extension(extension).as_message() {
+                check_field(&field, present, &value, rules, &field_path, out);
+            }
+        }
+
+        if !present || field.kind().as_message().is_none() {
+            continue;
+        }
+        match value.as_ref() {
+            Value::Message(nested) => check_message(nested, &field_path, out),
+            Value::List(items) => {
+                for (i, item) in items.iter().enumerate() {
+                    if let Value::Message(nested) = item {
+                        check_message(nested, &pointer(&field_path, &i.to_string()), out);
+                    }
+                }
+            }
+            Value::Map(entries) => {
+                for (key, item) in entries {
+                    if let Value::Message(nested) = item {
+                        check_message(nested, &pointer(&field_path, &map_key(key)), out);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn check_field(
+    field: &FieldDescriptor,
+    present: bool,
+    value: &Value,
+    rules: &DynamicMessage,
+    path: &str,
+    out: &mut Vec<FieldViolation>,
+) {
+    let pgv_required = rule(rules, "message")
+        .and_then(|m| m.as_message().map(|m| flag(m, "required")))
+        .unwrap_or(false);
+    if (flag(rules, "required") || pgv_required) && !present {
+        out.push(FieldViolation::new(path, "value is required"));
+        return;
+    }
+
+    if let Value::List(items) = value {
+        if let Some(repeated) = rule_message(rules, "repeated") {
+            check_repeated(&field.kind(), items, &repeated, path, out);
+        }
+        return;
+    }
+    if let Value::Map(entries) = value {
+        if let Some(map) = rule_message(rules, "map")
+            && let Some(entry) = field.kind().as_message()
+        {
+            let kinds = (
+                entry.map_entry_key_field().kind(),
+                entry.map_entry_value_field().kind(),
+            );
+            check_map(&kinds, entries, &map, path, out);
+        }
+        return;
+    }
+
+    // unset optional fields are only checked for presence
+    if field.supports_presence() && !present {
+        return;
+    }
+    check_value(&field.kind(), value, rules, path, out);
+}
+
+fn check_repeated(
+    kind: &Kind,
+    items: &[Value],
+    rules: &DynamicMessage,
+    path: &str,
+    out: &mut Vec<FieldViolation>,
+) {
+    if let Some(min) = rule(rules, "min_items").and_then(|v| v.as_u64())
+        && (items.len() as u64) < min
+    {
+        out.push(FieldViolation::new(
+            path,
+            format!("value must contain at least {} item(s)", min),
+        ));
+    }
+    if let Some(max) = rule(rules, "max_items").and_then(|v| v.as_u64())
+        && items.len() as u64 > max
+    {
+        out.push(FieldViolation::new(
+            path,
+            format!("value must contain no more than {} item(s)", max),
+        ));
+    }
+    if flag(rules, "unique") {
+        let duplicate = items
+            .iter()
+            .enumerate()
+            .any(|(i, item)| items[..i].contains(item));
+        if duplicate {
+            out.push(FieldViolation::new(
+                path,
+                "repeated value must contain unique items",
+            ));
+        }
+    }
+    if let Some(item_rules) = rule_message(rules, "items") {
+        for (i, item) in items.iter().enumerate() {
+            check_value(kind, item, &item_rules, &pointer(path, &i.to_string()), out);
+        }
+    }
+}
+
+fn check_map(
+    (key_kind, value_kind): &(Kind, Kind),
+    entries: &HashMap<MapKey, Value>,
+    rules: &DynamicMessage,
+    path: &str,
+    out: &mut Vec<FieldViolation>,
+) {
+    if let Some(min) = rule(rules, "min_pairs").and_then(|v| v.as_u64())
+        && (entries.len() as u64) < min
+    {
+        out.push(FieldViolation::new(
+            path,
+            format!("map must be at least {} entries", min),
+        ));
+    }
+    if let Some(max) = rule(rules, "max_pairs").and_then(|v| v.as_u64())
+        && entries.len() as u64 > max
+    {
+        out.push(FieldViolation::new(
+            path,
+            format!("map must be at most {} entries", max),
+        ));
+    }
+
+    let key_rules = rule_message(rules, "keys");
+    let value_rules = rule_message(rules, "values");
+    for (key, item) in entries {
+        let item_path = pointer(path, &map_key(key));
+        if let Some(key_rules) = &key_rules {
+            check_value(
+                key_kind,
+                &Value::from(key.clone()),
+                key_rules,
+                &item_path,
+                out,
+            );
+        }
+        if let Some(value_rules) = &value_rules {
+            check_value(value_kind, item, value_rules, &item_path, out);
+        }
+    }
+}
+
+/// Checks a single (non-repeated) value against the type specific rules set
+/// on `rules`.
+fn check_value(
+    kind: &Kind,
+    value: &Value,
+    rules: &DynamicMessage,
+    path: &str,
+    out: &mut Vec<FieldViolation>,
+) {
+    if let Some(string_rules) = rule_message(rules, "string")
+        && let Some(s) = value.as_str()
+    {
+        check_string(s, &string_rules, path, out);
+    } else if let Some(bytes_rules) = rule_message(rules, "bytes")
+        && let Some(b) = value.as_bytes()
+    {
+        check_bytes(b, &bytes_rules, path, out);
+    } else if let Some(enum_rules) = rule_message(rules, "enum")
+        && let Some(number) = value.as_enum_number()
+    {
+        check_enum(kind, number, &enum_rules, path, out);
+    } else if let Some(numeric) = NUMERIC_RULES
+        .iter()
+        .find_map(|name| rule_message(rules, name))
+    {
+        match value {
+            Value::F32(_) | Value::F64(_) => check_range(value, &numeric, to_f64, path, out),
+            _ => check_range(value, &numeric, to_i128, path, out),
+        }
+    }
+}
+
+fn check_string(s: &str, rules: &DynamicMessage, path: &str, out: &mut Vec<FieldViolation>) {
+    let mut fail = |message: String| out.push(FieldViolation::new(path, message));
+    let chars = s.chars().count() as u64;
+
+    if let Some(expected) = rule(rules, "const").and_then(|v| v.as_str().map(str::to_string))
+        && s != expected
+    {
+        fail(format!("value must equal `{}`", expected));
+    }
+    if let Some(len) = rule(rules, "len").and_then(|v| v.as_u64())
+        && chars != len
+    {
+        fail(format!("value length must be {} characters", len));
+    }
+    if let Some(min) = rule(rules, "min_len").and_then(|v| v.as_u64())
+        && chars < min
+    {
+        fail(format!("value length must be at least {} characters", min));
+    }
+    if let Some(max) = rule(rules, "max_len").and_then(|v| v.as_u64())
+        && chars > max
+    {
+        fail(format!("value length must be at most {} characters", max));
+    }
+    if let Some(len) = rule(rules, "len_bytes").and_then(|v| v.as_u64())
+        && s.len() as u64 != len
+    {
+        fail(format!("value length must be {} bytes", len));
+    }
+    if let Some(min) = rule(rules, "min_bytes").and_then(|v| v.as_u64())
+        && (s.len() as u64) < min
+    {
+        fail(format!("value length must be at least {} bytes", min));
+    }
+    if let Some(max) = rule(rules, "max_bytes").and_then(|v| v.as_u64())
+        && s.len() as u64 > max
+    {
+        fail(format!("value length must be at most {} bytes", max));
+    }
+    if let Some(pattern) = rule(rules, "pattern").and_then(|v| v.as_str().map(str::to_string))
+        && !matches_pattern(&pattern, s)
+    {
+        fail(format!("value does not match regex pattern `{}`", pattern));
+    }
+    if let Some(prefix) = rule(rules, "prefix").and_then(|v| v.as_str().map(str::to_string))
+        && !s.starts_with(&prefix)
+    {
+        fail(format!("value does not have prefix `{}`", prefix));
+    }
+    if let Some(suffix) = rule(rules, "suffix").and_then(|v| v.as_str().map(str::to_string))
+        && !s.ends_with(&suffix)
+    {
+        fail(format!("value does not have suffix `{}`", suffix));
+    }
+    if let Some(needle) = rule(rules, "contains").and_then(|v| v.as_str().map(str::to_string))
+        && !s.contains(&needle)
+    {
+        fail(format!("value does not contain substring `{}`", needle));
+    }
+    if let Some(needle) = rule(rules, "not_contains").and_then(|v| v.as_str().map(str::to_string))
+        && s.contains(&needle)
+    {
+        fail(format!("value contains substring `{}`", needle));
+    }
+
+    let values = |name: &str| -> Vec<String> {
+        rule(rules, name)
+            .and_then(|v| {
+                v.as_list().map(|items| {
+                    items
+                        .iter()
+                        .filter_map(|i| i.as_str().map(str::to_string))
+                        .collect()
+                })
+            })
+            .unwrap_or_default()
+    };
+    let allowed = values("in");
+    if !allowed.is_empty() && !allowed.iter().any(|a| a == s) {
+        out.push(FieldViolation {
+            allowed: Some(allowed),
+            ..FieldViolation::new(path, "value must be in list")
+        });
+    }
+    if values("not_in").iter().any(|a| a == s) {
+        out.push(FieldViolation::new(path, "value must not be in list"));
+    }
+
+    let well_known: [WellKnownFormat; 7] = [
+        ("email", is_email, "value must be a valid email address"),
+        ("hostname", is_hostname, "value must be a valid hostname"),
+        (
+            "ip",
+            |s| s.parse::<IpAddr>().is_ok(),
+            "value must be a valid IP address",
+        ),
+        (
+            "ipv4",
+            |s| s.parse::<std::net::Ipv4Addr>().is_ok(),
+            "value must be a valid IPv4 address",
+        ),
+        (
+            "ipv6",
+            |s| s.parse::<std::net::Ipv6Addr>().is_ok(),
+            "value must be a valid IPv6 address",
+        ),
+        (
+            "uri",
+            |s| url::Url::parse(s).is_ok(),
+            "value must be a valid URI",
+        ),
+        (
+            "uuid",
+            |s| uuid::Uuid::parse_str(s).is_ok(),
+            "value must be a valid UUID",
+        ),
+    ];
+    for (name, check, message) in well_known {
+        if flag(rules, name) && !check(s) {
+            out.push(FieldViolation::new(path, message));
+        }
+    }
+}
+
+fn check_bytes(b: &[u8], rules: &DynamicMessage, path: &str, out: &mut Vec<FieldViolation>) {
+    let len = b.len() as u64;
+    if let Some(expected) = rule(rules, "const").and_then(|v| v.as_bytes().cloned())
+        && b != expected.as_ref()
+    {
+        out.push(FieldViolation::new(
+            path,
+            "value must equal the configured bytes",
+        ));
+    }
+    if let Some(exact) = rule(rules, "len").and_then(|v| v.as_u64())
+        && len != exact
+    {
+        out.push(FieldViolation::new(
+            path,
+            format!("value length must be {} bytes", exact),
+        ));
+    }
+    if let Some(min) = rule(rules, "min_len").and_then(|v| v.as_u64())
+        && len < min
+    {
+        out.push(FieldViolation::new(
+            path,
+            format!("value length must be at least {} bytes", min),
+        ));
+    }
+    if let Some(max) = rule(rules, "max_len").and_then(|v| v.as_u64())
+        && len > max
+    {
+        out.push(FieldViolation::new(
+            path,
+            format!("value length must be at most {} bytes", max),
+        ));
+    }
+}
+
+fn check_enum(
+    kind: &Kind,
+    number: i32,
+    rules: &DynamicMessage,
+    path: &str,
+    out: &mut Vec<FieldViolation>,
+) {
+    let numbers = |name: &str| -> Vec<i32> {
+        rule(rules, name)
+            .and_then(|v| {
+                v.as_list()
+                    .map(|items| items.iter().filter_map(|i| i.as_i32()).collect())
+            })
+            .unwrap_or_default()
+    };
+
+    if let Some(expected) = rule(rules, "const").and_then(|v| v.as_i32())
+        && number != expected
+    {
+        out.push(FieldViolation::new(
+            path,
+            format!("value must equal {}", expected),
+        ));
+    }
+    let allowed = numbers("in");
+    if !allowed.is_empty() && !allowed.contains(&number) {
+        out.push(FieldViolation {
+            allowed: Some(allowed.iter().map(|n| n.to_string()).collect()),
+            ..FieldViolation::new(path, "value must be in list")
+        });
+    }
+    if numbers("not_in").contains(&number) {
+        out.push(FieldViolation::new(path, "value must not be in list"));
+    }
+    if flag(rules, "defined_only")
+        && let Kind::Enum(enum_desc) = kind
+        && enum_desc.get_value(number).is_none()
+    {
+        out.push(FieldViolation {
+            allowed: Some(enum_desc.values().map(|v| v.name().to_string()).collect()),
+            ..FieldViolation::new(path, "value must be one of the defined enum values")
+        });
+    }
+}
+
+fn check_range<T: PartialOrd + Copy + Display>(
+    value: &Value,
+    rules: &DynamicMessage,
+    read: fn(&Value) -> Option<T>,
+    path: &str,
+    out: &mut Vec<FieldViolation>,
+) {
+    let Some(n) = read(value) else {
+        return;
+    };
+    let get = |name: &str| rule(rules, name).and_then(|v| read(&v));
+
+    if let Some(expected) = get("const")
+        && n != expected
+    {
+        out.push(FieldViolation::new(
+            path,
+            format!("value must equal {}", expected),
+        ));
+    }
+
+    let upper = get("lt")
+        .map(|b| (b, n < b, "less than"))
+        .or_else(|| get("lte").map(|b| (b, n <= b, "less than or equal to")));
+    let lower = get("gt")
+        .map(|b| (b, n > b, "greater than"))
+        .or_else(|| get("gte").map(|b| (b, n >= b, "greater than or equal to")));
+    match (lower, upper) {
+        // lower bound above the upper bound describes an exclusive range
+        (Some((lo, lo_ok, lo_text)), Some((hi, hi_ok, hi_text))) if lo > hi => {
+            if !lo_ok && !hi_ok {
+                out.push(FieldViolation::new(
+                    path,
+                    format!("value must be {} {} or {} {}", lo_text, lo, hi_text, hi),
+                ));
+            }
+        }
+        (lower, upper) => {
+            for (bound, ok, text) in lower.into_iter().chain(upper) {
+                if !ok {
+                    out.push(FieldViolation::new(
+                        path,
+                        format!("value must be {} {}", text, bound),
+                    ));
+                }
+            }
+        }
+    }
+
+    let list = |name: &str| -> Vec<T> {
+        rule(rules, name)
+            .and_then(|v| {
+                v.as_list()
+                    .map(|items| items.iter().filter_map(read).collect())
+            })
+            .unwrap_or_default()
+    };
+    let allowed = list("in");
+    if !allowed.is_empty() && !allowed.contains(&n) {
+        out.push(FieldViolation {
+            allowed: Some(allowed.iter().map(|a| a.to_string()).collect()),
+            ..FieldViolation::new(path, "value must be in list")
+        });
+    }
+    if list("not_in").contains(&n) {
+        out.push(FieldViolation::new(path, "value must not be in list"));
+    }
+}
+
+fn to_i128(value: &Value) -> Option<i128> {
+    match value {
+        Value::I32(n) => Some(*n as i128),
+        Value::I64(n) => Some(*n as i128),
+        Value::U32(n) => Some(*n as i128),
+        Value::U64(n) => Some(*n as i128),
+        _ => None,
+    }
+}
+
+fn to_f64(value: &Value) -> Option<f64> {
+    match value {
+        Value::F32(n) => Some(*n as f64),
+        Value::F64(n) => Some(*n),
+        _ => None,
+    }
+}
+
+/// Reads a rule only when it is explicitly set.
+fn rule<'a>(rules: &'a DynamicMessage, name: &str) -> Option<Cow<'a, Value>> {
+    if rules.has_field_by_name(name) {
+        rules.get_field_by_name(name)
+    } else {
+        None
+    }
+}
+
+fn rule_message(rules: &DynamicMessage, name: &str) -> Option<DynamicMessage> {
+    rule(rules, name).and_then(|v| v.as_message().cloned())
+}
+
+fn flag(rules: &DynamicMessage, name: &str) -> bool {
+    rule(rules, name).and_then(|v| v.as_bool()).unwrap_or(false)
+}
+
+pub(crate) fn map_key(key: &MapKey) -> String {
+    match key {
+        MapKey::Bool(b) => b.to_string(),
+        MapKey::I32(n) => n.to_string(),
+        MapKey::I64(n) => n.to_string(),
+        MapKey::U32(n) => n.to_string(),
+        MapKey::U64(n) => n.to_string(),
+        MapKey::String(s) => s.clone(),
+    }
+}
+
+fn matches_pattern(pattern: &str, s: &str) -> bool {
+    let Ok(mut patterns) = PATTERNS.lock() else {
+        return true;
+    };
+    let compiled = patterns
+        .entry(pattern.to_string())
+        .or_insert_with(|| Regex::new(pattern).ok());
+    // an invalid pattern is a schema problem, not a client one
+    compiled.as_ref().is_none_or(|re| re.is_match(s))
+}
+
+fn is_email(s: &str) -> bool {
+    match s.rsplit_once('@') {
+        Some((local, domain)) => !local.is_empty() && local.len() <= 64 && is_hostname(domain),
+        None => false,
+    }
+}
+
+fn is_hostname(s: &str) -> bool {
+    let s = s.strip_suffix('.').unwrap_or(s);
+    !s.is_empty()
+        && s.len() <= 253
+        && s.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_descriptors;
+    use crate::validation::validator::{ValidationOptions, validate_with};
+    use prost_reflect::MessageDescriptor;
+    use serde_json::json;
+
+    // the part of buf/validate/validate.proto the tests use
+    const VALIDATE_PROTO: &str = r#"
+        name: "buf/validate/validate.proto" package: "buf.validate" syntax: "proto3"
+        dependency: "google/protobuf/descriptor.proto"
+        message_type {
+            name: "FieldConstraints"
+            field { name: "int32" number: 3 type: TYPE_MESSAGE type_name: ".buf.validate.Int32Rules" label: LABEL_OPTIONAL json_name: "int32" }
+            field { name: "string" number: 14 type: TYPE_MESSAGE type_name: ".buf.validate.StringRules" label: LABEL_OPTIONAL json_name: "string" }
+            field { name: "enum" number: 16 type: TYPE_MESSAGE type_name: ".buf.validate.EnumRules" label: LABEL_OPTIONAL json_name: "enum" }
+            field { name: "repeated" number: 18 type: TYPE_MESSAGE type_name: ".buf.validate.RepeatedRules" label: LABEL_OPTIONAL json_name: "repeated" }
+            field { name: "required" number: 25 type: TYPE_BOOL label: LABEL_OPTIONAL json_name: "required" }
+        }
+        message_type {
+            name: "Int32Rules"
+            field { name: "lt" number: 2 type: TYPE_INT32 label: LABEL_OPTIONAL json_name: "lt" proto3_optional: true oneof_index: 0 }
+            field { name: "gt" number: 4 type: TYPE_INT32 label: LABEL_OPTIONAL json_name: "gt" proto3_optional: true oneof_index: 1 }
+            oneof_decl { name: "_lt" }
+            oneof_decl { name: "_gt" }
+        }
+        message_type {
+            name: "StringRules"
+            field { name: "min_len" number: 2 type: TYPE_UINT64 label: LABEL_OPTIONAL json_name: "minLen" proto3_optional: true oneof_index: 0 }
+            field { name: "pattern" number: 6 type: TYPE_STRING label: LABEL_OPTIONAL json_name: "pattern" proto3_optional: true oneof_index: 1 }
+            field { name: "in" number: 10 type: TYPE_STRING label: LABEL_REPEATED json_name: "in" }
+            field { name: "email" number: 12 type: TYPE_BOOL label: LABEL_OPTIONAL json_name: "email" }
+            oneof_decl { name: "_min_len" }
+            oneof_decl { name: "_pattern" }
+        }
+        message_type {
+            name: "EnumRules"
+            field { name: "defined_only" number: 2 type: TYPE_BOOL label: LABEL_OPTIONAL json_name: "definedOnly" }
+        }
+        message_type {
+            name: "RepeatedRules"
+            field { name: "min_items" number: 1 type: TYPE_UINT64 label: LABEL_OPTIONAL json_name: "minItems" proto3_optional: true oneof_index: 0 }
+            field { name: "unique" number: 3 type: TYPE_BOOL label: LABEL_OPTIONAL json_name: "unique" }
+            field { name: "items" number: 4 type: TYPE_MESSAGE type_name: ".buf.validate.FieldConstraints" label: LABEL_OPTIONAL json_name: "items" }
+            oneof_decl { name: "_min_items" }
+        }
+        extension { name: "field" number: 1159 type: TYPE_MESSAGE type_name: ".buf.validate.FieldConstraints" label: LABEL_OPTIONAL extendee: ".google.protobuf.FieldOptions" json_name: "field" }
+    "#;
+
+    const SHOP_PROTO: &str = r#"
+        name: "shop.proto" package: "shop" syntax: "proto3"
+        dependency: "buf/validate/validate.proto"
+        message_type {
+            name: "Order"
+            field { name: "customer_email" number: 1 type: TYPE_STRING label: LABEL_OPTIONAL json_name: "customerEmail"
+                    options { [buf.validate.field] { string { email: true } } } }
+            field { name: "items" number: 2 type: TYPE_MESSAGE type_name: ".shop.Item" label: LABEL_REPEATED json_name: "items"
+                    options { [buf.validate.field] { repeated { min_items: 1 } } } }
+            field { name: "tags" number: 3 type: TYPE_STRING label: LABEL_REPEATED json_name: "tags"
+                    options { [buf.validate.field] { repeated { unique: true items { string { min_len: 2 } } } } } }
+            field { name: "note" number: 4 type: TYPE_MESSAGE type_name: ".shop.Note" label: LABEL_OPTIONAL json_name: "note"
+                    options { [buf.validate.field] { required: true } } }
+            field { name: "status" number: 5 type: TYPE_ENUM type_name: ".shop.Status" label: LABEL_OPTIONAL json_name: "status"
+                    options { [buf.validate.field] { enum { defined_only: true } } } }
+        }
+        message_type {
+            name: "Item"
+            field { name: "sku" number: 1 type: TYPE_STRING label: LABEL_OPTIONAL json_name: "sku"
+                    options { [buf.validate.field] { string { pattern: "^[A-Z]{3}-[0-9]+$" } } } }
+            field { name: "quantity" number: 2 type: TYPE_INT32 label: LABEL_OPTIONAL json_name: "quantity"
+                    options { [buf.validate.field] { int32 { gt: 0 lt: 100 } } } }
+            field { name: "size" number: 3 type: TYPE_STRING label: LABEL_OPTIONAL json_name: "size"
+                    options { [buf.validate.field] { string { in: ["S", "M", "L"] } } } }
+        }
+        message_type { name: "Note" field { name: "text" number: 1 type: TYPE_STRING label: LABEL_OPTIONAL json_name: "text" } }
+        enum_type { name: "Status" value { name: "STATUS_UNSPECIFIED" number: 0 } value { name: "STATUS_OPEN" number: 1 } }
+    "#;
+
+    fn order() -> MessageDescriptor {
+        test_descriptors::pool(&[VALIDATE_PROTO, SHOP_PROTO])
+            .get_message_by_name("shop.Order")
+            .unwrap()
+    }
+
+    // (path, message) of every violation of `data`
+    fn violations(data: serde_json::Value) -> Vec<(String, String)> {
+        let mut data = data;
+        match validate_with(&ValidationOptions::default(), &order(), &mut data) {
+            Ok(()) => Vec::new(),
+            Err(e) => e
+                .violations
+                .into_iter()
+                .map(|v| (v.path, v.message))
+                .collect(),
+        }
+    }
+
+    fn valid_order() -> serde_json::Value {
+        json!({
+            "customerEmail": "ada@example.com",
+            "items": [{ "sku": "ABC-1", "quantity": 2, "size": "M" }],
+            "tags": ["gift"],
+            "note": { "text": "leave at the door" },
+            "status": "STATUS_OPEN",
+        })
+    }
+
+    #[test]
+    fn a_valid_request_has_no_violations() {
+        assert_eq!(violations(valid_order()), Vec::new());
+    }
+
+    #[test]
+    fn nested_rule_violations_point_at_the_value() {
+        let mut order = valid_order();
+        order["items"] = json!([
+            { "sku": "ABC-1", "quantity": 1, "size": "S" },
+            { "sku": "abc", "quantity": 100, "size": "XL" },
+        ]);
+
+        let found = violations(order);
+
+        assert_eq!(
+            found,
+            vec![
+                (
+                    "/items/1/sku".to_string(),
+                    "value does not match regex pattern `^[A-Z]{3}-[0-9]+$`".to_string()
+                ),
+                (
+                    "/items/1/quantity".to_string(),
+                    "value must be less than 100".to_string()
+                ),
+                (
+                    "/items/1/size".to_string(),
+                    "value must be in list".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn repeated_rules_apply_to_the_list_and_each_item() {
+        let mut order = valid_order();
+        order["items"] = json!([]);
+        order["tags"] = json!(["gift", "x", "gift"]);
+
+        let found = violations(order);
+
+        assert!(found.contains(&(
+            "/items".to_string(),
+            "value must contain at least 1 item(s)".to_string()
+        )));
+        assert!(found.contains(&(
+            "/tags".to_string(),
+            "repeated value must contain unique items".to_string()
+        )));
+        assert!(found.contains(&(
+            "/tags/1".to_string(),
+            "value length must be at least 2 characters".to_string()
+        )));
+    }
+
+    #[test]
+    fn required_well_known_and_enum_rules() {
+        let mut order = valid_order();
+        order["customerEmail"] = json!("not-an-email");
+        order["status"] = json!(7);
+        order.as_object_mut().unwrap().remove("note");
+
+        let found = violations(order);
+
+        assert_eq!(
+            found,
+            vec![
+                (
+                    "/customerEmail".to_string(),
+                    "value must be a valid email address".to_string()
+                ),
+                ("/note".to_string(), "value is required".to_string()),
+                (
+                    "/status".to_string(),
+                    "value must be one of the defined enum values".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn map_keys_are_written_as_json_keys() {
+        assert_eq!(map_key(&MapKey::I32(-7)), "-7");
+        assert_eq!(map_key(&MapKey::Bool(true)), "true");
+        assert_eq!(map_key(&MapKey::String("a/b".to_string())), "a/b");
+    }
+}
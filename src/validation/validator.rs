@@ -0,0 +1,552 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::RwLock;
+
+use prost_reflect::{DynamicMessage, Kind, MessageDescriptor};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::validation::rules;
+
+/// A single problem with the request payload.
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldViolation {
+    /// JSON pointer to the offending value, e.g. `/items/0/quantity`
+    pub path: String,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expected: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allowed: Option<Vec<String>>,
+}
+
+impl FieldViolation {
+    pub fn new(path: &str, message: impl Into<String>) -> Self {
+        Self {
+            path: path.to_string(),
+            message: message.into(),
+            expected: None,
+            allowed: None,
+        }
+    }
+
+    fn expected(path: &str, expected: &str) -> Self {
+        Self {
+            expected: Some(expected.to_string()),
+            ..Self::new(path, format!("expected {}", expected))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RequestValidationError {
+    pub violations: Vec<FieldViolation>,
+}
+
+impl fmt::Display for RequestValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let details: Vec<String> = self
+            .violations
+            .iter()
+            .map(|v| format!("{}: {}", v.path, v.message))
+            .collect();
+        write!(f, "{}", details.join("; "))
+    }
+}
+
+impl std::error::Error for RequestValidationError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationOptions {
+    /// reject fields that are not part of the input message; when `false`
+    /// they are dropped before the request is encoded
+    pub reject_unknown_fields: bool,
+    /// enforce `buf.validate` / `validate.rules` annotations
+    pub enforce_rules: bool,
+}
+
+impl Default for ValidationOptions {
+    fn default() -> Self {
+        Self {
+            reject_unknown_fields: true,
+            enforce_rules: true,
+        }
+    }
+}
+
+/// Validation settings, overridable per service.
+#[derive(Debug, Default)]
+pub struct RequestValidator {
+    pub defaults: ValidationOptions,
+    services: RwLock<HashMap<String, ValidationOptions>>,
+}
+
+impl RequestValidator {
+    pub fn set_service_options(&self, service: &str, options: ValidationOptions) {
+        if let Ok(mut services) = self.services.write() {
+            services.insert(service.to_string(), options);
+        }
+    }
+
+    pub fn options_for(&self, service: &str) -> ValidationOptions {
+        self.services
+            .read()
+            .ok()
+            .and_then(|services| services.get(service).cloned())
+            .unwrap_or_else(|| self.defaults.clone())
+    }
+
+    /// Checks `data` against the input message, removing unknown fields when
+    /// they are ignored for the service.
+    pub fn validate(
+        &self,
+        service: &str,
+        input: &MessageDescriptor,
+        data: &mut Value,
+    ) -> Result<(), RequestValidationError> {
+        validate_with(&self.options_for(service), input, data)
+    }
+}
+
+/// Same as `RequestValidator::validate` with explicit options.
+pub fn validate_with(
+    options: &ValidationOptions,
+    input: &MessageDescriptor,
+    data: &mut Value,
+) -> Result<(), RequestValidationError> {
+    let mut violations = Vec::new();
+    check_message(input, data, "", options, &mut violations);
+
+    if violations.is_empty() && options.enforce_rules {
+        // structurally valid, so the payload decodes
+        if let Ok(message) = DynamicMessage::deserialize(input.clone(), &*data) {
+            rules::check_message(&message, "", &mut violations);
+        }
+    }
+
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(RequestValidationError { violations })
+    }
+}
+
+pub(crate) fn pointer(path: &str, segment: &str) -> String {
+    format!("{}/{}", path, segment.replace('~', "~0").replace('/', "~1"))
+}
+
+fn check_message(
+    message: &MessageDescriptor,
+    value: &mut Value,
+    path: &str,
+    options: &ValidationOptions,
+    out: &mut Vec<FieldViolation>,
+) {
+    let Value::Object(object) = value else {
+        if !value.is_null() {
+            out.push(FieldViolation::expected(path, "object"));
+        }
+        return;
+    };
+
+    let mut unknown = Vec::new();
+    let mut oneofs_seen: HashMap<String, String> = HashMap::new();
+
+    for (key, field_value) in object.iter_mut() {
+        let field = message
+            .get_field_by_json_name(key)
+            .or_else(|| message.get_field_by_name(key));
+        let Some(field) = field else {
+            unknown.push(key.to_string());
+            continue;
+        };
+        let field_path = pointer(path, key);
+
+        if let Some(oneof) = field.containing_oneof()
+            && !oneof.is_synthetic()
+            && !field_value.is_null()
+            && let Some(other) = oneofs_seen.insert(oneof.name().to_string(), key.to_string())
+        {
+            out.push(FieldViolation::new(
+                &field_path,
+                format!(
+                    "only one of oneof {} may be set, {} is already set",
+                    oneof.name(),
+                    other
+                ),
+            ));
+        }
+
+        if field.is_map() {
+            check_map(&field.kind(), field_value, &field_path, options, out);
+        } else if field.is_list() {
+            match field_value {
+                Value::Array(items) => {
+                    for (i, item) in items.iter_mut().enumerate() {
+                        check_kind(
+                            &field.kind(),
+                            item,
+                            &pointer(&field_path, &i.to_string()),
+                            options,
+                            out,
+                        );
+                    }
+                }
+                Value::Null => {}
+                _ => out.push(FieldViolation::expected(&field_path, "array")),
+            }
+        } else {
+            check_kind(&field.kind(), field_value, &field_path, options, out);
+        }
+    }
+
+    for key in unknown {
+        if options.reject_unknown_fields {
+            out.push(FieldViolation::new(
+                &pointer(path, &key),
+                format!("unknown field for {}", message.full_name()),
+            ));
+        } else {
+            object.remove(&key);
+        }
+    }
+}
+
+fn check_map(
+    entry: &Kind,
+    value: &mut Value,
+    path: &str,
+    options: &ValidationOptions,
+    out: &mut Vec<FieldViolation>,
+) {
+    let Some(entry) = entry.as_message() else {
+        return;
+    };
+    let Value::Object(object) = value else {
+        if !value.is_null() {
+            out.push(FieldViolation::expected(path, "object"));
+        }
+        return;
+    };
+
+    let key_kind = entry.map_entry_key_field().kind();
+    let value_kind = entry.map_entry_value_field().kind();
+    for (key, item) in object.iter_mut() {
+        let item_path = pointer(path, key);
+        let key_valid = match key_kind {
+            Kind::Bool => key == "true" || key == "false",
+            Kind::String => true,
+            _ => key.parse::<i128>().is_ok(),
+        };
+        if !key_valid {
+            out.push(FieldViolation {
+                expected: Some(kind_name(&key_kind)),
+                ..FieldViolation::new(
+                    &item_path,
+                    format!("invalid map key, expected {}", kind_name(&key_kind)),
+                )
+            });
+        }
+        check_kind(&value_kind, item, &item_path, options, out);
+    }
+}
+
+fn check_kind(
+    kind: &Kind,
+    value: &mut Value,
+    path: &str,
+    options: &ValidationOptions,
+    out: &mut Vec<FieldViolation>,
+) {
+    // null means the default value for every field type
+    if value.is_null() {
+        return;
+    }
+
+    let valid = match kind {
+        Kind::Int32 | Kind::Sint32 | Kind::Sfixed32 => {
+            integer_in_range(value, i32::MIN as i128, i32::MAX as i128)
+        }
+        Kind::Uint32 | Kind::Fixed32 => integer_in_range(value, 0, u32::MAX as i128),
+        Kind::Int64 | Kind::Sint64 | Kind::Sfixed64 => {
+            integer_in_range(value, i64::MIN as i128, i64::MAX as i128)
+        }
+        Kind::Uint64 | Kind::Fixed64 => integer_in_range(value, 0, u64::MAX as i128),
+        Kind::Float | Kind::Double => match value {
+            Value::Number(_) => true,
+            Value::String(s) => {
+                matches!(s.as_str(), "NaN" | "Infinity" | "-Infinity") || s.parse::<f64>().is_ok()
+            }
+            _ => false,
+        },
+        Kind::Bool => value.is_boolean(),
+        Kind::String => value.is_string(),
+        Kind::Bytes => value.as_str().is_some_and(|s| {
+            s.bytes()
+                .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'+' | b'/' | b'=' | b'-' | b'_'))
+        }),
+        Kind::Enum(enum_desc) => {
+            let valid = match value {
+                Value::String(name) => enum_desc.get_value_by_name(name).is_some(),
+                Value::Number(_) => integer_in_range(value, i32::MIN as i128, i32::MAX as i128),
+                _ => false,
+            };
+            if !valid {
+                out.push(FieldViolation {
+                    expected: Some(format!("enum {}", enum_desc.full_name())),
+                    allowed: Some(enum_desc.values().map(|v| v.name().to_string()).collect()),
+                    ..FieldViolation::new(
+                        path,
+                        format!("invalid value for enum {}", enum_desc.full_name()),
+                    )
+                });
+            }
+            return;
+        }
+        Kind::Message(message) => {
+            check_well_known(message, value, path, options, out);
+            return;
+        }
+    };
+
+    if !valid {
+        out.push(FieldViolation::expected(path, &kind_name(kind)));
+    }
+}
+
+fn check_well_known(
+    message: &MessageDescriptor,
+    value: &mut Value,
+    path: &str,
+    options: &ValidationOptions,
+    out: &mut Vec<FieldViolation>,
+) {
+    let (valid, expected) = match message.full_name() {
+        "google.protobuf.Timestamp" => (
+            value
+                .as_str()
+                .is_some_and(|s| chrono::DateTime::parse_from_rfc3339(s).is_ok()),
+            "RFC 3339 timestamp string",
+        ),
+        "google.protobuf.Duration" => (
+            value.as_str().is_some_and(|s| {
+                s.strip_suffix('s')
+                    .is_some_and(|secs| secs.parse::<f64>().is_ok())
+            }),
+            "duration string such as \"1.5s\"",
+        ),
+        "google.protobuf.FieldMask" => (value.is_string(), "comma separated field paths"),
+        "google.protobuf.Struct" | "google.protobuf.Empty" => (value.is_object(), "object"),
+        "google.protobuf.ListValue" => (value.is_array(), "array"),
+        "google.protobuf.Value" => (true, "any JSON value"),
+        "google.protobuf.Any" => (
+            value.get("@type").is_some_and(|t| t.is_string()),
+            "object with an \"@type\" string",
+        ),
+        name if name.starts_with("google.protobuf.") && name.ends_with("Value") => {
+            // wrapper types are encoded as their wrapped scalar
+            if let Some(inner) = message.get_field_by_name("value") {
+                check_kind(&inner.kind(), value, path, options, out);
+            }
+            return;
+        }
+        _ => {
+            check_message(message, value, path, options, out);
+            return;
+        }
+    };
+
+    if !valid {
+        out.push(FieldViolation::expected(path, expected));
+    }
+}
+
+fn integer_in_range(value: &Value, min: i128, max: i128) -> bool {
+    let parsed = match value {
+        Value::Number(n) => n
+            .as_i64()
+            .map(i128::from)
+            .or_else(|| n.as_u64().map(i128::from))
+            .or_else(|| n.as_f64().filter(|f| f.fract() == 0.0).map(|f| f as i128)),
+        Value::String(s) => s.parse::<i128>().ok(),
+        _ => None,
+    };
+    parsed.is_some_and(|n| n >= min && n <= max)
+}
+
+pub(crate) fn kind_name(kind: &Kind) -> String {
+    match kind {
+        Kind::Double => "double".into(),
+        Kind::Float => "float".into(),
+        Kind::Int32 => "int32".into(),
+        Kind::Int64 => "int64 (number or string)".into(),
+        Kind::Uint32 => "uint32".into(),
+        Kind::Uint64 => "uint64 (number or string)".into(),
+        Kind::Sint32 => "sint32".into(),
+        Kind::Sint64 => "sint64 (number or string)".into(),
+        Kind::Fixed32 => "fixed32".into(),
+        Kind::Fixed64 => "fixed64 (number or string)".into(),
+        Kind::Sfixed32 => "sfixed32".into(),
+        Kind::Sfixed64 => "sfixed64 (number or string)".into(),
+        Kind::Bool => "boolean".into(),
+        Kind::String => "string".into(),
+        Kind::Bytes => "base64 encoded string".into(),
+        Kind::Message(message) => format!("message {}", message.full_name()),
+        Kind::Enum(enum_desc) => format!("enum {}", enum_desc.full_name()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_descriptors;
+    use serde_json::json;
+
+    const PROFILE_PROTO: &str = r#"
+        name: "profile.proto" package: "profile" syntax: "proto3"
+        dependency: "google/protobuf/timestamp.proto"
+        message_type {
+            name: "Profile"
+            field { name: "user_id" number: 1 type: TYPE_INT64 label: LABEL_OPTIONAL json_name: "userId" }
+            field { name: "addresses" number: 2 type: TYPE_MESSAGE type_name: ".profile.Address" label: LABEL_REPEATED json_name: "addresses" }
+            field { name: "labels" number: 3 type: TYPE_MESSAGE type_name: ".profile.Profile.LabelsEntry" label: LABEL_REPEATED json_name: "labels" }
+            field { name: "email" number: 4 type: TYPE_STRING label: LABEL_OPTIONAL json_name: "email" oneof_index: 0 }
+            field { name: "phone" number: 5 type: TYPE_STRING label: LABEL_OPTIONAL json_name: "phone" oneof_index: 0 }
+            field { name: "role" number: 6 type: TYPE_ENUM type_name: ".profile.Role" label: LABEL_OPTIONAL json_name: "role" }
+            field { name: "updated_at" number: 7 type: TYPE_MESSAGE type_name: ".google.protobuf.Timestamp" label: LABEL_OPTIONAL json_name: "updatedAt" }
+            nested_type {
+                name: "LabelsEntry"
+                field { name: "key" number: 1 type: TYPE_INT32 label: LABEL_OPTIONAL json_name: "key" }
+                field { name: "value" number: 2 type: TYPE_STRING label: LABEL_OPTIONAL json_name: "value" }
+                options { map_entry: true }
+            }
+            oneof_decl { name: "contact" }
+        }
+        message_type {
+            name: "Address"
+            field { name: "city" number: 1 type: TYPE_STRING label: LABEL_OPTIONAL json_name: "city" }
+        }
+        enum_type { name: "Role" value { name: "ROLE_UNSPECIFIED" number: 0 } value { name: "ROLE_ADMIN" number: 1 } }
+    "#;
+
+    fn profile() -> MessageDescriptor {
+        test_descriptors::pool(&[PROFILE_PROTO])
+            .get_message_by_name("profile.Profile")
+            .unwrap()
+    }
+
+    fn paths(result: Result<(), RequestValidationError>) -> Vec<String> {
+        result
+            .err()
+            .map(|e| e.violations.into_iter().map(|v| v.path).collect())
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn accepts_the_canonical_json_mapping() {
+        let mut data = json!({
+            "userId": "9007199254740993",
+            "addresses": [{ "city": "Pune" }],
+            "labels": { "1": "vip" },
+            "email": "ada@example.com",
+            "role": "ROLE_ADMIN",
+            "updatedAt": "2024-05-01T10:00:00Z",
+        });
+
+        let result = validate_with(&ValidationOptions::default(), &profile(), &mut data);
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn type_errors_point_at_the_offending_value() {
+        let mut data = json!({
+            "userId": true,
+            "addresses": [{ "city": "Pune" }, { "city": 7 }],
+            "labels": { "one": "vip" },
+            "role": "ROLE_OWNER",
+            "updatedAt": "yesterday",
+        });
+
+        let result = validate_with(&ValidationOptions::default(), &profile(), &mut data);
+
+        let mut found = paths(result);
+        found.sort();
+        assert_eq!(
+            found,
+            vec![
+                "/addresses/1/city",
+                "/labels/one",
+                "/role",
+                "/updatedAt",
+                "/userId"
+            ]
+        );
+    }
+
+    #[test]
+    fn only_one_field_of_a_oneof_may_be_set() {
+        let mut data = json!({ "email": "ada@example.com", "phone": "555" });
+
+        let result = validate_with(&ValidationOptions::default(), &profile(), &mut data);
+
+        let violations = result.unwrap_err().violations;
+        assert_eq!(violations.len(), 1);
+        assert!(
+            violations[0]
+                .message
+                .starts_with("only one of oneof contact")
+        );
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected_by_default() {
+        let mut data = json!({ "nickname": "ada", "addresses": [{ "zip": "411001" }] });
+
+        let result = validate_with(&ValidationOptions::default(), &profile(), &mut data);
+
+        let violations = result.unwrap_err().violations;
+        let found: Vec<(&str, &str)> = violations
+            .iter()
+            .map(|v| (v.path.as_str(), v.message.as_str()))
+            .collect();
+        assert!(found.contains(&("/nickname", "unknown field for profile.Profile")));
+        assert!(found.contains(&("/addresses/0/zip", "unknown field for profile.Address")));
+    }
+
+    #[test]
+    fn ignored_unknown_fields_are_dropped() {
+        let options = ValidationOptions {
+            reject_unknown_fields: false,
+            ..Default::default()
+        };
+        let mut data = json!({ "nickname": "ada", "addresses": [{ "city": "Pune", "zip": "1" }] });
+
+        let result = validate_with(&options, &profile(), &mut data);
+
+        assert!(result.is_ok());
+        assert_eq!(data, json!({ "addresses": [{ "city": "Pune" }] }));
+    }
+
+    #[test]
+    fn pointer_segments_are_escaped() {
+        assert_eq!(pointer("", "a/b"), "/a~1b");
+        assert_eq!(pointer("/x", "m~n"), "/x/m~0n");
+    }
+
+    #[test]
+    fn services_can_override_the_defaults() {
+        let validator = RequestValidator::default();
+        let lenient = ValidationOptions {
+            reject_unknown_fields: false,
+            enforce_rules: false,
+        };
+        validator.set_service_options("profile.Profiles", lenient.clone());
+
+        assert_eq!(validator.options_for("profile.Profiles"), lenient);
+        assert_eq!(
+            validator.options_for("other.Service"),
+            ValidationOptions::default()
+        );
+    }
+}
@@ -0,0 +1,119 @@
+mod common;
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use grpc_gateway::Gateway;
+use grpc_gateway::registry::model::AuthRefreshConfig;
+use grpc_gateway::registry::service_registry::RegistryTrait;
+use grpc_gateway::testing::MockServer;
+use reqwest::StatusCode;
+use serde_json::json;
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+async fn jwt_gateway(server: &MockServer, package: &str, expired_at: u64) -> Gateway {
+    server.respond(
+        &common::auth(package),
+        "Refresh",
+        json!({
+            "accessToken": "refreshed-token",
+            "refreshToken": "refresh-2",
+            "expiredAt": (now() + 3600).to_string(),
+        }),
+    );
+    let gateway = Gateway::new();
+    let refresh = AuthRefreshConfig {
+        service_name: common::auth(package),
+        method: "Refresh".to_string(),
+        header_name: "authorization".to_string(),
+        access_token: "initial-token".to_string(),
+        expired_at,
+        refresh_token: "refresh-1".to_string(),
+    };
+    gateway
+        .service_registry
+        .register(server.jwt_registration(&common::users(package), refresh))
+        .await
+        .unwrap();
+    gateway
+}
+
+#[tokio::test]
+async fn valid_token_is_sent_as_is() {
+    let server = MockServer::start(common::descriptors("auth_valid"))
+        .await
+        .unwrap();
+    let gateway = jwt_gateway(&server, "auth_valid", now() + 3600).await;
+    let refreshes = server
+        .calls_to(&common::auth("auth_valid"), "Refresh")
+        .len();
+
+    let response = gateway
+        .invoker(common::get_user("auth_valid", json!({ "id": "1" })))
+        .await;
+
+    assert_eq!(response.status_code, StatusCode::OK);
+    let call = &server.calls_to(&common::users("auth_valid"), "GetUser")[0];
+    assert_eq!(call.metadata["authorization"], "initial-token");
+    assert_eq!(
+        server
+            .calls_to(&common::auth("auth_valid"), "Refresh")
+            .len(),
+        refreshes
+    );
+}
+
+#[tokio::test]
+async fn expired_token_is_refreshed_before_the_call() {
+    let server = MockServer::start(common::descriptors("auth_expired"))
+        .await
+        .unwrap();
+    let gateway = jwt_gateway(&server, "auth_expired", now() - 1).await;
+    let refreshes = server
+        .calls_to(&common::auth("auth_expired"), "Refresh")
+        .len();
+
+    let response = gateway
+        .invoker(common::get_user("auth_expired", json!({ "id": "1" })))
+        .await;
+
+    assert_eq!(response.status_code, StatusCode::OK);
+    let call = &server.calls_to(&common::users("auth_expired"), "GetUser")[0];
+    assert_eq!(call.metadata["authorization"], "refreshed-token");
+    let refresh_calls = server.calls_to(&common::auth("auth_expired"), "Refresh");
+    assert_eq!(refresh_calls.len(), refreshes + 1);
+    assert_eq!(
+        refresh_calls[refreshes].message["refreshToken"],
+        "refresh-1"
+    );
+}
+
+#[tokio::test]
+async fn failed_refresh_fails_the_call() {
+    let server = MockServer::start(common::descriptors("auth_failed"))
+        .await
+        .unwrap();
+    let gateway = jwt_gateway(&server, "auth_failed", now() - 1).await;
+    server.fail(
+        &common::auth("auth_failed"),
+        "Refresh",
+        tonic::Code::Unauthenticated,
+        "refresh token revoked",
+    );
+
+    let response = gateway
+        .invoker(common::get_user("auth_failed", json!({ "id": "1" })))
+        .await;
+
+    assert!(!response.status_code.is_success());
+    assert!(
+        server
+            .calls_to(&common::users("auth_failed"), "GetUser")
+            .is_empty()
+    );
+}
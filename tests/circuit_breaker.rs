@@ -0,0 +1,111 @@
+mod common;
+
+use std::time::Duration;
+
+use grpc_gateway::circuitbreaker::breaker::{
+    CircuitBreaker, CircuitBreakerConfig, CircuitBreakerState,
+};
+use grpc_gateway::registry::service_registry::RegistryTrait;
+use grpc_gateway::testing::{MockServer, mock_gateway};
+use serde_json::json;
+use tonic::Code;
+
+const FAILURE_THRESHOLD: u32 = 5;
+
+fn config() -> CircuitBreakerConfig {
+    CircuitBreakerConfig {
+        failure_threshold: FAILURE_THRESHOLD,
+        recovery_timeout: Duration::from_millis(100),
+        half_open_max_calls: 2,
+    }
+}
+
+async fn fail(breaker: &CircuitBreaker) {
+    let _ = breaker
+        .call(|| async { Err::<(), _>(anyhow::anyhow!("backend down")) })
+        .await;
+}
+
+async fn succeed(breaker: &CircuitBreaker) -> bool {
+    breaker.call(|| async { Ok(()) }).await.is_ok()
+}
+
+async fn open(breaker: &CircuitBreaker) {
+    for _ in 0..FAILURE_THRESHOLD {
+        fail(breaker).await;
+    }
+    assert!(matches!(
+        breaker.state().await,
+        CircuitBreakerState::Open { .. }
+    ));
+}
+
+#[tokio::test]
+async fn opens_after_repeated_failures_and_rejects_calls() {
+    let breaker = CircuitBreaker::new(config());
+    open(&breaker).await;
+
+    assert!(!succeed(&breaker).await);
+}
+
+#[tokio::test]
+async fn closes_after_successful_trial_calls() {
+    let breaker = CircuitBreaker::new(config());
+    open(&breaker).await;
+    tokio::time::sleep(Duration::from_millis(150)).await;
+
+    assert!(succeed(&breaker).await);
+    assert_eq!(breaker.state().await, CircuitBreakerState::HalfOpen);
+    assert!(succeed(&breaker).await);
+    assert_eq!(breaker.state().await, CircuitBreakerState::Closed);
+}
+
+#[tokio::test]
+async fn reopens_when_a_trial_call_fails() {
+    let breaker = CircuitBreaker::new(config());
+    open(&breaker).await;
+    tokio::time::sleep(Duration::from_millis(150)).await;
+
+    fail(&breaker).await;
+
+    assert!(matches!(
+        breaker.state().await,
+        CircuitBreakerState::Open { .. }
+    ));
+    assert!(!succeed(&breaker).await);
+}
+
+#[tokio::test]
+async fn failing_backend_trips_the_service_breaker() {
+    let server = MockServer::start(common::descriptors("breaker_gateway"))
+        .await
+        .unwrap();
+    let service = common::users("breaker_gateway");
+    server.fail(&service, "GetUser", Code::Internal, "boom");
+    let gateway = mock_gateway(&server, &[&service]).await.unwrap();
+    let threshold = CircuitBreakerConfig::default().failure_threshold as usize;
+    for _ in 0..threshold * 2 {
+        gateway
+            .invoker(common::get_user("breaker_gateway", json!({})))
+            .await;
+    }
+
+    let breaker = gateway
+        .service_registry
+        .discover(service.to_string())
+        .unwrap()
+        .breaker
+        .unwrap();
+    assert!(matches!(
+        breaker.state().await,
+        CircuitBreakerState::Open { .. }
+    ));
+    let reached = server.calls().len();
+    assert!(reached <= threshold);
+
+    let rejected = gateway
+        .invoker(common::get_user("breaker_gateway", json!({})))
+        .await;
+    assert!(!rejected.status_code.is_success());
+    assert_eq!(server.calls().len(), reached);
+}
@@ -0,0 +1,133 @@
+//! Descriptors of the services the integration tests run against.
+
+#![allow(dead_code)]
+
+use grpc_gateway::utils::model::RequestType;
+use prost_types::field_descriptor_proto::{Label, Type};
+use prost_types::{
+    DescriptorProto, FieldDescriptorProto, FileDescriptorProto, FileDescriptorSet,
+    MethodDescriptorProto, ServiceDescriptorProto,
+};
+use serde_json::Value;
+
+fn field(name: &str, number: i32, kind: Type) -> FieldDescriptorProto {
+    FieldDescriptorProto {
+        name: Some(name.to_string()),
+        number: Some(number),
+        label: Some(Label::Optional as i32),
+        r#type: Some(kind as i32),
+        json_name: Some(json_name(name)),
+        ..Default::default()
+    }
+}
+
+fn json_name(name: &str) -> String {
+    let mut parts = name.split('_');
+    let mut json = parts.next().unwrap_or_default().to_string();
+    for part in parts {
+        let mut chars = part.chars();
+        if let Some(first) = chars.next() {
+            json.push(first.to_ascii_uppercase());
+            json.push_str(chars.as_str());
+        }
+    }
+    json
+}
+
+fn message(name: &str, fields: Vec<FieldDescriptorProto>) -> DescriptorProto {
+    DescriptorProto {
+        name: Some(name.to_string()),
+        field: fields,
+        ..Default::default()
+    }
+}
+
+fn method(
+    package: &str,
+    name: &str,
+    input: &str,
+    output: &str,
+    streaming: bool,
+) -> MethodDescriptorProto {
+    MethodDescriptorProto {
+        name: Some(name.to_string()),
+        input_type: Some(format!(".{}.{}", package, input)),
+        output_type: Some(format!(".{}.{}", package, output)),
+        server_streaming: Some(streaming),
+        ..Default::default()
+    }
+}
+
+/// `{package}.Users` with `GetUser` and the server streaming `ListUsers`,
+/// and `{package}.Auth` with `Refresh`. Tests use their own package since
+/// the service registry is shared by the whole test binary.
+pub fn descriptors(package: &str) -> FileDescriptorSet {
+    let file = FileDescriptorProto {
+        name: Some(format!("{}.proto", package)),
+        package: Some(package.to_string()),
+        syntax: Some("proto3".to_string()),
+        message_type: vec![
+            message("GetUserRequest", vec![field("id", 1, Type::String)]),
+            message(
+                "User",
+                vec![
+                    field("id", 1, Type::String),
+                    field("display_name", 2, Type::String),
+                    field("age", 3, Type::Int32),
+                ],
+            ),
+            message(
+                "RefreshRequest",
+                vec![field("refresh_token", 1, Type::String)],
+            ),
+            message(
+                "RefreshResponse",
+                vec![
+                    field("access_token", 1, Type::String),
+                    field("refresh_token", 2, Type::String),
+                    field("expired_at", 3, Type::String),
+                ],
+            ),
+        ],
+        service: vec![
+            ServiceDescriptorProto {
+                name: Some("Users".to_string()),
+                method: vec![
+                    method(package, "GetUser", "GetUserRequest", "User", false),
+                    method(package, "ListUsers", "GetUserRequest", "User", true),
+                ],
+                ..Default::default()
+            },
+            ServiceDescriptorProto {
+                name: Some("Auth".to_string()),
+                method: vec![method(
+                    package,
+                    "Refresh",
+                    "RefreshRequest",
+                    "RefreshResponse",
+                    false,
+                )],
+                ..Default::default()
+            },
+        ],
+        ..Default::default()
+    };
+    FileDescriptorSet { file: vec![file] }
+}
+
+pub fn users(package: &str) -> String {
+    format!("{}.Users", package)
+}
+
+pub fn auth(package: &str) -> String {
+    format!("{}.Auth", package)
+}
+
+/// A `GetUser` call to `{package}.Users`.
+pub fn get_user(package: &str, data: Value) -> RequestType {
+    RequestType {
+        service: users(package),
+        method: "GetUser".to_string(),
+        data,
+    }
+}
@@ -0,0 +1,366 @@
+mod common;
+
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use futures::StreamExt;
+use grpc_gateway::testing::{
+    MOCK_API_KEY, MOCK_API_KEY_HEADER, MockResponse, MockServer, mock_gateway,
+};
+use grpc_gateway::utils::model::{RequestContext, RequestType};
+use grpc_gateway::validation::ValidationOptions;
+use reqwest::StatusCode;
+use serde_json::{Value, json};
+use tonic::Code;
+
+#[tokio::test]
+async fn returns_the_backend_response() {
+    let server = MockServer::start(common::descriptors("inv_ok"))
+        .await
+        .unwrap();
+    server.respond(
+        &common::users("inv_ok"),
+        "GetUser",
+        json!({ "id": "42", "displayName": "Ada", "age": 36 }),
+    );
+    let gateway = mock_gateway(&server, &[&common::users("inv_ok")])
+        .await
+        .unwrap();
+
+    let response = gateway
+        .invoker(common::get_user("inv_ok", json!({ "id": "42" })))
+        .await;
+
+    assert_eq!(response.status_code, StatusCode::OK);
+    assert_eq!(
+        response.data,
+        Some(json!({ "id": "42", "displayName": "Ada", "age": 36 }))
+    );
+    let calls = server.calls_to(&common::users("inv_ok"), "GetUser");
+    assert_eq!(calls.len(), 1);
+    assert_eq!(calls[0].message, json!({ "id": "42" }));
+}
+
+#[tokio::test]
+async fn forwards_credentials_and_request_id() {
+    let server = MockServer::start(common::descriptors("inv_metadata"))
+        .await
+        .unwrap();
+    let gateway = mock_gateway(&server, &[&common::users("inv_metadata")])
+        .await
+        .unwrap();
+    let ctx = RequestContext {
+        headers: [("x-request-id".to_string(), "req-1".to_string())].into(),
+        ..Default::default()
+    };
+
+    let response = gateway
+        .invoke_with_context(common::get_user("inv_metadata", json!({})), ctx)
+        .await;
+
+    assert_eq!(response.status_code, StatusCode::OK);
+    assert_eq!(response.request_id.as_deref(), Some("req-1"));
+    let call = &server.calls()[0];
+    assert_eq!(
+        call.metadata.get(MOCK_API_KEY_HEADER),
+        Some(&MOCK_API_KEY.to_string())
+    );
+    assert_eq!(
+        call.metadata.get("x-request-id"),
+        Some(&"req-1".to_string())
+    );
+}
+
+#[tokio::test]
+async fn binary_bodies_are_passed_through_unchanged() {
+    let server = MockServer::start(common::descriptors("inv_binary"))
+        .await
+        .unwrap();
+    server.respond(
+        &common::users("inv_binary"),
+        "GetUser",
+        json!({ "id": "7", "age": 36 }),
+    );
+    let gateway = mock_gateway(&server, &[&common::users("inv_binary")])
+        .await
+        .unwrap();
+    // `id: "7"` followed by field 15, which the schema does not declare
+    let body = Bytes::from_static(&[0x0a, 0x01, b'7', 0x78, 0x01]);
+    let ctx = RequestContext {
+        protobuf_body: Some(body.clone()),
+        ..Default::default()
+    };
+
+    let response = gateway
+        .invoke_with_context(common::get_user("inv_binary", json!({ "id": "7" })), ctx)
+        .await;
+
+    assert_eq!(response.status_code, StatusCode::OK);
+    assert_eq!(server.calls()[0].body, body);
+    assert_eq!(
+        response.protobuf_body.as_deref(),
+        Some(&[0x0a, 0x01, b'7', 0x18, 0x24][..])
+    );
+    assert_eq!(response.data, Some(json!({ "id": "7", "age": 36 })));
+}
+
+#[tokio::test]
+async fn reports_backend_errors() {
+    let server = MockServer::start(common::descriptors("inv_error"))
+        .await
+        .unwrap();
+    server.fail(
+        &common::users("inv_error"),
+        "GetUser",
+        Code::NotFound,
+        "no such user",
+    );
+    let gateway = mock_gateway(&server, &[&common::users("inv_error")])
+        .await
+        .unwrap();
+
+    let response = gateway
+        .invoker(common::get_user("inv_error", json!({ "id": "7" })))
+        .await;
+
+    assert_eq!(response.status_code, StatusCode::BAD_REQUEST);
+    assert_eq!(response.grpc_code, Some(Code::NotFound));
+    assert!(response.message.contains("no such user"));
+}
+
+#[tokio::test]
+async fn unavailable_backend_is_a_503() {
+    let server = MockServer::start(common::descriptors("inv_unavailable"))
+        .await
+        .unwrap();
+    server.fail(
+        &common::users("inv_unavailable"),
+        "GetUser",
+        Code::Unavailable,
+        "draining",
+    );
+    let gateway = mock_gateway(&server, &[&common::users("inv_unavailable")])
+        .await
+        .unwrap();
+
+    let response = gateway
+        .invoker(common::get_user("inv_unavailable", json!({})))
+        .await;
+
+    assert_eq!(response.status_code, StatusCode::SERVICE_UNAVAILABLE);
+}
+
+#[tokio::test]
+async fn unregistered_service_is_rejected() {
+    let server = MockServer::start(common::descriptors("inv_unregistered"))
+        .await
+        .unwrap();
+    let gateway = mock_gateway(&server, &[]).await.unwrap();
+
+    let response = gateway
+        .invoker(common::get_user("inv_unregistered", json!({})))
+        .await;
+
+    assert_eq!(response.status_code, StatusCode::BAD_REQUEST);
+    assert!(server.calls().is_empty());
+}
+
+#[tokio::test]
+async fn invalid_requests_do_not_reach_the_backend() {
+    let server = MockServer::start(common::descriptors("inv_invalid"))
+        .await
+        .unwrap();
+    let gateway = mock_gateway(&server, &[&common::users("inv_invalid")])
+        .await
+        .unwrap();
+
+    let response = gateway
+        .invoker(common::get_user(
+            "inv_invalid",
+            json!({ "id": 42, "nickname": "x" }),
+        ))
+        .await;
+
+    assert_eq!(response.status_code, StatusCode::BAD_REQUEST);
+    let violations = response.data.unwrap()["violations"]
+        .as_array()
+        .unwrap()
+        .len();
+    assert_eq!(violations, 2);
+    assert!(server.calls().is_empty());
+}
+
+fn with_alt(alt: &str) -> RequestContext {
+    RequestContext {
+        query: [("$alt".to_string(), alt.to_string())].into(),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn requests_cannot_loosen_the_unknown_fields_policy() {
+    let server = MockServer::start(common::descriptors("inv_unknown_strict"))
+        .await
+        .unwrap();
+    let gateway = mock_gateway(&server, &[&common::users("inv_unknown_strict")])
+        .await
+        .unwrap();
+
+    let response = gateway
+        .invoke_with_context(
+            common::get_user("inv_unknown_strict", json!({ "id": "1", "nickname": "x" })),
+            with_alt("json;unknown=ignore"),
+        )
+        .await;
+
+    assert_eq!(response.status_code, StatusCode::BAD_REQUEST);
+    assert!(server.calls().is_empty());
+}
+
+#[tokio::test]
+async fn requests_can_tighten_the_unknown_fields_policy() {
+    let server = MockServer::start(common::descriptors("inv_unknown_loose"))
+        .await
+        .unwrap();
+    let gateway = mock_gateway(&server, &[&common::users("inv_unknown_loose")])
+        .await
+        .unwrap();
+    gateway.validator.set_service_options(
+        &common::users("inv_unknown_loose"),
+        ValidationOptions {
+            reject_unknown_fields: false,
+            enforce_rules: true,
+        },
+    );
+    let data = json!({ "id": "1", "nickname": "x" });
+
+    let dropped = gateway
+        .invoker(common::get_user("inv_unknown_loose", data.clone()))
+        .await;
+    let rejected = gateway
+        .invoke_with_context(
+            common::get_user("inv_unknown_loose", data),
+            with_alt("json;unknown=reject"),
+        )
+        .await;
+
+    assert_eq!(dropped.status_code, StatusCode::OK);
+    assert_eq!(rejected.status_code, StatusCode::BAD_REQUEST);
+    assert_eq!(server.calls().len(), 1);
+}
+
+#[tokio::test]
+async fn queued_responses_come_first() {
+    let server = MockServer::start(common::descriptors("inv_queue"))
+        .await
+        .unwrap();
+    let service = common::users("inv_queue");
+    server.respond(&service, "GetUser", json!({ "displayName": "standing" }));
+    server.enqueue(
+        &service,
+        "GetUser",
+        MockResponse::Message(json!({ "displayName": "first" })),
+    );
+    server.enqueue(
+        &service,
+        "GetUser",
+        MockResponse::error(Code::Internal, "second"),
+    );
+    let gateway = mock_gateway(&server, &[&service]).await.unwrap();
+
+    let first = gateway
+        .invoker(common::get_user("inv_queue", json!({})))
+        .await;
+    let second = gateway
+        .invoker(common::get_user("inv_queue", json!({})))
+        .await;
+    let third = gateway
+        .invoker(common::get_user("inv_queue", json!({})))
+        .await;
+
+    assert_eq!(first.data, Some(json!({ "displayName": "first" })));
+    assert_eq!(second.grpc_code, Some(Code::Internal));
+    assert_eq!(third.data, Some(json!({ "displayName": "standing" })));
+}
+
+#[tokio::test]
+async fn responses_can_depend_on_the_request() {
+    let server = MockServer::start(common::descriptors("inv_dynamic"))
+        .await
+        .unwrap();
+    let service = common::users("inv_dynamic");
+    server.respond_with(&service, "GetUser", |call| {
+        MockResponse::Message(json!({ "id": call.message["id"], "displayName": "echo" }))
+    });
+    let gateway = mock_gateway(&server, &[&service]).await.unwrap();
+
+    let response = gateway
+        .invoker(common::get_user("inv_dynamic", json!({ "id": "9" })))
+        .await;
+
+    assert_eq!(
+        response.data,
+        Some(json!({ "id": "9", "displayName": "echo" }))
+    );
+}
+
+#[tokio::test]
+async fn scripted_latency_delays_the_answer() {
+    let server = MockServer::start(common::descriptors("inv_latency"))
+        .await
+        .unwrap();
+    let service = common::users("inv_latency");
+    server.set_latency(&service, "GetUser", Duration::from_millis(200));
+    let gateway = mock_gateway(&server, &[&service]).await.unwrap();
+
+    let started_at = Instant::now();
+    let response = gateway
+        .invoker(common::get_user("inv_latency", json!({})))
+        .await;
+
+    assert_eq!(response.status_code, StatusCode::OK);
+    assert!(started_at.elapsed() >= Duration::from_millis(200));
+}
+
+#[tokio::test]
+async fn streams_server_streaming_responses() {
+    let server = MockServer::start(common::descriptors("inv_stream"))
+        .await
+        .unwrap();
+    let service = common::users("inv_stream");
+    server.respond_with(&service, "ListUsers", |_| {
+        MockResponse::Stream(vec![json!({ "id": "1" }), json!({ "id": "2" })])
+    });
+    let gateway = mock_gateway(&server, &[&service]).await.unwrap();
+    let req = RequestType {
+        service: service.to_string(),
+        method: "ListUsers".to_string(),
+        data: json!({}),
+    };
+
+    let stream = gateway
+        .invoke_stream(req, RequestContext::default())
+        .await
+        .unwrap();
+    let messages: Vec<Value> = stream.map(|item| item.unwrap()).collect().await;
+
+    assert_eq!(messages, vec![json!({ "id": "1" }), json!({ "id": "2" })]);
+}
+
+#[tokio::test]
+async fn an_empty_message_is_a_response() {
+    let server = MockServer::start(common::descriptors("inv_empty"))
+        .await
+        .unwrap();
+    let service = common::users("inv_empty");
+    // every field at its default encodes to a zero-length frame
+    server.respond(&service, "GetUser", json!({}));
+    let gateway = mock_gateway(&server, &[&service]).await.unwrap();
+
+    let response = gateway
+        .invoker(common::get_user("inv_empty", json!({})))
+        .await;
+
+    assert_eq!(response.status_code, StatusCode::OK);
+    assert_eq!(response.data, Some(json!({})));
+}
@@ -0,0 +1,117 @@
+mod common;
+
+use grpc_gateway::metrics::gateway_metrics;
+use grpc_gateway::testing::{MockServer, mock_gateway};
+use grpc_gateway::utils::model::{RequestContext, RequestType};
+use serde_json::json;
+
+// lines of the request counter carrying every given label
+fn request_series(labels: &[String]) -> Vec<String> {
+    gateway_metrics::gather()
+        .unwrap()
+        .lines()
+        .filter(|line| line.starts_with("grpc_gateway_requests_total{"))
+        .filter(|line| labels.iter().all(|label| line.contains(label.as_str())))
+        .map(str::to_string)
+        .collect()
+}
+
+// value of the in-flight gauge of one method
+fn in_flight(service: &str, method: &str) -> i64 {
+    let labels = [
+        format!("service=\"{}\"", service),
+        format!("method=\"{}\"", method),
+    ];
+    gateway_metrics::gather()
+        .unwrap()
+        .lines()
+        .filter(|line| line.starts_with("grpc_gateway_requests_in_flight{"))
+        .find(|line| labels.iter().all(|label| line.contains(label.as_str())))
+        .and_then(|line| line.rsplit(' ').next())
+        .and_then(|value| value.parse().ok())
+        .unwrap_or_default()
+}
+
+#[tokio::test]
+async fn known_calls_are_labelled_by_name() {
+    let server = MockServer::start(common::descriptors("metrics_known"))
+        .await
+        .unwrap();
+    let gateway = mock_gateway(&server, &[&common::users("metrics_known")])
+        .await
+        .unwrap();
+
+    gateway
+        .invoker(common::get_user("metrics_known", json!({})))
+        .await;
+
+    let series = request_series(&[
+        format!("service=\"{}\"", common::users("metrics_known")),
+        "method=\"GetUser\"".to_string(),
+    ]);
+    assert_eq!(series.len(), 1);
+}
+
+#[tokio::test]
+async fn unknown_names_are_collapsed() {
+    let server = MockServer::start(common::descriptors("metrics_unknown"))
+        .await
+        .unwrap();
+    let gateway = mock_gateway(&server, &[&common::users("metrics_unknown")])
+        .await
+        .unwrap();
+
+    gateway
+        .invoker(RequestType {
+            service: common::users("metrics_unknown"),
+            method: "NoSuchMethod".to_string(),
+            data: json!({}),
+        })
+        .await;
+    gateway
+        .invoker(RequestType {
+            service: "metrics_unknown.NoSuchService".to_string(),
+            method: "GetUser".to_string(),
+            data: json!({}),
+        })
+        .await;
+
+    let unknown_method = request_series(&[
+        format!("service=\"{}\"", common::users("metrics_unknown")),
+        "method=\"unknown\"".to_string(),
+    ]);
+    assert_eq!(unknown_method.len(), 1);
+    assert!(request_series(&["NoSuchMethod".to_string()]).is_empty());
+    assert!(request_series(&["NoSuchService".to_string()]).is_empty());
+    assert!(!request_series(&["service=\"unknown\"".to_string()]).is_empty());
+}
+
+#[tokio::test]
+async fn streams_are_counted_and_in_flight_until_dropped() {
+    let server = MockServer::start(common::descriptors("metrics_stream"))
+        .await
+        .unwrap();
+    let service = common::users("metrics_stream");
+    let gateway = mock_gateway(&server, &[&service]).await.unwrap();
+    let req = RequestType {
+        service: service.to_string(),
+        method: "ListUsers".to_string(),
+        data: json!({}),
+    };
+
+    let stream = gateway
+        .invoke_stream(req, RequestContext::default())
+        .await
+        .unwrap();
+    let open = in_flight(&service, "ListUsers");
+    drop(stream);
+
+    assert_eq!(open, 1);
+    assert_eq!(in_flight(&service, "ListUsers"), 0);
+    let series = request_series(&[
+        format!("service=\"{}\"", service),
+        "method=\"ListUsers\"".to_string(),
+        "grpc_code=\"Ok\"".to_string(),
+    ]);
+    assert_eq!(series.len(), 1);
+}
@@ -0,0 +1,193 @@
+mod common;
+
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use grpc_gateway::Gateway;
+use grpc_gateway::mock::mock_mode::{MOCK_HEADER, MockModeConfig};
+use grpc_gateway::utils::model::RequestType;
+use prost_reflect::DescriptorPool;
+use reqwest::StatusCode;
+use serde_json::{Value, json};
+use tonic::Code;
+
+fn fixtures(package: &str, fixture: Value) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("grpc_gateway_fixtures_{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(dir.join(common::users(package))).unwrap();
+    std::fs::write(
+        dir.join(common::users(package)).join("GetUser.json"),
+        fixture.to_string(),
+    )
+    .unwrap();
+    dir
+}
+
+fn descriptors(package: &str) -> DescriptorPool {
+    DescriptorPool::from_file_descriptor_set(common::descriptors(package)).unwrap()
+}
+
+#[tokio::test]
+async fn answers_from_a_fixture_without_a_backend() {
+    let gateway = Gateway::new();
+    gateway.mock.enable(
+        &common::users("mock_fixture"),
+        MockModeConfig {
+            fixtures_dir: Some(fixtures("mock_fixture", json!({ "displayName": "Ada" }))),
+            ..Default::default()
+        },
+    );
+
+    let response = gateway
+        .invoker(common::get_user("mock_fixture", json!({})))
+        .await;
+
+    assert_eq!(response.status_code, StatusCode::OK);
+    assert_eq!(response.data, Some(json!({ "displayName": "Ada" })));
+    assert_eq!(response.headers[MOCK_HEADER], "fixture");
+}
+
+#[tokio::test]
+async fn fixtures_cannot_be_read_from_outside_the_directory() {
+    let gateway = Gateway::new();
+    let dir = fixtures("mock_traversal", json!({ "displayName": "Ada" }));
+    std::fs::write(
+        dir.join("secret.json"),
+        json!({ "displayName": "secret" }).to_string(),
+    )
+    .unwrap();
+    gateway.mock.enable(
+        &common::users("mock_traversal"),
+        MockModeConfig {
+            fixtures_dir: Some(dir),
+            ..Default::default()
+        },
+    );
+
+    let response = gateway
+        .invoker(RequestType {
+            service: common::users("mock_traversal"),
+            method: "../secret".to_string(),
+            data: json!({}),
+        })
+        .await;
+
+    assert_eq!(response.status_code, StatusCode::BAD_REQUEST);
+    assert_eq!(response.grpc_code, Some(Code::InvalidArgument));
+    assert_eq!(response.data, None);
+}
+
+#[tokio::test]
+async fn fixture_rules_match_the_request() {
+    let gateway = Gateway::new();
+    let rules = json!([
+        { "match": { "id": "1" }, "response": { "displayName": "first" } },
+        { "match": { "id": "404" }, "error": { "code": "NOT_FOUND", "message": "no such user" } },
+        { "response": { "displayName": "anyone" } },
+    ]);
+    gateway.mock.enable(
+        &common::users("mock_rules"),
+        MockModeConfig {
+            fixtures_dir: Some(fixtures("mock_rules", rules)),
+            ..Default::default()
+        },
+    );
+
+    let first = gateway
+        .invoker(common::get_user("mock_rules", json!({ "id": "1" })))
+        .await;
+    let missing = gateway
+        .invoker(common::get_user("mock_rules", json!({ "id": "404" })))
+        .await;
+    let other = gateway
+        .invoker(common::get_user("mock_rules", json!({ "id": "2" })))
+        .await;
+
+    assert_eq!(first.data, Some(json!({ "displayName": "first" })));
+    assert_eq!(missing.status_code, StatusCode::BAD_REQUEST);
+    assert_eq!(missing.grpc_code, Some(Code::NotFound));
+    assert!(missing.message.contains("no such user"));
+    assert_eq!(other.data, Some(json!({ "displayName": "anyone" })));
+}
+
+#[tokio::test]
+async fn generates_a_sample_without_a_fixture() {
+    let gateway = Gateway::new();
+    gateway.mock.enable(
+        &common::users("mock_generated"),
+        MockModeConfig {
+            descriptors: Some(descriptors("mock_generated")),
+            ..Default::default()
+        },
+    );
+
+    let response = gateway
+        .invoker(common::get_user("mock_generated", json!({})))
+        .await;
+
+    assert_eq!(response.status_code, StatusCode::OK);
+    assert_eq!(response.headers[MOCK_HEADER], "generated");
+    assert_eq!(
+        response.data,
+        Some(json!({
+            "id": "00000000-0000-4000-8000-000000000001",
+            "displayName": "sample display_name",
+            "age": 1,
+        }))
+    );
+}
+
+#[tokio::test]
+async fn without_fixture_or_descriptors_the_method_is_unimplemented() {
+    let gateway = Gateway::new();
+    gateway
+        .mock
+        .enable(&common::users("mock_empty"), MockModeConfig::default());
+
+    let response = gateway
+        .invoker(common::get_user("mock_empty", json!({})))
+        .await;
+
+    assert_eq!(response.grpc_code, Some(Code::Unimplemented));
+}
+
+#[tokio::test]
+async fn injects_errors_and_latency() {
+    let gateway = Gateway::new();
+    gateway.mock.enable(
+        &common::users("mock_chaos"),
+        MockModeConfig {
+            descriptors: Some(descriptors("mock_chaos")),
+            latency: Duration::from_millis(100),
+            error_rate: 1.0,
+            ..Default::default()
+        },
+    );
+
+    let started_at = Instant::now();
+    let response = gateway
+        .invoker(common::get_user("mock_chaos", json!({})))
+        .await;
+
+    assert!(started_at.elapsed() >= Duration::from_millis(100));
+    assert_eq!(response.status_code, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(response.headers[MOCK_HEADER], "injected-error");
+}
+
+#[tokio::test]
+async fn disabling_mock_mode_goes_back_to_the_registry() {
+    let gateway = Gateway::new();
+    gateway.mock.enable(
+        &common::users("mock_disabled"),
+        MockModeConfig {
+            descriptors: Some(descriptors("mock_disabled")),
+            ..Default::default()
+        },
+    );
+    gateway.mock.disable(&common::users("mock_disabled"));
+
+    let response = gateway
+        .invoker(common::get_user("mock_disabled", json!({})))
+        .await;
+
+    assert!(response.message.contains("is not register"));
+}
@@ -0,0 +1,293 @@
+mod common;
+
+use std::path::PathBuf;
+
+use grpc_gateway::recording::recorder::{RecorderConfig, rotated};
+use grpc_gateway::recording::replay::{self, Difference, ReplayTarget};
+use grpc_gateway::testing::{MOCK_API_KEY_HEADER, MockServer, mock_gateway};
+use grpc_gateway::utils::model::RequestContext;
+use serde_json::json;
+
+fn recording_path() -> PathBuf {
+    std::env::temp_dir()
+        .join(format!("grpc_gateway_recording_{}", uuid::Uuid::new_v4()))
+        .join("traffic.jsonl")
+}
+
+#[tokio::test]
+async fn records_request_metadata_and_response() {
+    let server = MockServer::start(common::descriptors("rec_fields"))
+        .await
+        .unwrap();
+    server.respond(
+        &common::users("rec_fields"),
+        "GetUser",
+        json!({ "id": "7", "displayName": "Grace" }),
+    );
+    let gateway = mock_gateway(&server, &[&common::users("rec_fields")])
+        .await
+        .unwrap();
+    let path = recording_path();
+    gateway
+        .recorder
+        .enable(RecorderConfig {
+            path: path.clone(),
+            ..Default::default()
+        })
+        .unwrap();
+    let ctx = RequestContext {
+        headers: [("x-request-id".to_string(), "req-7".to_string())].into(),
+        ..Default::default()
+    };
+
+    gateway
+        .invoke_with_context(common::get_user("rec_fields", json!({ "id": "7" })), ctx)
+        .await;
+    gateway.recorder.disable();
+
+    let calls = replay::read_recording(&path).unwrap();
+    assert_eq!(calls.len(), 1);
+    let call = &calls[0];
+    assert_eq!(call.request_id.as_deref(), Some("req-7"));
+    assert_eq!(call.request, json!({ "id": "7" }));
+    assert_eq!(call.status, 200);
+    assert_eq!(call.grpc_code, "Ok");
+    assert_eq!(
+        call.response,
+        Some(json!({ "id": "7", "displayName": "Grace" }))
+    );
+    assert_eq!(
+        call.metadata.get("x-request-id"),
+        Some(&"req-7".to_string())
+    );
+    // credentials are attached after recording
+    assert!(!call.metadata.contains_key(MOCK_API_KEY_HEADER));
+    // field 1, length 1, "7"
+    assert_eq!(call.request_protobuf.as_deref(), Some("CgE3"));
+    assert_eq!(
+        call.format.as_deref(),
+        Some("json;defaults=false;names=json;enum=string;int64=string;any=resolve;unknown=reject")
+    );
+}
+
+#[tokio::test]
+async fn records_only_the_configured_services() {
+    let server = MockServer::start(common::descriptors("rec_filter"))
+        .await
+        .unwrap();
+    let gateway = mock_gateway(&server, &[&common::users("rec_filter")])
+        .await
+        .unwrap();
+    let path = recording_path();
+    gateway
+        .recorder
+        .enable(RecorderConfig {
+            path: path.clone(),
+            services: vec!["other.Service".to_string()],
+            ..Default::default()
+        })
+        .unwrap();
+
+    gateway
+        .invoker(common::get_user("rec_filter", json!({})))
+        .await;
+    gateway.recorder.disable();
+
+    assert!(replay::read_recording(&path).unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn rotates_the_recording_file() {
+    let server = MockServer::start(common::descriptors("rec_rotate"))
+        .await
+        .unwrap();
+    let gateway = mock_gateway(&server, &[&common::users("rec_rotate")])
+        .await
+        .unwrap();
+    let path = recording_path();
+    gateway
+        .recorder
+        .enable(RecorderConfig {
+            path: path.clone(),
+            max_file_bytes: 1,
+            max_files: 2,
+            ..Default::default()
+        })
+        .unwrap();
+
+    for id in ["1", "2", "3", "4"] {
+        gateway
+            .invoker(common::get_user("rec_rotate", json!({ "id": id })))
+            .await;
+    }
+    gateway.recorder.disable();
+
+    let current = replay::read_recording(&path).unwrap();
+    let previous = replay::read_recording(&rotated(&path, 1)).unwrap();
+    let oldest = replay::read_recording(&rotated(&path, 2)).unwrap();
+    assert_eq!(current[0].request, json!({ "id": "4" }));
+    assert_eq!(previous[0].request, json!({ "id": "3" }));
+    assert_eq!(oldest[0].request, json!({ "id": "2" }));
+    assert!(!rotated(&path, 3).exists());
+}
+
+#[tokio::test]
+async fn replays_against_the_backend_and_reports_differences() {
+    let server = MockServer::start(common::descriptors("rec_replay"))
+        .await
+        .unwrap();
+    server.respond(
+        &common::users("rec_replay"),
+        "GetUser",
+        json!({ "id": "1", "displayName": "Ada", "age": 36 }),
+    );
+    let gateway = mock_gateway(&server, &[&common::users("rec_replay")])
+        .await
+        .unwrap();
+    let path = recording_path();
+    gateway
+        .recorder
+        .enable(RecorderConfig {
+            path: path.clone(),
+            ..Default::default()
+        })
+        .unwrap();
+    gateway
+        .invoker(common::get_user("rec_replay", json!({ "id": "1" })))
+        .await;
+    gateway.recorder.disable();
+    let calls = replay::read_recording(&path).unwrap();
+    let target = ReplayTarget::Backend(server.endpoint());
+
+    let unchanged = replay::replay(&calls, &target).await;
+    server.respond(
+        &common::users("rec_replay"),
+        "GetUser",
+        json!({ "id": "1", "displayName": "Ada Lovelace", "age": 36 }),
+    );
+    let changed = replay::replay(&calls, &target).await;
+
+    assert!(unchanged[0].matches());
+    assert_eq!(changed[0].replayed_status, 200);
+    assert_eq!(
+        changed[0].differences,
+        vec![Difference {
+            path: "/displayName".to_string(),
+            recorded: Some(json!("Ada")),
+            replayed: Some(json!("Ada Lovelace")),
+        }]
+    );
+    let replayed = server.calls_to(&common::users("rec_replay"), "GetUser");
+    assert_eq!(replayed.len(), 3);
+    assert_eq!(replayed[2].message, json!({ "id": "1" }));
+}
+
+#[tokio::test]
+async fn replays_with_the_recorded_json_mapping() {
+    let server = MockServer::start(common::descriptors("rec_format"))
+        .await
+        .unwrap();
+    server.respond(
+        &common::users("rec_format"),
+        "GetUser",
+        json!({ "id": "1", "displayName": "Ada" }),
+    );
+    let gateway = mock_gateway(&server, &[&common::users("rec_format")])
+        .await
+        .unwrap();
+    let path = recording_path();
+    gateway
+        .recorder
+        .enable(RecorderConfig {
+            path: path.clone(),
+            ..Default::default()
+        })
+        .unwrap();
+    let ctx = RequestContext {
+        query: [("$alt".to_string(), "json;names=proto".to_string())].into(),
+        ..Default::default()
+    };
+    gateway
+        .invoke_with_context(common::get_user("rec_format", json!({ "id": "1" })), ctx)
+        .await;
+    gateway.recorder.disable();
+    let calls = replay::read_recording(&path).unwrap();
+
+    let results = replay::replay(&calls, &ReplayTarget::Backend(server.endpoint())).await;
+
+    assert_eq!(
+        calls[0].response,
+        Some(json!({ "id": "1", "display_name": "Ada" }))
+    );
+    assert!(results[0].matches(), "{:?}", results[0].differences);
+}
+
+#[tokio::test]
+async fn replays_projected_calls_with_their_field_mask() {
+    let server = MockServer::start(common::descriptors("rec_fields_mask"))
+        .await
+        .unwrap();
+    server.respond(
+        &common::users("rec_fields_mask"),
+        "GetUser",
+        json!({ "id": "1", "displayName": "Ada", "age": 36 }),
+    );
+    let gateway = mock_gateway(&server, &[&common::users("rec_fields_mask")])
+        .await
+        .unwrap();
+    let path = recording_path();
+    gateway
+        .recorder
+        .enable(RecorderConfig {
+            path: path.clone(),
+            ..Default::default()
+        })
+        .unwrap();
+    let ctx = RequestContext {
+        query: [("fields".to_string(), "displayName".to_string())].into(),
+        ..Default::default()
+    };
+    gateway
+        .invoke_with_context(
+            common::get_user("rec_fields_mask", json!({ "id": "1" })),
+            ctx,
+        )
+        .await;
+    gateway.recorder.disable();
+    let calls = replay::read_recording(&path).unwrap();
+
+    let results = replay::replay(&calls, &ReplayTarget::Backend(server.endpoint())).await;
+
+    assert_eq!(calls[0].fields.as_deref(), Some("displayName"));
+    assert_eq!(calls[0].response, Some(json!({ "displayName": "Ada" })));
+    assert!(results[0].matches(), "{:?}", results[0].differences);
+}
+
+#[test]
+fn diff_reports_missing_and_added_fields() {
+    let differences = replay::diff(
+        &json!({ "user": { "id": "1", "age": 36 }, "tags": ["a"] }),
+        &json!({ "user": { "id": "1", "name": "Ada" }, "tags": ["a", "b"] }),
+    );
+
+    assert_eq!(
+        differences,
+        vec![
+            Difference {
+                path: "/tags".to_string(),
+                recorded: Some(json!(["a"])),
+                replayed: Some(json!(["a", "b"])),
+            },
+            Difference {
+                path: "/user/age".to_string(),
+                recorded: Some(json!(36)),
+                replayed: None,
+            },
+            Difference {
+                path: "/user/name".to_string(),
+                recorded: None,
+                replayed: Some(json!("Ada")),
+            },
+        ]
+    );
+}
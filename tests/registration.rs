@@ -0,0 +1,233 @@
+mod common;
+
+use grpc_gateway::Gateway;
+use grpc_gateway::registry::model::{AuthRefreshConfig, ServiceFilter};
+use grpc_gateway::registry::service_registry::RegistryTrait;
+use grpc_gateway::testing::{MockResponse, MockServer};
+use grpc_gateway::utils::model::RequestType;
+use reqwest::StatusCode;
+use serde_json::json;
+use tonic::Code;
+
+#[tokio::test]
+async fn registers_a_reachable_backend() {
+    let server = MockServer::start(common::descriptors("reg_ok"))
+        .await
+        .unwrap();
+    let gateway = Gateway::new();
+
+    let endpoint = gateway
+        .service_registry
+        .register(server.registration(&common::users("reg_ok")))
+        .await
+        .unwrap();
+
+    assert_eq!(endpoint, Some(server.endpoint()));
+    let config = gateway
+        .service_registry
+        .discover(common::users("reg_ok"))
+        .unwrap();
+    assert_eq!(config.endpoint, server.endpoint());
+    assert!(config.breaker.is_some());
+}
+
+#[tokio::test]
+async fn discovers_reflected_services() {
+    let server = MockServer::start(common::descriptors("reg_reflect"))
+        .await
+        .unwrap();
+    let gateway = Gateway::new();
+    gateway
+        .service_registry
+        .register(server.registration(&common::users("reg_reflect")))
+        .await
+        .unwrap();
+
+    let method = gateway
+        .method_descriptor(&common::users("reg_reflect"), "GetUser")
+        .await
+        .unwrap();
+    assert_eq!(method.input().name(), "GetUserRequest");
+    assert_eq!(method.output().name(), "User");
+    assert!(
+        gateway
+            .discovered_services()
+            .await
+            .iter()
+            .any(|s| s.full_name() == common::users("reg_reflect"))
+    );
+}
+
+#[tokio::test]
+async fn unknown_service_is_not_discovered() {
+    let gateway = Gateway::new();
+    assert!(
+        gateway
+            .service_registry
+            .discover(common::users("reg_unknown"))
+            .is_none()
+    );
+}
+
+#[tokio::test]
+async fn jwt_registration_checks_the_refresh_method() {
+    let server = MockServer::start(common::descriptors("reg_jwt"))
+        .await
+        .unwrap();
+    server.fail(
+        &common::auth("reg_jwt"),
+        "Refresh",
+        Code::Unauthenticated,
+        "bad refresh token",
+    );
+    let gateway = Gateway::new();
+
+    let refresh = AuthRefreshConfig {
+        service_name: common::auth("reg_jwt"),
+        method: "Refresh".to_string(),
+        header_name: "authorization".to_string(),
+        access_token: "expired".to_string(),
+        expired_at: 0,
+        refresh_token: "refresh-1".to_string(),
+    };
+    let registered = gateway
+        .service_registry
+        .register(server.jwt_registration(&common::users("reg_jwt"), refresh.clone()))
+        .await;
+    assert!(registered.is_err());
+    assert!(
+        gateway
+            .service_registry
+            .discover(common::users("reg_jwt"))
+            .is_none()
+    );
+
+    server.enqueue(
+        &common::auth("reg_jwt"),
+        "Refresh",
+        MockResponse::Message(json!({
+            "accessToken": "access-2",
+            "refreshToken": "refresh-2",
+            "expiredAt": "4102444800",
+        })),
+    );
+    gateway
+        .service_registry
+        .register(server.jwt_registration(&common::users("reg_jwt"), refresh))
+        .await
+        .unwrap();
+    let calls = server.calls_to(&common::auth("reg_jwt"), "Refresh");
+    assert_eq!(calls.len(), 2);
+    assert_eq!(calls[1].message["refreshToken"], "refresh-1");
+}
+
+#[tokio::test]
+async fn jwt_registration_requires_a_refresh_method() {
+    let server = MockServer::start(common::descriptors("reg_jwt_missing"))
+        .await
+        .unwrap();
+    let refresh = AuthRefreshConfig {
+        service_name: String::new(),
+        method: String::new(),
+        header_name: "authorization".to_string(),
+        access_token: "token".to_string(),
+        expired_at: 0,
+        refresh_token: String::new(),
+    };
+
+    let registered = Gateway::new()
+        .service_registry
+        .register(server.jwt_registration(&common::users("reg_jwt_missing"), refresh))
+        .await;
+    assert!(registered.is_err());
+}
+
+#[tokio::test]
+async fn one_registration_claims_every_reflected_service() {
+    let server = MockServer::start(common::descriptors("reg_all"))
+        .await
+        .unwrap();
+    let gateway = Gateway::new();
+
+    let registered = gateway
+        .service_registry
+        .register_services(server.registration(""), ServiceFilter::All)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        registered,
+        vec![common::auth("reg_all"), common::users("reg_all")]
+    );
+    let users = gateway
+        .service_registry
+        .discover(common::users("reg_all"))
+        .unwrap();
+    let auth = gateway
+        .service_registry
+        .discover(common::auth("reg_all"))
+        .unwrap();
+    assert_eq!(users.endpoint, server.endpoint());
+    assert_eq!(auth.endpoint, server.endpoint());
+    assert_eq!(auth.service_name, common::auth("reg_all"));
+    let response = gateway
+        .invoker(RequestType {
+            service: common::auth("reg_all"),
+            method: "Refresh".to_string(),
+            data: json!({}),
+        })
+        .await;
+    assert_eq!(response.status_code, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn a_filter_limits_the_claimed_services() {
+    let server = MockServer::start(common::descriptors("reg_filter"))
+        .await
+        .unwrap();
+    let gateway = Gateway::new();
+
+    let registered = gateway
+        .service_registry
+        .register_services(
+            server.registration(""),
+            ServiceFilter::Names(vec![common::users("reg_filter")]),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(registered, vec![common::users("reg_filter")]);
+    assert!(
+        gateway
+            .service_registry
+            .discover(common::auth("reg_filter"))
+            .is_none()
+    );
+}
+
+#[tokio::test]
+async fn a_filter_matching_nothing_is_rejected() {
+    let server = MockServer::start(common::descriptors("reg_none"))
+        .await
+        .unwrap();
+
+    let registered = Gateway::new()
+        .service_registry
+        .register_services(
+            server.registration(""),
+            ServiceFilter::Packages(vec!["reg_other".to_string()]),
+        )
+        .await;
+
+    assert!(registered.is_err());
+}
+
+#[test]
+fn package_filters_match_whole_segments() {
+    let filter = ServiceFilter::Packages(vec!["users".to_string()]);
+
+    assert!(filter.matches("users.UserService"));
+    assert!(filter.matches("users.v1.AdminService"));
+    assert!(!filter.matches("usersx.UserService"));
+    assert!(!ServiceFilter::All.matches("grpc.health.v1.Health"));
+}
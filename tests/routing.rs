@@ -0,0 +1,132 @@
+mod common;
+
+use grpc_gateway::metrics::gateway_metrics;
+use grpc_gateway::registry::service_registry::RegistryTrait;
+use grpc_gateway::routing::table::RoutingTable;
+use grpc_gateway::testing::{MockServer, mock_gateway};
+use reqwest::StatusCode;
+use serde_json::json;
+
+#[tokio::test]
+async fn an_alias_calls_the_renamed_service() {
+    let server = MockServer::start(common::descriptors("route_alias"))
+        .await
+        .unwrap();
+    server.respond(
+        &common::users("route_alias"),
+        "GetUser",
+        json!({ "displayName": "Ada" }),
+    );
+    let gateway = mock_gateway(&server, &[&common::users("route_alias")])
+        .await
+        .unwrap();
+    gateway
+        .routing
+        .add_alias("legacy.Users", &common::users("route_alias"));
+
+    let response = gateway
+        .invoker(common::get_user("legacy", json!({ "id": "1" })))
+        .await;
+
+    assert_eq!(response.status_code, StatusCode::OK);
+    assert_eq!(response.data, Some(json!({ "displayName": "Ada" })));
+    assert_eq!(server.calls()[0].service, common::users("route_alias"));
+    assert!(
+        gateway
+            .method_descriptor("legacy.Users", "GetUser")
+            .await
+            .is_some()
+    );
+}
+
+#[tokio::test]
+async fn a_method_route_uses_another_backend() {
+    let primary = MockServer::start(common::descriptors("route_method"))
+        .await
+        .unwrap();
+    let other = MockServer::start(common::descriptors("route_method"))
+        .await
+        .unwrap();
+    other.respond(
+        &common::users("route_method"),
+        "GetUser",
+        json!({ "displayName": "from other" }),
+    );
+    let gateway = mock_gateway(&primary, &[&common::users("route_method")])
+        .await
+        .unwrap();
+    // the other process is registered under the service it is known for
+    let backend = common::auth("route_method");
+    gateway
+        .service_registry
+        .register(other.registration(&backend))
+        .await
+        .unwrap();
+    gateway
+        .routing
+        .route_method(&common::users("route_method"), "GetUser", &backend);
+
+    let routed = gateway
+        .invoker(common::get_user("route_method", json!({ "id": "1" })))
+        .await;
+    gateway
+        .routing
+        .remove_method_route(&common::users("route_method"), "GetUser");
+    let unrouted = gateway
+        .invoker(common::get_user("route_method", json!({ "id": "1" })))
+        .await;
+
+    assert_eq!(routed.data, Some(json!({ "displayName": "from other" })));
+    assert_eq!(other.calls()[0].service, common::users("route_method"));
+    assert_eq!(unrouted.status_code, StatusCode::OK);
+    assert_eq!(primary.calls().len(), 1);
+    // labelled with the called service rather than the routed backend
+    let breaker = format!(
+        "grpc_gateway_breaker_state{{service=\"{}\",version=\"\"}}",
+        common::users("route_method")
+    );
+    let metrics = gateway_metrics::gather().unwrap();
+    assert!(metrics.contains(&breaker));
+    assert!(!metrics.contains(&format!("service=\"{}\"", backend)));
+}
+
+#[tokio::test]
+async fn a_package_route_serves_unregistered_services() {
+    let server = MockServer::start(common::descriptors("route_package"))
+        .await
+        .unwrap();
+    let gateway = mock_gateway(&server, &[&common::auth("route_package")])
+        .await
+        .unwrap();
+
+    let before = gateway
+        .invoker(common::get_user("route_package", json!({ "id": "1" })))
+        .await;
+    gateway
+        .routing
+        .route_package("route_package", &common::auth("route_package"));
+    let after = gateway
+        .invoker(common::get_user("route_package", json!({ "id": "1" })))
+        .await;
+
+    assert_eq!(before.status_code, StatusCode::BAD_REQUEST);
+    assert_eq!(after.status_code, StatusCode::OK);
+    assert_eq!(server.calls()[0].service, common::users("route_package"));
+}
+
+#[test]
+fn package_routes_match_whole_segments_and_prefer_the_longest() {
+    let routing = RoutingTable::default();
+    routing.route_package("billing", "billing-backend");
+    routing.route_package("billing.v2.", "billing-v2-backend");
+
+    assert_eq!(
+        routing.package_backend("billing.Invoices").as_deref(),
+        Some("billing-backend")
+    );
+    assert_eq!(
+        routing.package_backend("billing.v2.Invoices").as_deref(),
+        Some("billing-v2-backend")
+    );
+    assert_eq!(routing.package_backend("billingx.Invoices"), None);
+}
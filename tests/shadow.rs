@@ -0,0 +1,246 @@
+mod common;
+
+use std::time::{Duration, Instant};
+
+use grpc_gateway::metrics::gateway_metrics;
+use grpc_gateway::shadow::mirror::{SHADOW_HEADER, ShadowConfig};
+use grpc_gateway::testing::{MOCK_API_KEY, MOCK_API_KEY_HEADER, MockServer, mock_gateway};
+use reqwest::StatusCode;
+use serde_json::{Value, json};
+use tonic::Code;
+
+// value of the shadow counter for one service and result
+fn shadow_count(package: &str, result: &str) -> u64 {
+    let service = format!("service=\"{}\"", common::users(package));
+    let result = format!("result=\"{}\"", result);
+    gateway_metrics::gather()
+        .unwrap()
+        .lines()
+        .find(|line| {
+            line.starts_with("grpc_gateway_shadow_requests_total{")
+                && line.contains(&service)
+                && line.contains(&result)
+        })
+        .and_then(|line| line.rsplit(' ').next())
+        .and_then(|value| value.parse().ok())
+        .unwrap_or_default()
+}
+
+async fn wait_for_shadow(package: &str, result: &str) {
+    let deadline = Instant::now() + Duration::from_secs(2);
+    while shadow_count(package, result) == 0 && Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+}
+
+async fn servers(package: &str, primary: Value, shadow: Value) -> (MockServer, MockServer) {
+    let primary_server = MockServer::start(common::descriptors(package))
+        .await
+        .unwrap();
+    let shadow_server = MockServer::start(common::descriptors(package))
+        .await
+        .unwrap();
+    primary_server.respond(&common::users(package), "GetUser", primary);
+    shadow_server.respond(&common::users(package), "GetUser", shadow);
+    (primary_server, shadow_server)
+}
+
+#[tokio::test]
+async fn mirrors_calls_and_answers_from_the_primary() {
+    let (primary, shadow) = servers(
+        "shadow_mirror",
+        json!({ "id": "1", "displayName": "Ada" }),
+        json!({ "id": "1", "displayName": "Ada Lovelace" }),
+    )
+    .await;
+    let gateway = mock_gateway(&primary, &[&common::users("shadow_mirror")])
+        .await
+        .unwrap();
+    gateway.shadow.enable(
+        &common::users("shadow_mirror"),
+        ShadowConfig::new(&shadow.endpoint()),
+    );
+
+    let response = gateway
+        .invoker(common::get_user("shadow_mirror", json!({ "id": "1" })))
+        .await;
+
+    assert_eq!(response.status_code, StatusCode::OK);
+    assert_eq!(
+        response.data,
+        Some(json!({ "id": "1", "displayName": "Ada" }))
+    );
+    wait_for_shadow("shadow_mirror", "body_mismatch").await;
+    assert_eq!(shadow_count("shadow_mirror", "body_mismatch"), 1);
+    let mirrored = &shadow.calls()[0];
+    assert_eq!(mirrored.message, json!({ "id": "1" }));
+    assert_eq!(
+        mirrored.metadata.get(SHADOW_HEADER),
+        Some(&"true".to_string())
+    );
+    // credentials are only forwarded when asked for
+    assert!(!mirrored.metadata.contains_key(MOCK_API_KEY_HEADER));
+}
+
+#[tokio::test]
+async fn forwards_credentials_when_asked() {
+    let (primary, shadow) = servers("shadow_credentials", json!({}), json!({})).await;
+    let gateway = mock_gateway(&primary, &[&common::users("shadow_credentials")])
+        .await
+        .unwrap();
+    gateway.shadow.enable(
+        &common::users("shadow_credentials"),
+        ShadowConfig {
+            forward_credentials: true,
+            ..ShadowConfig::new(&shadow.endpoint())
+        },
+    );
+
+    gateway
+        .invoker(common::get_user("shadow_credentials", json!({ "id": "1" })))
+        .await;
+
+    wait_for_shadow("shadow_credentials", "match").await;
+    assert_eq!(
+        shadow.calls()[0].metadata.get(MOCK_API_KEY_HEADER),
+        Some(&MOCK_API_KEY.to_string())
+    );
+}
+
+#[tokio::test]
+async fn ignored_fields_are_left_out_of_the_comparison() {
+    let (primary, shadow) = servers(
+        "shadow_ignored",
+        json!({ "id": "1", "displayName": "Ada" }),
+        json!({ "id": "2", "displayName": "Ada" }),
+    )
+    .await;
+    let gateway = mock_gateway(&primary, &[&common::users("shadow_ignored")])
+        .await
+        .unwrap();
+    gateway.shadow.enable(
+        &common::users("shadow_ignored"),
+        ShadowConfig {
+            ignored_fields: vec!["/id".to_string()],
+            ..ShadowConfig::new(&shadow.endpoint())
+        },
+    );
+
+    gateway
+        .invoker(common::get_user("shadow_ignored", json!({ "id": "1" })))
+        .await;
+
+    wait_for_shadow("shadow_ignored", "match").await;
+    assert_eq!(shadow_count("shadow_ignored", "match"), 1);
+    assert_eq!(shadow_count("shadow_ignored", "body_mismatch"), 0);
+}
+
+#[tokio::test]
+async fn reports_a_different_status() {
+    let (primary, shadow) = servers("shadow_status", json!({ "id": "1" }), json!({})).await;
+    shadow.fail(
+        &common::users("shadow_status"),
+        "GetUser",
+        Code::NotFound,
+        "missing",
+    );
+    let gateway = mock_gateway(&primary, &[&common::users("shadow_status")])
+        .await
+        .unwrap();
+    gateway.shadow.enable(
+        &common::users("shadow_status"),
+        ShadowConfig::new(&shadow.endpoint()),
+    );
+
+    let response = gateway
+        .invoker(common::get_user("shadow_status", json!({ "id": "1" })))
+        .await;
+
+    assert_eq!(response.status_code, StatusCode::OK);
+    wait_for_shadow("shadow_status", "status_mismatch").await;
+    assert_eq!(shadow_count("shadow_status", "status_mismatch"), 1);
+}
+
+#[tokio::test]
+async fn a_slow_shadow_does_not_delay_the_client() {
+    let (primary, shadow) = servers("shadow_slow", json!({}), json!({})).await;
+    shadow.set_latency(
+        &common::users("shadow_slow"),
+        "GetUser",
+        Duration::from_millis(500),
+    );
+    let gateway = mock_gateway(&primary, &[&common::users("shadow_slow")])
+        .await
+        .unwrap();
+    gateway.shadow.enable(
+        &common::users("shadow_slow"),
+        ShadowConfig {
+            timeout: Duration::from_millis(100),
+            ..ShadowConfig::new(&shadow.endpoint())
+        },
+    );
+
+    let started_at = Instant::now();
+    let response = gateway
+        .invoker(common::get_user("shadow_slow", json!({ "id": "1" })))
+        .await;
+
+    assert_eq!(response.status_code, StatusCode::OK);
+    assert!(started_at.elapsed() < Duration::from_millis(400));
+    wait_for_shadow("shadow_slow", "error").await;
+    assert_eq!(shadow_count("shadow_slow", "error"), 1);
+}
+
+#[tokio::test]
+async fn calls_beyond_the_in_flight_cap_are_skipped() {
+    let (primary, shadow) = servers("shadow_cap", json!({}), json!({})).await;
+    shadow.set_latency(
+        &common::users("shadow_cap"),
+        "GetUser",
+        Duration::from_millis(300),
+    );
+    let gateway = mock_gateway(&primary, &[&common::users("shadow_cap")])
+        .await
+        .unwrap();
+    gateway.shadow.enable(
+        &common::users("shadow_cap"),
+        ShadowConfig {
+            max_in_flight: 1,
+            ..ShadowConfig::new(&shadow.endpoint())
+        },
+    );
+
+    for _ in 0..3 {
+        let response = gateway
+            .invoker(common::get_user("shadow_cap", json!({ "id": "1" })))
+            .await;
+        assert_eq!(response.status_code, StatusCode::OK);
+    }
+
+    assert_eq!(shadow_count("shadow_cap", "skipped"), 2);
+    wait_for_shadow("shadow_cap", "match").await;
+    assert_eq!(shadow_count("shadow_cap", "match"), 1);
+    assert_eq!(shadow.calls().len(), 1);
+}
+
+#[tokio::test]
+async fn unsampled_calls_are_not_mirrored() {
+    let (primary, shadow) = servers("shadow_unsampled", json!({}), json!({})).await;
+    let gateway = mock_gateway(&primary, &[&common::users("shadow_unsampled")])
+        .await
+        .unwrap();
+    gateway.shadow.enable(
+        &common::users("shadow_unsampled"),
+        ShadowConfig {
+            sample_rate: 0.0,
+            ..ShadowConfig::new(&shadow.endpoint())
+        },
+    );
+
+    gateway
+        .invoker(common::get_user("shadow_unsampled", json!({ "id": "1" })))
+        .await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    assert!(shadow.calls().is_empty());
+}
@@ -0,0 +1,123 @@
+mod common;
+
+use grpc_gateway::telemetry::exporter::init_in_memory;
+use grpc_gateway::testing::{MockServer, mock_gateway};
+use grpc_gateway::utils::model::{RequestContext, RequestType};
+use serde_json::json;
+
+const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+const SPAN_ID: &str = "00f067aa0ba902b7";
+
+// traceparent metadata the backend received for a call made with `headers`
+async fn forwarded_traceparent(package: &str, headers: &[(&str, &str)]) -> String {
+    let server = MockServer::start(common::descriptors(package))
+        .await
+        .unwrap();
+    let gateway = mock_gateway(&server, &[&common::users(package)])
+        .await
+        .unwrap();
+    let ctx = RequestContext {
+        headers: headers
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect(),
+        ..Default::default()
+    };
+
+    gateway
+        .invoke_with_context(common::get_user(package, json!({ "id": "1" })), ctx)
+        .await;
+
+    server.calls()[0].metadata["traceparent"].to_string()
+}
+
+// (trace id, parent span id, flags) of a W3C traceparent
+fn parts(traceparent: &str) -> (String, String, String) {
+    let parts: Vec<&str> = traceparent.split('-').collect();
+    assert_eq!(parts.len(), 4, "{}", traceparent);
+    assert_eq!(parts[0], "00");
+    (
+        parts[1].to_string(),
+        parts[2].to_string(),
+        parts[3].to_string(),
+    )
+}
+
+#[tokio::test]
+async fn continues_a_w3c_trace() {
+    let (_provider, exporter) = init_in_memory("tracing-test");
+    let traceparent = format!("00-{}-{}-01", TRACE_ID, SPAN_ID);
+
+    let forwarded =
+        forwarded_traceparent("trace_w3c", &[("traceparent", traceparent.as_str())]).await;
+
+    let (trace_id, span_id, flags) = parts(&forwarded);
+    assert_eq!(trace_id, TRACE_ID);
+    // the backend's parent is the gateway span, not the caller's
+    assert_ne!(span_id, SPAN_ID);
+    assert_eq!(flags, "01");
+    let spans = exporter.get_finished_spans().unwrap();
+    assert!(
+        spans
+            .iter()
+            .any(|span| span.span_context.span_id().to_string() == span_id)
+    );
+}
+
+#[tokio::test]
+async fn continues_a_b3_single_header_trace() {
+    let b3 = format!("{}-{}-1", TRACE_ID, SPAN_ID);
+
+    let forwarded = forwarded_traceparent("trace_b3_single", &[("b3", b3.as_str())]).await;
+
+    let (trace_id, _, flags) = parts(&forwarded);
+    assert_eq!(trace_id, TRACE_ID);
+    assert_eq!(flags, "01");
+}
+
+#[tokio::test]
+async fn pads_a_64_bit_b3_trace_id() {
+    let forwarded = forwarded_traceparent(
+        "trace_b3_multi",
+        &[
+            ("x-b3-traceid", "a3ce929d0e0e4736"),
+            ("x-b3-spanid", SPAN_ID),
+            ("x-b3-sampled", "0"),
+        ],
+    )
+    .await;
+
+    let (trace_id, _, flags) = parts(&forwarded);
+    assert_eq!(trace_id, "0000000000000000a3ce929d0e0e4736");
+    assert_eq!(flags, "00");
+}
+
+#[tokio::test]
+async fn streams_continue_the_caller_trace() {
+    let server = MockServer::start(common::descriptors("trace_stream"))
+        .await
+        .unwrap();
+    let gateway = mock_gateway(&server, &[&common::users("trace_stream")])
+        .await
+        .unwrap();
+    let ctx = RequestContext {
+        headers: [(
+            "traceparent".to_string(),
+            format!("00-{}-{}-01", TRACE_ID, SPAN_ID),
+        )]
+        .into(),
+        ..Default::default()
+    };
+    let req = RequestType {
+        service: common::users("trace_stream"),
+        method: "ListUsers".to_string(),
+        data: json!({}),
+    };
+
+    let stream = gateway.invoke_stream(req, ctx).await;
+
+    assert!(stream.is_ok());
+    let (trace_id, _, flags) = parts(&server.calls()[0].metadata["traceparent"]);
+    assert_eq!(trace_id, TRACE_ID);
+    assert_eq!(flags, "01");
+}
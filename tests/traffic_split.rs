@@ -0,0 +1,338 @@
+mod common;
+
+use std::collections::HashSet;
+
+use grpc_gateway::Gateway;
+use grpc_gateway::routing::traffic_split::{
+    BACKEND_VERSION_HEADER, BackendVersion, RequestKey, RollbackPolicy, SplitConfig, VersionRoute,
+};
+use grpc_gateway::testing::MockServer;
+use grpc_gateway::utils::model::RequestContext;
+use grpc_gateway::utils::response::Response;
+use serde_json::json;
+use tonic::Code;
+
+struct Versions {
+    gateway: Gateway,
+    stable: MockServer,
+    canary: MockServer,
+}
+
+// a gateway with `v1` and `v2` of the package's Users service registered
+async fn versions(package: &str, config: SplitConfig) -> Versions {
+    let service = common::users(package);
+    let stable = MockServer::start(common::descriptors(package))
+        .await
+        .unwrap();
+    let canary = MockServer::start(common::descriptors(package))
+        .await
+        .unwrap();
+    stable.respond(&service, "GetUser", json!({ "displayName": "v1" }));
+    canary.respond(&service, "GetUser", json!({ "displayName": "v2" }));
+
+    let gateway = Gateway::new();
+    gateway
+        .service_registry
+        .register_version(stable.registration(&service), "v1")
+        .await
+        .unwrap();
+    gateway
+        .service_registry
+        .register_version(canary.registration(&service), "v2")
+        .await
+        .unwrap();
+    gateway.traffic_split.enable(&service, config);
+    Versions {
+        gateway,
+        stable,
+        canary,
+    }
+}
+
+fn split(v1: u32, v2: u32) -> SplitConfig {
+    SplitConfig {
+        versions: vec![BackendVersion::new("v1", v1), BackendVersion::new("v2", v2)],
+        stable: "v1".to_string(),
+        routes: Vec::new(),
+        sticky_key: None,
+        rollback: None,
+    }
+}
+
+async fn get_user(gateway: &Gateway, package: &str, headers: &[(&str, &str)]) -> Response {
+    let req = common::get_user(package, json!({}));
+    let ctx = RequestContext {
+        headers: headers
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect(),
+        ..Default::default()
+    };
+    gateway.invoke_with_context(req, ctx).await
+}
+
+fn version(response: &Response) -> &str {
+    response.headers[BACKEND_VERSION_HEADER].as_str()
+}
+
+#[tokio::test]
+async fn routes_by_weight() {
+    let split = versions("split_weight", split(1, 0)).await;
+
+    let before = get_user(&split.gateway, "split_weight", &[]).await;
+    split
+        .gateway
+        .traffic_split
+        .set_weight(&common::users("split_weight"), "v1", 0);
+    split
+        .gateway
+        .traffic_split
+        .set_weight(&common::users("split_weight"), "v2", 1);
+    let after = get_user(&split.gateway, "split_weight", &[]).await;
+
+    assert_eq!(version(&before), "v1");
+    assert_eq!(before.data, Some(json!({ "displayName": "v1" })));
+    assert_eq!(version(&after), "v2");
+    assert_eq!(after.data, Some(json!({ "displayName": "v2" })));
+    assert_eq!(split.stable.calls().len(), 1);
+    assert_eq!(split.canary.calls().len(), 1);
+}
+
+#[tokio::test]
+async fn a_header_pins_the_canary() {
+    let split = versions(
+        "split_header",
+        SplitConfig {
+            routes: vec![VersionRoute::header("x-canary", "true", "v2")],
+            ..split(1, 1)
+        },
+    )
+    .await;
+
+    for _ in 0..10 {
+        let pinned = get_user(&split.gateway, "split_header", &[("x-canary", "true")]).await;
+        assert_eq!(version(&pinned), "v2");
+    }
+}
+
+#[tokio::test]
+async fn a_route_to_a_version_without_weight_is_skipped() {
+    let split = versions(
+        "split_route_weight",
+        SplitConfig {
+            routes: vec![VersionRoute::header("x-canary", "true", "v2")],
+            ..split(1, 0)
+        },
+    )
+    .await;
+
+    let pinned = get_user(
+        &split.gateway,
+        "split_route_weight",
+        &[("x-canary", "true")],
+    )
+    .await;
+
+    assert_eq!(version(&pinned), "v1");
+    assert!(split.canary.calls().is_empty());
+}
+
+#[tokio::test]
+async fn an_unregistered_version_falls_back_without_a_version_header() {
+    let split = versions(
+        "split_unregistered",
+        SplitConfig {
+            versions: vec![BackendVersion::new("v3", 1)],
+            ..split(0, 0)
+        },
+    )
+    .await;
+
+    let response = get_user(&split.gateway, "split_unregistered", &[]).await;
+
+    assert_eq!(response.data, Some(json!({ "displayName": "v1" })));
+    assert!(!response.headers.contains_key(BACKEND_VERSION_HEADER));
+}
+
+#[tokio::test]
+async fn a_sticky_cookie_keeps_the_same_version() {
+    let split = versions(
+        "split_sticky",
+        SplitConfig {
+            sticky_key: Some(RequestKey::Cookie("session".to_string())),
+            ..split(1, 1)
+        },
+    )
+    .await;
+
+    let mut seen = HashSet::new();
+    for _ in 0..10 {
+        let response = get_user(
+            &split.gateway,
+            "split_sticky",
+            &[("cookie", "theme=dark; session=abc123")],
+        )
+        .await;
+        seen.insert(version(&response).to_string());
+    }
+
+    assert_eq!(seen.len(), 1);
+}
+
+#[tokio::test]
+async fn sticky_keys_land_on_a_fixed_version() {
+    let split = versions(
+        "split_sticky_fixed",
+        SplitConfig {
+            sticky_key: Some(RequestKey::Header("x-user".to_string())),
+            ..split(1, 1)
+        },
+    )
+    .await;
+
+    // FNV-1a of the key modulo the total weight picks the version, so the
+    // same key maps the same way in every gateway process
+    let first = get_user(
+        &split.gateway,
+        "split_sticky_fixed",
+        &[("x-user", "user-1")],
+    )
+    .await;
+    let second = get_user(
+        &split.gateway,
+        "split_sticky_fixed",
+        &[("x-user", "user-2")],
+    )
+    .await;
+
+    assert_eq!(version(&first), "v1");
+    assert_eq!(version(&second), "v2");
+}
+
+#[tokio::test]
+async fn rolls_back_when_the_error_rate_is_exceeded() {
+    let split = versions(
+        "split_errors",
+        SplitConfig {
+            rollback: Some(RollbackPolicy {
+                error_rate: 0.5,
+                min_calls: 3,
+                on_breaker_open: false,
+                ..Default::default()
+            }),
+            ..split(0, 1)
+        },
+    )
+    .await;
+    split.canary.fail(
+        &common::users("split_errors"),
+        "GetUser",
+        Code::Internal,
+        "broken",
+    );
+
+    for _ in 0..3 {
+        let response = get_user(&split.gateway, "split_errors", &[]).await;
+        assert_eq!(version(&response), "v2");
+    }
+    let after = get_user(&split.gateway, "split_errors", &[]).await;
+
+    assert_eq!(version(&after), "v1");
+    assert_eq!(after.data, Some(json!({ "displayName": "v1" })));
+    let weights = split
+        .gateway
+        .traffic_split
+        .weights(&common::users("split_errors"));
+    assert_eq!(weights[1].weight, 0);
+}
+
+#[tokio::test]
+async fn rolls_back_when_the_breaker_opens() {
+    let split = versions(
+        "split_breaker",
+        SplitConfig {
+            rollback: Some(RollbackPolicy {
+                min_calls: 1000,
+                ..Default::default()
+            }),
+            ..split(0, 1)
+        },
+    )
+    .await;
+    split.canary.fail(
+        &common::users("split_breaker"),
+        "GetUser",
+        Code::Unavailable,
+        "down",
+    );
+
+    let mut versions = Vec::new();
+    for _ in 0..10 {
+        let response = get_user(&split.gateway, "split_breaker", &[]).await;
+        versions.push(version(&response).to_string());
+    }
+
+    assert_eq!(versions[0], "v2");
+    assert_eq!(versions.last().map(String::as_str), Some("v1"));
+}
+
+#[tokio::test]
+async fn client_errors_do_not_roll_back() {
+    let split = versions(
+        "split_client_errors",
+        SplitConfig {
+            rollback: Some(RollbackPolicy {
+                error_rate: 0.5,
+                min_calls: 3,
+                on_breaker_open: false,
+                ..Default::default()
+            }),
+            ..split(0, 1)
+        },
+    )
+    .await;
+    split.canary.fail(
+        &common::users("split_client_errors"),
+        "GetUser",
+        Code::NotFound,
+        "no such user",
+    );
+
+    for _ in 0..5 {
+        let response = get_user(&split.gateway, "split_client_errors", &[]).await;
+        assert_eq!(version(&response), "v2");
+    }
+}
+
+#[tokio::test]
+async fn descriptors_come_from_the_stable_version() {
+    let service = common::users("split_descriptor");
+    let stable = MockServer::start(common::descriptors("split_descriptor"))
+        .await
+        .unwrap();
+    // the canary does not expose the service yet
+    let canary = MockServer::start(common::descriptors("split_descriptor_next"))
+        .await
+        .unwrap();
+    let gateway = Gateway::new();
+    gateway
+        .service_registry
+        .register_version(stable.registration(&service), "v1")
+        .await
+        .unwrap();
+    gateway
+        .service_registry
+        .register_version(canary.registration(&service), "v2")
+        .await
+        .unwrap();
+    gateway.traffic_split.enable(&service, split(0, 1));
+
+    for _ in 0..5 {
+        assert!(
+            gateway
+                .method_descriptor(&service, "GetUser")
+                .await
+                .is_some()
+        );
+    }
+}